@@ -0,0 +1,34 @@
+//! Records build information for `zet --version --verbose`: the enabled cargo
+//! features, the target triple, and the git commit (when built from a git
+//! checkout).
+use std::env;
+use std::process::Command;
+
+fn main() {
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(var, _)| var.strip_prefix("CARGO_FEATURE_").map(str::to_owned))
+        .map(|feature| feature.to_lowercase().replace('_', "-"))
+        .filter(|feature| feature != "default")
+        .collect();
+    features.sort();
+    println!("cargo:rustc-env=ZET_BUILD_FEATURES={}", features.join(","));
+
+    let target = env::var("TARGET").unwrap_or_else(|_| "unknown".to_string());
+    println!("cargo:rustc-env=ZET_BUILD_TARGET={target}");
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map_or_else(|| "unknown".to_string(), |commit| commit.trim().to_string());
+    println!("cargo:rustc-env=ZET_BUILD_COMMIT={commit}");
+
+    println!("cargo:rerun-if-changed=build.rs");
+    for git_file in [".git/HEAD", ".git/refs"] {
+        if std::path::Path::new(git_file).exists() {
+            println!("cargo:rerun-if-changed={git_file}");
+        }
+    }
+}
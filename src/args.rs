@@ -1,61 +1,911 @@
 //! Code to parse the command line using `clap`, and definitions of the parsed result
 
+use crate::condition::{Assertion, Condition};
+use crate::expr::Expression;
 use crate::help;
-use crate::operations::LogType;
+use crate::interrupt::Interrupt;
+use crate::key::{self, Column, IgnorePattern, Key, KeyRegex, LinePart, Normalizer};
+use crate::operands::{Blank, ReadOptions, Skipped, Utf8Check};
+use crate::operations::{
+    CountAlign, CountFormat, CountOrder, LineFormat, Locations, LogType, Options, OutputFormat,
+    Threshold,
+};
+pub use crate::operations::{OpName, UnknownOpName};
+use crate::sample::Sample;
+use crate::serve::Endpoint;
+use crate::set::{Framing, Keep, Order};
 use crate::styles::ColorChoice;
-use clap::{Parser, ValueEnum};
-use std::path::PathBuf;
+use crate::walk::Recursion;
+use clap::{CommandFactory, Parser, ValueEnum};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::{collections::HashMap, ffi::OsString, num::NonZeroUsize, path::PathBuf};
 
 /// Returns the parsed command line: the `Args` return value's `op` field is the set operation
 /// desired, and the `files` field holds the files to take as operands.
 #[must_use]
 pub fn parsed() -> Args {
-    let parsed = CliArgs::parse();
+    // On Windows, cmd.exe and PowerShell leave wildcards for the program to
+    // expand; `wild` does that (and elsewhere just returns the arguments).
+    let parsed = CliArgs::parse_from(with_marked_operands(wild::args_os().collect()));
+    check_usage(&parsed);
+    check_selection_usage(&parsed);
+    check_count_usage(&parsed);
+    check_region_usage(&parsed);
+    check_state_usage(&parsed);
+    check_quiet_usage(&parsed);
+    check_stats_usage(&parsed);
+    let log_type = log_type(&parsed);
+    let count_distinct = count_distinct(&parsed);
+    let framing = framing(&parsed);
+    let key = key_for(&parsed);
+    // The options share one interrupt, so that a Ctrl-C stops both the engine
+    // and the readers
+    let interrupt = Interrupt::default();
+    let read_options = read_options(&parsed, framing.clone(), &interrupt);
+    let output = output_path(&parsed);
+    let output_fields = output_fields(&parsed);
+    let recursion = recursion(&parsed);
+    let count_order = count_order(&parsed);
+    let seed = seed(&parsed);
+    let output_format = output_format(&parsed);
     let cc = parsed.color.unwrap_or(ColorChoice::Auto);
     if parsed.help {
         help_and_exit(&cc);
     }
     if parsed.version {
-        println!("{}", help::version());
+        println!("{}", if parsed.verbose { help::verbose_version() } else { help::version() });
         exit_success();
     }
-    let Some(op) = parsed.command else { help_and_exit(&cc) };
-    let op = match op {
-        CliName::Help => help_and_exit(&cc),
+    let Some(command) = parsed.command else { help_and_exit(&cc) };
+    let Some(op) = op_for(command, parsed.files) else { help_and_exit(&cc) };
+    let (expression, paths) = expression_and_operands(command, op, &parsed.paths);
+    let line_format = line_format(parsed.shell_quote, output_format);
+
+    // `Options` is non-exhaustive, so we start from the defaults and fill in
+    // the fields the command line sets
+    let mut options = Options::new(op);
+    options.log_type = log_type;
+    options.interrupt = interrupt;
+    options.key = key;
+    options.framing = framing;
+    options.top = parsed.top;
+    options.approx = parsed.approx;
+    options.count_format = if parsed.si { CountFormat::Si } else { CountFormat::Full };
+    options.line_format = line_format;
+    options.output_format = output_format;
+    options.header_row = parsed.header_row;
+    options.in_at_least = parsed.in_at_least;
+    options.min_files = parsed.min_files;
+    options.max_files = parsed.max_files;
+    options.min_count = parsed.min_count;
+    options.max_count = parsed.max_count;
+    options.invert = parsed.invert;
+    options.grouped = parsed.grouped.then(Vec::new);
+    options.show_files = (parsed.show_files || parsed.with_filename).then(Vec::new);
+    options.count_per_file = parsed.count_per_file.then(Vec::new);
+    options.count_after = parsed.count_after;
+    options.count_align = parsed.count_align.map_or(CountAlign::Right, CountAlign::from);
+    options.with_filename = parsed.with_filename;
+    options.locations =
+        parsed.locate.then(|| Locations { limit: parsed.where_limit, names: vec![] });
+    options.counted_input = parsed.counted_input;
+    options.output_fields = output_fields;
+    options.count_distinct = count_distinct;
+    options.keep = parsed.keep.into();
+    options.sort = parsed.sort;
+    options.order = parsed.order.into();
+    options.count_order = count_order;
+    options.head = parsed.head;
+    options.sample = parsed.sample.map(|size| Sample { size, seed });
+    options.shuffle = parsed.shuffle.then_some(seed);
+
+    Args {
+        options,
+        read_options,
+        condition: condition_for(command),
+        assertions: assertions(
+            &parsed.assert_equal,
+            &parsed.assert_subset,
+            &parsed.assert_disjoint,
+        ),
+        fail_fast: parsed.fail_fast,
+        explain: parsed.verbose && condition_for(command).is_some(),
+        venn: command == CliName::Venn,
+        similarity: command == CliName::Similarity,
+        matrix: parsed.format == Some(CliFormat::Matrix),
+        comm: parsed.format == Some(CliFormat::Comm),
+        out_prefix: parsed.out_prefix,
+        output,
+        #[cfg(feature = "tui")]
+        tui: command == CliName::Tui,
+        serve: (command == CliName::Serve)
+            .then(|| parsed.socket.map_or(Endpoint::Stdio, Endpoint::Socket)),
+        state: parsed.state,
+        stream: parsed.stream,
+        hashed: parsed.hashed,
+        quiet: parsed.quiet,
+        summary: parsed.summary,
+        stats: parsed.stats.map(|_| parsed.stats_file.map_or(StatsTo::Stderr, StatsTo::File)),
+        partial_on_interrupt: parsed.partial_on_interrupt,
+        recursion,
+        expression,
+        paths,
+        literals: parsed.literal,
+    }
+}
+
+/// Which count, if any, the counting flags ask for
+fn log_type(parsed: &CliArgs) -> LogType {
+    if parsed.count_lines && parsed.count_files {
+        LogType::LinesAndFiles
+    } else if parsed.count_files {
+        LogType::Files
+    } else if parsed.count_lines {
+        LogType::Lines
+    } else if parsed.count_split {
+        LogType::Split
+    } else if parsed.count {
+        if parsed.files {
+            LogType::Files
+        } else {
+            LogType::Lines
+        }
+    } else {
+        LogType::None
+    }
+}
+
+/// The lines to leave out: blank ones with `--ignore-blank` (counting lines of
+/// whitespace as blank if we trim keys), comments with `--skip-comments`, and
+/// with `--skip-unmatched`, those the `--key-regex` doesn't match
+fn skipped(parsed: &CliArgs) -> Skipped {
+    let trims = parsed.trim || parsed.normalize;
+    Skipped {
+        blank: parsed.ignore_blank.then_some(if trims { Blank::Whitespace } else { Blank::Empty }),
+        comments: parsed.skip_comments.as_ref().map(|prefix| prefix.as_bytes().to_vec()),
+        unmatched: parsed.key_regex.clone().filter(|_| parsed.skip_unmatched),
+    }
+}
+
+/// How to divide the input into records: fixed-width records with
+/// `--record-bytes`, lines left undecoded with `--no-decode`, CSV records with
+/// `--csv`, records ending with NUL with `-z` or with the given delimiter with
+/// `--record-delimiter`, and otherwise lines
+fn framing(parsed: &CliArgs) -> Framing {
+    match (parsed.record_bytes, parsed.no_decode) {
+        (Some(width), _) => Framing::FixedWidth(width),
+        (None, true) => Framing::RawLines,
+        (None, false) if parsed.zero_terminated => Framing::Separated(b"\0".to_vec()),
+        (None, false) if parsed.record_delimiter.is_some() => {
+            Framing::Separated(parsed.record_delimiter.clone().unwrap_or_default())
+        }
+        (None, false) if parsed.csv => Framing::Csv,
+        (None, false) => Framing::Lines,
+    }
+}
+
+/// The set operation `command` performs (with `--files` if `files` is set), or
+/// `None` for the `help` command
+fn op_for(command: CliName, files: bool) -> Option<OpName> {
+    let op = match command {
+        CliName::Help => return None,
         CliName::Intersect => OpName::Intersect,
-        CliName::Union => OpName::Union,
+        // The `equal`, `is-subset`, `is-disjoint`, and `check` commands test
+        // conditions instead of performing an operation
+        CliName::Union
+        | CliName::Equal
+        | CliName::IsSubset
+        | CliName::IsDisjoint
+        | CliName::Check => OpName::Union,
         CliName::Diff => OpName::Diff,
+        CliName::Majority => OpName::Majority,
+        CliName::Top => OpName::Top,
+        CliName::Stats => OpName::Stats,
+        CliName::MergeCounts => OpName::MergeCounts,
+        // `venn`, `similarity`, and `tui` sort lines into regions instead of
+        // performing an operation
+        CliName::Venn | CliName::Similarity => OpName::Union,
+        #[cfg(feature = "tui")]
+        CliName::Tui => OpName::Union,
+        // `serve` loads a baseline and answers queries about it, and `expr`
+        // and `eval` evaluate an expression over the operands
+        CliName::Serve | CliName::Expr | CliName::Eval => OpName::Union,
         CliName::Single => {
-            if parsed.files {
+            if files {
                 OpName::SingleByFile
             } else {
                 OpName::Single
             }
         }
         CliName::Multiple => {
-            if parsed.files {
+            if files {
                 OpName::MultipleByFile
             } else {
                 OpName::Multiple
             }
         }
     };
+    Some(op)
+}
 
-    let log_type = if parsed.count_files {
-        LogType::Files
-    } else if parsed.count_lines {
-        LogType::Lines
-    } else if parsed.count {
-        if parsed.files {
-            LogType::Files
+/// The key of the `--count-distinct` field's values, which are compared like
+/// keys
+fn count_distinct(parsed: &CliArgs) -> Option<Key> {
+    parsed.count_distinct.map(|field| {
+        Key::fields(&[field.get()], parsed.field_delimiter.clone())
+            .normalized_by(&normalizers(parsed))
+    })
+}
+
+/// The key lines are compared by: the `--key` fields, the `--key-cmd` output,
+/// the `--key-regex` match, the `--json-key` value, the part of the line left
+/// by `--skip-fields`, `--skip-chars`, and `--check-chars`, or the whole line;
+/// without the `--ignore-pattern` matches, and normalized as asked. With
+/// `--csv`, `--key` columns may be named, to be looked up in each operand's
+/// header.
+fn key_for(parsed: &CliArgs) -> Key {
+    use clap::error::ErrorKind;
+    let fail = |message: &str| -> ! {
+        CliArgs::command().error(ErrorKind::ArgumentConflict, message).exit()
+    };
+    let named = parsed.key.iter().flatten().any(|column| matches!(column, Column::Name(_)));
+    if named && !parsed.csv {
+        fail("--key takes column names only with --csv; otherwise it takes field numbers");
+    }
+    let headerless = parsed.stream
+        || parsed.hashed
+        || parsed.approx.is_some()
+        || parsed.count_distinct.is_some()
+        || matches!(parsed.command, Some(CliName::MergeCounts | CliName::Serve));
+    if parsed.csv && headerless {
+        fail(
+            "the merge-counts and serve commands, --stream, --hashed, --approx, and \
+             --count-distinct can't skip each file's CSV header, so can't be used with --csv",
+        );
+    }
+    let normalizers = normalizers(parsed);
+    let edited = |key: Key| {
+        let key = parsed.ignore_pattern.iter().cloned().fold(key, Key::edited_by);
+        key.normalized_by(&normalizers)
+    };
+    if parsed.csv {
+        if let Some(columns) = &parsed.key {
+            return edited(Key::whole_line()).csv_columns(columns);
+        }
+    }
+    let part = LinePart {
+        skip_fields: parsed.skip_fields,
+        skip_bytes: parsed.skip_chars,
+        check_bytes: parsed.check_chars,
+    };
+    let key = match (&parsed.key, &parsed.key_cmd, &parsed.key_regex) {
+        (Some(columns), ..) => {
+            let numbers: Vec<_> = columns
+                .iter()
+                .filter_map(|column| match column {
+                    Column::Number(n) => Some(*n),
+                    Column::Name(_) => None,
+                })
+                .collect();
+            Key::fields(&numbers, parsed.field_delimiter.clone())
+        }
+        (None, Some(command), _) => Key::command(key::ShellCommand(command.clone())),
+        (None, None, Some(regex)) => Key::extracted_by(regex.clone()),
+        (None, None, None) if part != LinePart::default() => Key::extracted_by(part),
+        (None, None, None) => Key::whole_line(),
+    };
+    #[cfg(feature = "json")]
+    let key = parsed.json_key.clone().map_or(key, Key::extracted_by);
+    edited(key)
+}
+
+/// Where to write the output: the `--output` file, or with `--in-place`, the
+/// first operand
+fn output_path(parsed: &CliArgs) -> Option<PathBuf> {
+    let first = || parsed.paths.iter().find(|path| *path != "--").cloned();
+    parsed.output.clone().or_else(|| parsed.in_place.then(first).flatten())
+}
+
+/// The normalizers applied to keys: all of them for `--normalize`, trimming for
+/// `--trim`, and the `--ignore-case` one
+fn normalizers(parsed: &CliArgs) -> Vec<Normalizer> {
+    let mut normalizers = Vec::new();
+    if parsed.normalize {
+        normalizers.extend(Normalizer::ALL);
+    }
+    if parsed.trim {
+        normalizers.push(Normalizer::Trim);
+    }
+    normalizers.extend(parsed.ignore_case.map(Normalizer::from));
+    if parsed.numeric {
+        normalizers.push(Normalizer::Numeric);
+    }
+    normalizers
+}
+
+/// The operands of the `eval` command start with `+` or `-`, and the latter
+/// would be taken for flags. So every argument after the `eval` command is an
+/// operand: we put a `--` after it, to tell `clap` so. For other commands, a
+/// `--` separates operand groups, but `clap` swallows the first one; so we
+/// double it, keeping the second among the operands.
+fn with_marked_operands(mut args: Vec<OsString>) -> Vec<OsString> {
+    let command = command_position(&args);
+    if let Some(eval) = command.filter(|&position| args[position] == "eval") {
+        if args.get(eval + 1).map_or(true, |next| next != "--") {
+            args.insert(eval + 1, "--".into());
+        }
+    } else if let Some(separator) = args.iter().skip(1).position(|arg| arg == "--") {
+        args.insert(separator + 1, "--".into());
+    }
+    args
+}
+
+/// The position in `args` (which start with the program's name) of the command:
+/// the first argument that is neither a flag nor a flag's value
+fn command_position(args: &[OsString]) -> Option<usize> {
+    let cli = CliArgs::command();
+    let takes_value =
+        |arg: &clap::Arg| arg.get_action().takes_values() && !arg.is_require_equals_set();
+    let mut position = 1;
+    while let Some(arg) = args.get(position) {
+        let arg = arg.to_string_lossy();
+        let value_follows = if arg == "--" {
+            return args.get(position + 1).map(|_| position + 1);
+        } else if let Some(long) = arg.strip_prefix("--") {
+            !long.contains('=')
+                && cli.get_arguments().any(|flag| {
+                    let longs = [flag.get_long()].into_iter().flatten();
+                    let mut longs = longs.chain(flag.get_all_aliases().into_iter().flatten());
+                    longs.any(|name| name == long) && takes_value(flag)
+                })
+        } else if let Some(shorts) = arg.strip_prefix('-').filter(|shorts| !shorts.is_empty()) {
+            // In a cluster like `-cf2`, the first flag taking a value takes the
+            // rest of the cluster, or if there's none, the next argument
+            let takes = |c| {
+                let flag = cli.get_arguments().find(|flag| {
+                    flag.get_short() == Some(c)
+                        || flag.get_all_short_aliases().is_some_and(|shorts| shorts.contains(&c))
+                });
+                flag.is_some_and(takes_value)
+            };
+            shorts
+                .char_indices()
+                .find(|&(_, c)| takes(c))
+                .is_some_and(|(i, c)| i + c.len_utf8() == shorts.len())
         } else {
-            LogType::Lines
+            return Some(position);
+        };
+        position += 1 + usize::from(value_follows);
+    }
+    None
+}
+
+/// The operand groups `paths` are split into by `--` separators. A `--` before
+/// any operand just ends the flags, as usual, so doesn't start a group.
+fn operand_groups(paths: &[PathBuf]) -> Vec<&[PathBuf]> {
+    let mut groups: Vec<_> = paths.split(|path| path.as_os_str() == "--").collect();
+    if groups.len() > 1 && groups[0].is_empty() {
+        groups.remove(0);
+    }
+    groups
+}
+
+/// For the `expr` command, the expression given as its first argument, and for
+/// the `eval` command, the expression its signed operands make up, and for
+/// operand groups, the expression combining them with `op`; with the files they
+/// name as the operands. Otherwise, no expression and `paths`.
+fn expression_and_operands(
+    command: CliName,
+    op: OpName,
+    paths: &[PathBuf],
+) -> (Option<Expression>, Vec<PathBuf>) {
+    use clap::error::ErrorKind;
+    let fail = |kind, message: String| -> ! { CliArgs::command().error(kind, message).exit() };
+
+    if command == CliName::Eval {
+        let signed: Vec<_> = paths.iter().map(|path| path.to_string_lossy()).collect();
+        let expression = Expression::signed(signed.iter().map(AsRef::as_ref))
+            .unwrap_or_else(|err| fail(ErrorKind::InvalidValue, format!("{err:#}")));
+        let paths = expression.operands().iter().map(PathBuf::from).collect();
+        return (Some(expression), paths);
+    }
+    let groups = operand_groups(paths);
+    if groups.len() > 1 {
+        let names: Vec<Vec<_>> = groups
+            .iter()
+            .map(|group| group.iter().map(|path| path.to_string_lossy()).collect())
+            .collect();
+        let groups = names.iter().map(|group| group.iter().map(AsRef::as_ref));
+        let expression = Expression::grouped(op, groups)
+            .unwrap_or_else(|err| fail(ErrorKind::InvalidValue, format!("{err:#}")));
+        let paths = expression.operands().iter().map(PathBuf::from).collect();
+        return (Some(expression), paths);
+    }
+    let paths = groups.concat();
+    if command != CliName::Expr {
+        return (None, paths);
+    }
+    let text = match paths.as_slice() {
+        [text] => text.to_string_lossy(),
+        _ => fail(
+            ErrorKind::WrongNumberOfValues,
+            "the expr command takes one argument, an expression naming the input files".into(),
+        ),
+    };
+    let expression = Expression::parse(&text)
+        .unwrap_or_else(|err| fail(ErrorKind::InvalidValue, format!("{err:#}")));
+    let paths = expression.operands().iter().map(PathBuf::from).collect();
+    (Some(expression), paths)
+}
+
+/// The condition tested by `command`, if it tests one
+fn condition_for(command: CliName) -> Option<Condition> {
+    match command {
+        CliName::Equal => Some(Condition::Equal),
+        CliName::IsSubset => Some(Condition::Subset),
+        CliName::IsDisjoint => Some(Condition::Disjoint),
+        _ => None,
+    }
+}
+
+/// The `check` command's assertions: the `equal` ones, then `is-subset`, then
+/// `is-disjoint`. Each option takes two files, which `clap` gives us as one
+/// list, so we split them into pairs.
+fn assertions(equal: &[PathBuf], subset: &[PathBuf], disjoint: &[PathBuf]) -> Vec<Assertion> {
+    let pairs = |condition, paths: &[PathBuf]| {
+        let pairs = paths.chunks(2).map(<[PathBuf]>::to_vec);
+        pairs.map(move |paths| Assertion { condition, paths }).collect::<Vec<_>>()
+    };
+    let mut all = pairs(Condition::Equal, equal);
+    all.extend(pairs(Condition::Subset, subset));
+    all.extend(pairs(Condition::Disjoint, disjoint));
+    all
+}
+
+/// Exit with a usage error for mistakes `clap` can't catch by itself
+fn check_usage(parsed: &CliArgs) {
+    use clap::error::ErrorKind;
+    let fail = |kind, message: String| CliArgs::command().error(kind, message).exit();
+
+    // A label for a file that isn't an operand is probably a mistake — unless
+    // the file may be found by recursing into a directory operand.
+    if !parsed.recursive {
+        let asserted = [&parsed.assert_equal, &parsed.assert_subset, &parsed.assert_disjoint];
+        let asserted = asserted.into_iter().flatten();
+        let is_operand = |path| parsed.paths.contains(path) || asserted.clone().any(|p| p == path);
+        if let Some((name, path)) = parsed.label.iter().find(|(_, path)| !is_operand(path)) {
+            let message = format!("--label {name}={}: that file isn't an operand", path.display());
+            fail(ErrorKind::InvalidValue, message);
         }
-    } else {
-        LogType::None
+    }
+    let plain = matches!(parsed.format, None | Some(CliFormat::Plain));
+    if parsed.header_row && plain && parsed.column_delimiter.is_none() {
+        let message = "--header-row can only be used with --format csv or --format tsv, \
+                       or with --column-delimiter";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    let check = matches!(parsed.command, Some(CliName::Check));
+    if parsed.fail_fast && !check && parsed.command.and_then(condition_for).is_none() {
+        let message = "--fail-fast can only be used with the equal, is-subset, is-disjoint, and \
+                       check commands";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    let assertions =
+        parsed.assert_equal.len() + parsed.assert_subset.len() + parsed.assert_disjoint.len();
+    if check && (assertions == 0 || !parsed.paths.is_empty() || !parsed.literal.is_empty()) {
+        let message = "the check command takes --assert-equal, --assert-subset, or \
+                       --assert-disjoint options, rather than input files";
+        fail(ErrorKind::MissingRequiredArgument, message.to_string());
+    }
+    if !check && assertions > 0 {
+        let message = "--assert-equal, --assert-subset, and --assert-disjoint can only be used \
+                       with the check command";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    if parsed.locate
+        && (!selects_lines(parsed.command) || !matches!(log_type(parsed), LogType::None))
+    {
+        let message = "--where can only be used with the union, intersect, diff, single, \
+                       multiple, and majority commands, without counts";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    if parsed.key_cmd.is_some() && matches!(parsed.command, Some(CliName::MergeCounts)) {
+        let message = "--key-cmd can't be used with the merge-counts command";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    let serve = matches!(parsed.command, Some(CliName::Serve));
+    if parsed.socket.is_some() && !serve {
+        fail(
+            ErrorKind::ArgumentConflict,
+            "--socket can only be used with the serve command".into(),
+        );
+    }
+    let summary = parsed.summary || parsed.stats.is_some();
+    if serve && (!matches!(log_type(parsed), LogType::None) || summary) {
+        let message = "the serve command answers queries; it shows no counts, summary, or stats";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    if serve && parsed.socket.is_none() && reads_stdin(parsed) {
+        let message = "without --socket, the serve command reads queries from standard input, \
+                       so its baseline must come from files";
+        fail(ErrorKind::MissingRequiredArgument, message.to_string());
+    }
+    let expr = matches!(parsed.command, Some(CliName::Expr | CliName::Eval))
+        || operand_groups(&parsed.paths).len() > 1;
+    let unnamed_operands = parsed.recursive || !parsed.literal.is_empty();
+    if expr && (unnamed_operands || !matches!(log_type(parsed), LogType::None)) {
+        let message = "the expr and eval commands, and operand groups, read just the files \
+                       they name (not --literal operands or directories), and show no counts";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    let union = matches!(parsed.command, Some(CliName::Union));
+    if parsed.count_distinct.is_some() && (!union || !matches!(log_type(parsed), LogType::None)) {
+        let message = "--count-distinct can only be used with the union command, without counts";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    let multiple = matches!(parsed.command, Some(CliName::Multiple));
+    if parsed.stream && (!multiple || !matches!(log_type(parsed), LogType::None)) {
+        let message = "--stream can only be used with the multiple command, without counts";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    let hashable = matches!(parsed.command, Some(CliName::Intersect | CliName::Diff));
+    let counts = !matches!(log_type(parsed), LogType::None);
+    if parsed.hashed && (!(union || hashable) || expr || counts) {
+        let message = "--hashed can only be used with the union, intersect, and diff commands, \
+                       without counts or operand groups";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    if parsed.hashed && hashable && parsed.paths.first().map_or(true, |path| path == "-") {
+        let message = "intersect --hashed and diff --hashed read the first file twice, \
+                       so it can't be standard input";
+        fail(ErrorKind::MissingRequiredArgument, message.to_string());
+    }
+    let top = matches!(parsed.command, Some(CliName::Top));
+    if !top && (parsed.top.is_some() || parsed.approx.is_some()) {
+        let message = "--top and --approx can only be used with the top command";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+}
+
+/// How lines are quoted on output: for the shell with `--shell-quote`, as CSV
+/// fields with `--format csv`, and otherwise not at all
+fn line_format(shell_quote: bool, output_format: OutputFormat) -> LineFormat {
+    match (shell_quote, output_format) {
+        (true, _) => LineFormat::ShellQuoted,
+        (false, OutputFormat::Csv) => LineFormat::CsvQuoted,
+        (false, _) => LineFormat::Raw,
+    }
+}
+
+/// Parse the argument of `--record-delimiter`: a delimiter (with the escapes
+/// `--field-delimiter` allows) that can't be empty
+fn parse_record_delimiter(text: &str) -> Result<Bytes, String> {
+    let delimiter = key::parse_delimiter(text)?;
+    if delimiter.is_empty() {
+        return Err("the record delimiter can't be empty".to_string());
+    }
+    Ok(delimiter)
+}
+
+/// Parse the argument of `--column-delimiter`: a delimiter (with the escapes
+/// `--field-delimiter` allows) that must be a single byte
+fn parse_column_delimiter(text: &str) -> Result<u8, String> {
+    match key::parse_delimiter(text)?[..] {
+        [byte] => Ok(byte),
+        _ => Err(format!("the column delimiter must be a single byte, not '{text}'")),
+    }
+}
+
+/// With `--output-fields`, the bytes joining the fields printed. Output fields
+/// are joined like the fields of a key, unless we're told otherwise.
+fn output_fields(parsed: &CliArgs) -> Option<Vec<u8>> {
+    parsed.output_fields.then(|| {
+        let joiner = parsed.output_delimiter.as_ref().or(parsed.field_delimiter.as_ref());
+        joiner.map_or_else(|| b" ".to_vec(), Clone::clone)
+    })
+}
+
+/// With `--sort-by-count`, the order of the counts: descending, unless `--asc`
+fn count_order(parsed: &CliArgs) -> Option<CountOrder> {
+    let order = if parsed.asc { CountOrder::Ascending } else { CountOrder::Descending };
+    parsed.sort_by_count.then_some(order)
+}
+
+/// How to lay out the output: as `--column-delimiter` or `--format` says
+fn output_format(parsed: &CliArgs) -> OutputFormat {
+    match parsed.column_delimiter {
+        Some(delimiter) => OutputFormat::Delimited(delimiter),
+        None => parsed.format.map_or(OutputFormat::Plain, OutputFormat::from),
+    }
+}
+
+/// How to read the operands: with `framing`, and as the other reading flags say
+fn read_options(parsed: &CliArgs, framing: Framing, interrupt: &Interrupt) -> ReadOptions {
+    ReadOptions {
+        framing,
+        utf8: parsed.validate_utf8,
+        strip_boms: parsed.strip_inner_boms,
+        skipped: skipped(parsed),
+        labels: parsed.label.iter().map(|(name, path)| (path.clone(), name.clone())).collect(),
+        contents: HashMap::new(),
+        sniff_encoding: parsed.sniff_encoding,
+        report_encodings: parsed.sniff_encoding && parsed.verbose,
+        interrupt: interrupt.clone(),
+    }
+}
+
+/// The seed for `--sample` and `--shuffle`: `--seed`'s, or a random one
+fn seed(parsed: &CliArgs) -> u64 {
+    parsed.seed.unwrap_or_else(|| RandomState::new().hash_one(0))
+}
+
+/// With `--recursive`, how to walk the directories among the operands
+fn recursion(parsed: &CliArgs) -> Option<Recursion> {
+    parsed.recursive.then(|| Recursion {
+        follow: parsed.follow,
+        hidden: parsed.hidden,
+        no_ignore: parsed.no_ignore,
+        globs: parsed.glob.clone(),
+        max_depth: parsed.max_depth,
+    })
+}
+
+/// Does `command` select lines of its input to print (as `union`, `intersect`,
+/// and so on do)?
+fn selects_lines(command: Option<CliName>) -> bool {
+    matches!(
+        command,
+        Some(
+            CliName::Union
+                | CliName::Intersect
+                | CliName::Diff
+                | CliName::Single
+                | CliName::Multiple
+                | CliName::Majority
+        )
+    )
+}
+
+/// Exit with a usage error if one of the flags that change which lines are
+/// selected — `--invert`, `--in-at-least`, and the `--min-*` and `--max-*`
+/// flags — is used where it can't be
+fn check_selection_usage(parsed: &CliArgs) {
+    use clap::error::ErrorKind;
+    let fail = |kind, message: String| CliArgs::command().error(kind, message).exit();
+
+    if parsed.invert && !selects_lines(parsed.command) {
+        let message = "--invert can only be used with the union, intersect, diff, single, \
+                       multiple, and majority commands";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    let expr = matches!(parsed.command, Some(CliName::Expr | CliName::Eval));
+    let ordering = [
+        (parsed.sort, "--sort"),
+        (parsed.order != CliOrder::FirstSeen, "--order"),
+        (parsed.head.is_some(), "--head"),
+        (parsed.sample.is_some(), "--sample"),
+        (parsed.shuffle, "--shuffle"),
+    ];
+    for (used, flag) in ordering {
+        if used && !selects_lines(parsed.command) && !expr {
+            let message = format!(
+                "{flag} can only be used with the union, intersect, diff, single, multiple, \
+                 majority, expr, and eval commands"
+            );
+            fail(ErrorKind::ArgumentConflict, message);
+        }
+    }
+    if parsed.seed.is_some() && parsed.sample.is_none() && !parsed.shuffle {
+        let message = "--seed can only be used with --sample or --shuffle";
+        fail(ErrorKind::MissingRequiredArgument, message.to_string());
+    }
+    if parsed.in_at_least.is_some() && !matches!(parsed.command, Some(CliName::Intersect)) {
+        let message = "--in-at-least can only be used with the intersect command";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    let counts_files = match parsed.command {
+        Some(CliName::Union | CliName::Intersect | CliName::Diff | CliName::Majority) => true,
+        Some(CliName::Single | CliName::Multiple) => parsed.files,
+        _ => false,
+    };
+    if (parsed.min_files.is_some() || parsed.max_files.is_some()) && !counts_files {
+        let message = "--min-files and --max-files can only be used with the union, intersect, \
+                       diff, and majority commands, and with single and multiple when --files \
+                       is present";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    let single_by_file = matches!(parsed.command, Some(CliName::Single)) && parsed.files;
+    if parsed.grouped && (!single_by_file || !matches!(log_type(parsed), LogType::None)) {
+        let message = "--grouped can only be used with the single command with --files, and no \
+                       counts";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    let selects = selects_lines(parsed.command) || expr;
+    let plain = matches!(log_type(parsed), LogType::None);
+    let flags = [
+        (parsed.show_files, "--show-files"),
+        (parsed.with_filename, "-H"),
+        (parsed.count_per_file, "--count-per-file"),
+    ];
+    for (used, flag) in flags {
+        if used && (!selects || !plain) {
+            let message = format!(
+                "{flag} can only be used with the union, intersect, diff, single, multiple, \
+                 majority, expr, and eval commands, and no counts"
+            );
+            fail(ErrorKind::ArgumentConflict, message);
+        }
+    }
+    let counts_lines = match parsed.command {
+        Some(CliName::Union) => true,
+        Some(CliName::Single | CliName::Multiple) => !parsed.files,
+        _ => false,
     };
+    if (parsed.min_count.is_some() || parsed.max_count.is_some()) && !counts_lines {
+        let message = "--min-count and --max-count can only be used with the union command, and \
+                       with single and multiple when --files isn't present";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    let bounds = [
+        ("files", parsed.min_files, parsed.max_files),
+        ("count", parsed.min_count, parsed.max_count),
+    ];
+    for (what, min, max) in bounds {
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                let message = format!("--min-{what} {min} is more than --max-{what} {max}");
+                fail(ErrorKind::InvalidValue, message);
+            }
+        }
+    }
+}
+
+/// Exit with a usage error if `--sort-by-count`, `--count-after`, or
+/// `--count-align` is used without counts to go by
+fn check_count_usage(parsed: &CliArgs) {
+    use clap::error::ErrorKind;
+    let fail = |kind, message: String| CliArgs::command().error(kind, message).exit();
+
+    let counted = !matches!(log_type(parsed), LogType::None)
+        || parsed.count_per_file
+        || matches!(parsed.command, Some(CliName::Top | CliName::MergeCounts));
+    let flags =
+        [(parsed.count_after, "--count-after"), (parsed.count_align.is_some(), "--count-align")];
+    for (used, flag) in flags {
+        if used && !counted {
+            let message = format!(
+                "{flag} needs counts (as from --count), or the top or merge-counts command"
+            );
+            fail(ErrorKind::ArgumentConflict, message);
+        }
+    }
+    if parsed.sort_by_count
+        && (!selects_lines(parsed.command) || matches!(log_type(parsed), LogType::None))
+    {
+        let message = "--sort-by-count needs counts (as from --count), and can only be used with \
+                       the union, intersect, diff, single, multiple, and majority commands";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+}
+
+/// Exit with a usage error if the `venn`, `similarity`, or `tui` command,
+/// `--format matrix` or `comm`, or `--out-prefix`, is misused
+fn check_region_usage(parsed: &CliArgs) {
+    use clap::error::ErrorKind;
+    let fail = |kind, message: String| CliArgs::command().error(kind, message).exit();
+
+    let venn = matches!(parsed.command, Some(CliName::Venn));
+    if venn && !matches!(log_type(parsed), LogType::None) {
+        let message = "the venn command shows no counts of lines or files";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    let similarity = matches!(parsed.command, Some(CliName::Similarity));
+    if similarity && !matches!(log_type(parsed), LogType::None) {
+        let message = "the similarity command shows no counts of lines or files";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    #[cfg(feature = "tui")]
+    if matches!(parsed.command, Some(CliName::Tui)) && !matches!(log_type(parsed), LogType::None) {
+        let message = "the tui command shows no counts of lines or files";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    if matches!(parsed.format, Some(CliFormat::Matrix | CliFormat::Comm))
+        && (parsed.command != Some(CliName::Union)
+            || !matches!(log_type(parsed), LogType::None)
+            || parsed.count_per_file)
+    {
+        let format = if parsed.format == Some(CliFormat::Comm) { "comm" } else { "matrix" };
+        let message =
+            format!("--format {format} can only be used with the union command, without counts");
+        fail(ErrorKind::ArgumentConflict, message);
+    }
+    if parsed.out_prefix.is_some() && !venn {
+        let message = "--out-prefix can only be used with the venn command";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    let interactive = matches!(parsed.command, Some(CliName::Serve));
+    #[cfg(feature = "tui")]
+    let interactive = interactive || matches!(parsed.command, Some(CliName::Tui));
+    if parsed.output.is_some() && interactive {
+        let message = "the serve and tui commands can't write their output to a file";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    if parsed.in_place && !selects_lines(parsed.command) {
+        let message = "--in-place can only be used with the union, intersect, diff, single, \
+                       multiple, and majority commands";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    if parsed.in_place && parsed.paths.iter().find(|path| *path != "--").map_or(true, |p| p == "-")
+    {
+        let message = "--in-place rewrites the first file, so it can't be standard input";
+        fail(ErrorKind::MissingRequiredArgument, message.to_string());
+    }
+}
 
-    Args { op, log_type, paths: parsed.paths }
+/// Exit with a usage error if `--state` is used where it can't be
+fn check_state_usage(parsed: &CliArgs) {
+    use clap::error::ErrorKind;
+    let fail = |kind, message: String| CliArgs::command().error(kind, message).exit();
+
+    if parsed.state.is_none() {
+        return;
+    }
+    let set_command = match parsed.command {
+        Some(
+            CliName::Union
+            | CliName::Intersect
+            | CliName::Diff
+            | CliName::Majority
+            | CliName::Equal
+            | CliName::IsSubset
+            | CliName::IsDisjoint,
+        ) => true,
+        Some(CliName::Single | CliName::Multiple) => parsed.files,
+        _ => false,
+    };
+    if !set_command || !matches!(log_type(parsed), LogType::None | LogType::Files) {
+        let message = "--state remembers each file's lines as a set, so it can only be used \
+                       with the union, intersect, diff, majority, equal, is-subset, and \
+                       is-disjoint commands (or single and multiple with --files), and no \
+                       counts but --count-files";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+    if reads_stdin(parsed) {
+        let message = "--state follows files, so it can't be used with standard input";
+        fail(ErrorKind::ArgumentConflict, message.to_string());
+    }
+}
+
+/// Exit with a usage error if `-q` is used with a command that doesn't print
+/// lines
+fn check_quiet_usage(parsed: &CliArgs) {
+    use clap::error::ErrorKind;
+
+    let prints_lines = selects_lines(parsed.command)
+        || matches!(parsed.command, Some(CliName::Expr | CliName::Eval));
+    let table = matches!(parsed.format, Some(CliFormat::Matrix | CliFormat::Comm));
+    if parsed.quiet && (!prints_lines || table) {
+        let message = "-q can only be used with the union, intersect, diff, single, multiple, \
+                       majority, expr, and eval commands, and not with --format matrix or comm";
+        CliArgs::command().error(ErrorKind::ArgumentConflict, message).exit();
+    }
+}
+
+/// Exit with a usage error if `--stats` is used without the `json` feature,
+/// which writes the statistics
+fn check_stats_usage(parsed: &CliArgs) {
+    use clap::error::ErrorKind;
+
+    if cfg!(not(feature = "json")) && parsed.stats.is_some() {
+        let message = "--stats json needs zet to be built with the json feature";
+        CliArgs::command().error(ErrorKind::InvalidValue, message).exit();
+    }
+}
+
+/// Do the operands include standard input?
+fn reads_stdin(parsed: &CliArgs) -> bool {
+    parsed.paths.is_empty() || parsed.paths.iter().any(|path| path == "-")
 }
 
 fn help_and_exit(cc: &ColorChoice) -> ! {
@@ -84,54 +934,169 @@ fn safe_exit(code: i32) -> ! {
 }
 
 pub struct Args {
-    /// `op` is the set operation requested
-    pub op: OpName,
-    /// Should we count the number of times each line occurs?
-    pub log_type: LogType,
+    /// `options` holds the set operation requested, and the options (counting,
+    /// keys, framing) that `calculate` needs to perform it
+    pub options: Options,
+    /// `read_options` says how to read the operands: their framing, whether to
+    /// check for valid UTF-8 or strip Byte Order Marks, and their labels
+    pub read_options: ReadOptions,
+    /// For the `equal`, `is-subset`, and `is-disjoint` commands, the condition
+    /// to test (rather than performing `options.op`)
+    pub condition: Option<Condition>,
+    /// For the `check` command, the assertions to test
+    pub assertions: Vec<Assertion>,
+    /// When testing a condition (or assertion), should we stop reading as soon as the verdict
+    /// is certain?
+    pub fail_fast: bool,
+    /// For `equal -v`, `is-subset -v`, and `is-disjoint -v`, print the lines
+    /// that make the condition fail
+    pub explain: bool,
+    /// For the `venn` command: print the lines of each region of a Venn
+    /// diagram of the operands (rather than performing `options.op`)
+    pub venn: bool,
+    /// For `venn --out-prefix`, write each region to a file whose name is the
+    /// prefix followed by the region's label (see `zet_core::venn`) and `.txt`,
+    /// rather than to standard output
+    pub out_prefix: Option<PathBuf>,
+    /// With `--output`, write to this file rather than to standard output
+    pub output: Option<PathBuf>,
+    /// For the `similarity` command: print the Jaccard similarity of each pair
+    /// of operands (rather than performing `options.op`)
+    pub similarity: bool,
+    /// With `--format matrix`: print each line with a column for each operand,
+    /// marking those it occurs in (rather than performing `options.op`)
+    pub matrix: bool,
+    /// With `--format comm`: print the lines of two operands in three columns,
+    /// like `comm` (rather than performing `options.op`)
+    pub comm: bool,
+    /// For the `tui` command: browse the regions of a Venn diagram of the
+    /// operands (rather than performing `options.op`)
+    #[cfg(feature = "tui")]
+    pub tui: bool,
+    /// For the `serve` command: where the queries come from
+    pub serve: Option<Endpoint>,
+    /// With `--state`, the state file for following the operands from run to
+    /// run (see the `state` module)
+    pub state: Option<PathBuf>,
+    /// With `--stream`, print each multiple line as soon as it's detected
+    pub stream: bool,
+    /// With `--hashed`, keep just a hash of each line (see `zet::hashed`)
+    pub hashed: bool,
+    /// For the `expr` command, the expression to evaluate over the operands
+    /// (which are the files it names)
+    pub expression: Option<Expression>,
+    /// With `-q`, print nothing, but exit with status 0 if we'd have printed
+    /// some lines, and 1 if not
+    pub quiet: bool,
+    /// Should we finish with a summary line on stderr, giving the number of
+    /// lines written and read?
+    pub summary: bool,
+    /// With `--stats json`, where to write the run's statistics as JSON
+    pub stats: Option<StatsTo>,
+    /// On Ctrl-C, should we print the result for the input read so far?
+    pub partial_on_interrupt: bool,
+    /// With `--recursive`, how to walk directory operands
+    pub recursion: Option<Recursion>,
     /// `paths` is the list of files from the command line
     pub paths: Vec<PathBuf>,
+    /// `literals` holds the `--literal` operands, each as newline-separated
+    /// lines, to follow the files
+    pub literals: Vec<Vec<u8>>,
 }
 
-/// Set operation to perform
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
-pub enum OpName {
-    /// Print the lines present in every file
-    Intersect,
-    /// Print the lines present in any file
-    Union,
-    /// Print the lines present in the first file but no other
-    Diff,
-    /// Print the lines present exactly once in the entire input
-    Single,
-    /// Print the lines present in exactly one file
-    SingleByFile,
-    /// Print the lines present more than once in the entire input
-    Multiple,
-    /// Print the lines present in two or more files
-    MultipleByFile,
+// `clap` treats a field of type `Option<Vec<_>>` as taking multiple values, but
+// these are single values that parse to a `Vec`. Aliases hide the `Vec`.
+type Columns = Vec<Column>;
+type Bytes = Vec<u8>;
+type Label = (String, PathBuf);
+
+/// Parse a `--label` value, `NAME=PATH`
+fn parse_label(text: &str) -> Result<Label, String> {
+    match text.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => {
+            Ok((name.to_string(), PathBuf::from(path)))
+        }
+        _ => Err(format!("'{text}' isn't of the form NAME=PATH")),
+    }
 }
 
+/// Parse a `--literal` value: lines separated by commas or newlines. We add
+/// a newline to the last one; an empty value has no lines at all.
+#[allow(clippy::unnecessary_wraps)] // `clap` value parsers return a `Result`
+fn parse_literal(text: &str) -> Result<Bytes, String> {
+    let mut literal = text.replace(',', "\n");
+    if !literal.is_empty() && !literal.ends_with('\n') {
+        literal.push('\n');
+    }
+    Ok(literal.into_bytes())
+}
+
+// The counting flags override each other: the last one given wins. But
+// --count-lines and --count-files can be given together, for both counts.
+const COUNT_FLAGS: [&str; 6] =
+    ["count", "count_files", "count_lines", "count_split", "count_per_file", "count_none"];
+
 #[derive(Debug, Parser)]
 #[command(name = "zet")]
 /// `CliArgs` contains the parsed command line.
 struct CliArgs {
-    #[arg(long, overrides_with_all(["count", "count_files", "count_lines", "count_none"]))]
-    /// The --count-files flag tells `zet` to report the number of files a line occurs in
+    #[arg(
+        long,
+        overrides_with_all(["count", "count_files", "count_split", "count_per_file", "count_none"])
+    )]
+    /// The --count-files flag tells `zet` to report the number of files a line occurs in (with
+    /// --count-lines, after the number of times it appears)
     count_files: bool,
 
-    #[arg(long, overrides_with_all(["count", "count_files", "count_lines", "count_none"]))]
+    #[arg(
+        long,
+        overrides_with_all(["count", "count_lines", "count_split", "count_per_file", "count_none"])
+    )]
     /// The --count-lines flag tells `zet` to report the times a line appears in the entire input
     count_lines: bool,
 
-    #[arg(long, overrides_with_all(["count", "count_files", "count_lines", "count_none"]))]
+    #[arg(long, overrides_with_all(COUNT_FLAGS))]
+    /// The --count-split flag tells `zet` to report the times a line appears in the first file,
+    /// and separately, the times it appears in the other files
+    count_split: bool,
+
+    #[arg(
+        long,
+        overrides_with_all(COUNT_FLAGS),
+        conflicts_with_all([
+            "show_files", "with_filename", "grouped", "locate", "count_distinct", "stream",
+            "hashed", "counted_input"
+        ])
+    )]
+    /// The --count-per-file flag tells `zet` to report, in a column for each file, the times a
+    /// line appears in that file
+    count_per_file: bool,
+
+    #[arg(long)]
+    /// The --count-after flag tells `zet` to print counts after each line rather than before it
+    /// (with plain output, after a tab)
+    count_after: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ALIGN",
+        conflicts_with_all(["format", "column_delimiter", "count_after"])
+    )]
+    /// The --count-align flag tells `zet` how to pad the counts of plain output: `right`-aligned
+    /// in a column as wide as the largest count (the default), `left`-aligned, or `none`, with no
+    /// padding at all
+    count_align: Option<CliCountAlign>,
+
+    #[arg(long, overrides_with_all(COUNT_FLAGS))]
     /// The --count-none flag tells `zet` to turn off reporting
     count_none: bool,
 
-    #[arg(short, long, overrides_with_all(["count", "count_files", "count_lines", "count_none"]))]
+    #[arg(short, long, overrides_with_all(COUNT_FLAGS))]
     /// The --count is like --count-lines, but --files makes it act like --count-files
     count: bool,
 
-    #[arg(long, alias("file"), overrides_with_all(["files", "lines"]))]
+    #[arg(long, aliases(["file", "by-file"]), overrides_with_all(["files", "lines"]))]
     /// With `--files`, the `single` and `multiple` commands count a line as occuring
     /// once if it's only contained in one file, even if it occurs many times in that file.
     files: bool,
@@ -140,6 +1105,529 @@ struct CliArgs {
     /// `--lines` is the default. Specify it explicitly to override a previous `--files`
     lines: bool,
 
+    #[arg(long, value_name = "FIELDS", value_parser = key::parse_column_list)]
+    /// The --key flag gives a comma-separated list of field numbers and ranges (like `2-4`);
+    /// lines are compared using just those fields. With --csv, fields may also be named by
+    /// the header.
+    key: Option<Columns>,
+
+    #[arg(long, value_name = "DELIM", value_parser = key::parse_delimiter, requires = "key")]
+    /// The --field-delimiter flag gives the string (possibly multi-byte, possibly with escapes
+    /// like `\t` or `\x1f`) separating fields. By default fields are separated by whitespace.
+    field_delimiter: Option<Bytes>,
+
+    #[arg(long, requires = "key")]
+    /// The --output-fields flag tells `zet` to print just the fields of each line given by
+    /// `--key`, rather than the whole line
+    output_fields: bool,
+
+    #[arg(long, value_name = "DELIM", value_parser = key::parse_delimiter, requires = "output_fields")]
+    /// The --output-delimiter flag gives the string joining the fields printed by
+    /// `--output-fields`. By default it's the field delimiter (or a space).
+    output_delimiter: Option<Bytes>,
+
+    #[arg(
+        long,
+        value_name = "FIELD",
+        requires = "key",
+        conflicts_with_all(["format", "locate", "output_fields", "counted_input"])
+    )]
+    /// The --count-distinct flag tells the `union` command to print each key, followed by a tab
+    /// and the number of distinct values the field numbered FIELD takes in lines with that key
+    count_distinct: Option<NonZeroUsize>,
+
+    #[arg(long, value_name = "COMMAND", conflicts_with_all(["key", "record_bytes"]))]
+    /// The --key-cmd flag gives a shell command that reads lines and prints the comparison key
+    /// for each, one per line. It is run once per input file.
+    key_cmd: Option<String>,
+
+    #[arg(long, value_name = "REGEX", conflicts_with_all(["key", "key_cmd", "record_bytes"]))]
+    /// The --key-regex flag gives a regular expression; lines it matches are compared by its
+    /// first capture group (or if it has none, the whole match), and other lines are compared
+    /// whole.
+    key_regex: Option<KeyRegex>,
+
+    #[arg(short('f'), long, value_name = "N", default_value_t, hide_default_value = true)]
+    #[arg(conflicts_with_all(["key", "key_cmd", "key_regex"]))]
+    /// As for `uniq -f`, the --skip-fields flag tells `zet` to compare lines ignoring their
+    /// first N fields, each a run of blanks and the non-blanks after it
+    skip_fields: usize,
+
+    #[arg(short('s'), long, value_name = "M", default_value_t, hide_default_value = true)]
+    #[arg(conflicts_with_all(["key", "key_cmd", "key_regex"]))]
+    /// As for `uniq -s`, the --skip-chars flag tells `zet` to compare lines ignoring their
+    /// first M bytes (after any skipped fields)
+    skip_chars: usize,
+
+    #[arg(short('w'), long, value_name = "W", conflicts_with_all(["key", "key_cmd", "key_regex"]))]
+    /// As for `uniq -w`, the --check-chars flag tells `zet` to compare no more than W bytes of
+    /// each line (after any skipped fields and bytes)
+    check_chars: Option<usize>,
+
+    #[arg(long, value_name = "REGEX", conflicts_with = "record_bytes")]
+    /// The --ignore-pattern flag (which may be repeated) gives a regular expression whose
+    /// matches are removed from each line's key before lines are compared, as for ignoring
+    /// timestamps. Each line is printed as first seen.
+    ignore_pattern: Vec<IgnorePattern>,
+
+    #[cfg(feature = "json")]
+    #[arg(
+        long,
+        value_name = "POINTER",
+        conflicts_with_all([
+            "key",
+            "key_cmd",
+            "key_regex",
+            "skip_fields",
+            "skip_chars",
+            "check_chars",
+            "record_bytes"
+        ])
+    )]
+    /// The --json-key flag gives a JSON pointer, like `/user/id`; lines of JSON are compared
+    /// by the value it points to, ignoring whitespace and the order of object members. Other
+    /// lines are compared whole.
+    json_key: Option<key::JsonPointer>,
+
+    #[arg(long, requires = "key_regex")]
+    /// The --skip-unmatched flag tells `zet` to leave out lines the --key-regex doesn't match
+    skip_unmatched: bool,
+
+    #[arg(long)]
+    /// The --normalize flag tells `zet` to compare keys after trimming them, collapsing runs
+    /// of whitespace to a single space, and lowercasing them. Output lines are unchanged: each
+    /// is printed as first seen.
+    normalize: bool,
+
+    #[arg(long)]
+    /// The --trim flag tells `zet` to compare keys without their leading and trailing
+    /// whitespace. Each line is printed as first seen, untrimmed.
+    trim: bool,
+
+    #[arg(long)]
+    /// The --numeric flag tells `zet` to compare keys that are decimal numbers as numbers,
+    /// so that `1`, `01`, and `1.0` are the same. Each line is printed as first seen.
+    numeric: bool,
+
+    #[arg(long, conflicts_with = "record_bytes")]
+    /// The --ignore-blank flag tells `zet` to leave empty lines out of every input file, and
+    /// with --trim or --normalize, lines holding nothing but whitespace too
+    ignore_blank: bool,
+
+    #[arg(
+        long,
+        value_name = "PREFIX",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "#",
+        value_parser = clap::builder::NonEmptyStringValueParser::new(),
+        conflicts_with = "record_bytes"
+    )]
+    /// The --skip-comments flag tells `zet` to leave out of every input file the lines that
+    /// start with PREFIX (`#` by default), after any leading whitespace
+    skip_comments: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        value_name = "FOLDING",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "ascii"
+    )]
+    /// The -i or --ignore-case flag tells `zet` to compare keys after lowercasing them. With
+    /// `ascii` (the default) just ASCII letters are lowercased; with `unicode`, UTF-8 keys get
+    /// Unicode's full case folding. Each line is printed as first seen.
+    ignore_case: Option<CaseFolding>,
+
+    #[arg(
+        long,
+        value_name = "WHICH",
+        value_enum,
+        default_value_t,
+        conflicts_with_all(["stream", "hashed"])
+    )]
+    /// The --keep flag tells `zet` which of the lines with the same key to print, when keys
+    /// aren't whole lines: the `first` seen (the default), or the `last`
+    keep: CliKeep,
+
+    #[arg(long, conflicts_with_all(["stream", "hashed"]))]
+    /// The --sort flag tells `zet` to print its output lines in bytewise order, rather than in
+    /// the order they first appear in the input
+    sort: bool,
+
+    #[arg(
+        long,
+        value_name = "WHICH",
+        value_enum,
+        default_value_t,
+        conflicts_with_all(["sort", "stream", "hashed"])
+    )]
+    /// The --order flag tells `zet` whether to print its output lines in the order of their
+    /// first appearance in the input (`first-seen`, the default) or of their last (`last-seen`)
+    order: CliOrder,
+
+    #[arg(long)]
+    /// The --sort-by-count flag tells `zet` to print the lines it counts in order of their
+    /// counts, most frequent first — as for `zet multiple --count` reports of the commonest lines
+    sort_by_count: bool,
+
+    #[arg(long, requires = "sort_by_count")]
+    /// The --asc flag tells --sort-by-count to print the least frequent lines first
+    asc: bool,
+
+    #[arg(long, value_name = "N", conflicts_with_all(["stream", "hashed"]))]
+    /// The --head flag tells `zet` to print at most N lines: the first N it would otherwise
+    /// print (with --sort-by-count, the N most frequent)
+    head: Option<usize>,
+
+    #[arg(long, value_name = "N", conflicts_with_all(["stream", "hashed"]))]
+    /// The --sample flag tells `zet` to print a random sample of N of the lines it would otherwise
+    /// print, keeping their order
+    sample: Option<usize>,
+
+    #[arg(long, conflicts_with_all(["sort", "order", "sort_by_count", "stream", "hashed"]))]
+    /// The --shuffle flag tells `zet` to print its lines in a random order
+    shuffle: bool,
+
+    #[arg(long, value_name = "S")]
+    /// The --seed flag gives the seed for the random choices of --sample and --shuffle, so the same
+    /// seed and input give the same output (by default the seed is itself random)
+    seed: Option<u64>,
+
+    #[arg(long, value_name = "N")]
+    /// The --record-bytes flag tells `zet` that its input is a sequence of binary records,
+    /// each N bytes long, with no terminator
+    record_bytes: Option<NonZeroUsize>,
+
+    #[arg(
+        long,
+        conflicts_with_all(["record_bytes", "no_decode", "field_delimiter", "output_fields"])
+    )]
+    /// The --csv flag tells `zet` that its input is CSV: a newline inside a double-quoted
+    /// field doesn't end a record, and --key fields are CSV fields, which may be quoted, and
+    /// may be named by the header. Then each file's first record is its header, which may
+    /// list the columns in any order, and which isn't compared or printed
+    csv: bool,
+
+    #[arg(long, conflicts_with = "record_bytes")]
+    /// The --no-decode flag tells `zet` to treat its input as opaque bytes, split into lines
+    /// only at `\n`: no Byte Order Mark handling, no UTF-16 translation, and `\r` is kept
+    no_decode: bool,
+
+    #[arg(
+        short,
+        long,
+        visible_short_alias = '0',
+        conflicts_with_all(["record_bytes", "no_decode", "csv", "key_cmd", "state"])
+    )]
+    /// The -z or --zero-terminated flag tells `zet` that its input records end with a NUL
+    /// byte rather than a newline, and to end its output records with NUL too — as with
+    /// `sort -z` or `find -print0`, for lists of file names that may contain newlines
+    zero_terminated: bool,
+
+    #[arg(
+        long,
+        visible_alias = "delimiter",
+        value_name = "DELIM",
+        value_parser = parse_record_delimiter,
+        conflicts_with_all(["record_bytes", "no_decode", "csv", "zero_terminated", "key_cmd", "state"])
+    )]
+    /// The --record-delimiter flag tells `zet` that its input records end with DELIM (a byte
+    /// or short string, possibly with escapes like `\t` or `\x1e`) rather than a newline, and
+    /// to end its output records with DELIM too
+    record_delimiter: Option<Bytes>,
+
+    #[arg(long, conflicts_with_all(["record_bytes", "no_decode"]))]
+    /// The --sniff-encoding flag tells `zet` to decode each operand without a Byte Order Mark
+    /// from the encoding it seems to be in (UTF-8, UTF-16, or windows-1252), judging by its
+    /// first 64 KiB, rather than assuming UTF-8
+    sniff_encoding: bool,
+
+    #[arg(long, conflicts_with = "record_bytes")]
+    /// The --counted-input flag tells `zet` that each input line starts with a count, as in the
+    /// output of `uniq -c` or `zet --count`, and stands for that many occurrences of the rest of
+    /// the line
+    counted_input: bool,
+
+    #[arg(long, conflicts_with_all(["key_cmd", "locate", "state"]))]
+    /// The --stream flag tells the `multiple` command to print each line as soon as it occurs
+    /// a second time (or with `--files`, in a second file), rather than after reading all the
+    /// input
+    stream: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all([
+            "key_cmd",
+            "locate",
+            "state",
+            "stream",
+            "invert",
+            "in_at_least",
+            "min_files",
+            "max_files",
+            "min_count",
+            "max_count",
+            "counted_input",
+            "partial_on_interrupt"
+        ])
+    )]
+    /// The --hashed flag tells the `union`, `intersect`, and `diff` commands to keep just a
+    /// 128-bit hash of each line, rather than the line, to save memory on very large input.
+    /// `union` prints each line as soon as it's first seen; `intersect` and `diff` read the
+    /// first file a second time, to print its surviving lines.
+    hashed: bool,
+
+    #[arg(long, value_name = "FILE", conflicts_with_all(["record_bytes", "counted_input", "locate"]))]
+    /// The --state flag tells `zet` to remember in FILE how much of each operand it has read,
+    /// and the distinct lines it found, so that the next run need only read what's been appended
+    state: Option<PathBuf>,
+
+    #[arg(long, value_name = "LINES", value_parser = parse_literal, conflicts_with = "record_bytes")]
+    /// The --literal flag (which may be repeated) gives an operand on the command line, as lines
+    /// separated by commas or newlines, to follow the input files
+    literal: Vec<Bytes>,
+
+    #[arg(long, value_name = "NAME=PATH", value_parser = parse_label)]
+    /// The --label flag (which may be repeated) tells `zet` to call the operand PATH by NAME
+    /// in messages
+    label: Vec<Label>,
+
+    #[arg(short, long)]
+    /// The --recursive flag tells `zet` to read all the files beneath each directory operand
+    recursive: bool,
+
+    #[arg(long, overrides_with = "no_follow", requires = "recursive")]
+    /// The --follow flag tells `zet` to follow symbolic links found while recursing
+    follow: bool,
+
+    #[arg(long, overrides_with = "follow", requires = "recursive")]
+    /// The --no-follow flag (the default) tells `zet` to skip symbolic links found while
+    /// recursing
+    no_follow: bool,
+
+    #[arg(long, requires = "recursive")]
+    /// The --hidden flag tells `zet` to include hidden files and directories (whose names
+    /// start with `.`) when recursing
+    hidden: bool,
+
+    #[arg(long, requires = "recursive")]
+    /// The --no-ignore flag tells `zet` to include files excluded by `.gitignore` or `.ignore`
+    /// files when recursing
+    no_ignore: bool,
+
+    #[arg(long, value_name = "PATTERN", requires = "recursive")]
+    /// The --glob flag (which may be repeated) tells `zet` to include only files matching
+    /// PATTERN when recursing, or with a leading `!`, to exclude files matching it
+    glob: Vec<String>,
+
+    #[arg(long, value_name = "N", requires = "recursive")]
+    /// The --max-depth flag tells `zet` to descend at most N levels of directories when
+    /// recursing
+    max_depth: Option<usize>,
+
+    #[arg(long)]
+    /// The --summary flag tells `zet` to finish by printing the number of lines it wrote,
+    /// lines it read, and files it read, on a line of stderr
+    summary: bool,
+
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    /// The --stats flag tells `zet` to finish by writing statistics of the run — lines read from
+    /// each file, distinct lines, lines written, and time taken — in FORMAT (just `json` for
+    /// now), on stderr unless --stats-file says where. It needs the json feature.
+    stats: Option<CliStatsFormat>,
+
+    #[arg(long, value_name = "FILE", requires = "stats")]
+    /// The --stats-file flag tells `zet` to write --stats' statistics to FILE rather than stderr
+    stats_file: Option<PathBuf>,
+
+    #[arg(long)]
+    /// The --partial-on-interrupt flag tells `zet` to print the result for the input read so
+    /// far when interrupted by Ctrl-C, rather than exiting without output
+    partial_on_interrupt: bool,
+
+    #[arg(
+        long,
+        value_name = "ACTION",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "warn",
+        conflicts_with = "record_bytes"
+    )]
+    /// The --validate-utf8 flag tells `zet` to check each line for valid UTF-8. With `warn`
+    /// (the default) invalid lines are reported on stderr; with `skip` they're ignored.
+    validate_utf8: Option<Utf8Check>,
+
+    #[arg(long, conflicts_with_all(["record_bytes", "no_decode"]))]
+    /// The --strip-inner-boms flag tells `zet` to strip Byte Order Marks from the start of
+    /// lines other than the first line of a file (as when files are `cat`-ed together)
+    strip_inner_boms: bool,
+
+    #[arg(long, value_name = "PREFIX")]
+    /// The --out-prefix flag tells the `venn` command to write each region to its own file,
+    /// named PREFIX followed by the region's label (like `AC` for the lines in just the first
+    /// and third files) and `.txt`
+    out_prefix: Option<PathBuf>,
+
+    #[arg(short, long, value_name = "FILE", conflicts_with = "out_prefix")]
+    /// The -o or --output flag tells `zet` to write its output to FILE rather than to standard
+    /// output, so that no shell redirection is needed
+    output: Option<PathBuf>,
+
+    #[arg(short('I'), long, conflicts_with_all(["output", "out_prefix", "recursive", "state"]))]
+    /// The -I or --in-place flag tells `zet` to write its output to its first file, replacing
+    /// that file's contents — as for removing duplicates from a list, or merging others into it.
+    /// (Its short form isn't `-i`, as for `sed`, since that's --ignore-case, as for `uniq`.)
+    in_place: bool,
+
+    #[arg(
+        short,
+        long,
+        conflicts_with_all(["output", "in_place", "summary", "stats", "header_row", "grouped"])
+    )]
+    /// The -q or --quiet flag tells `zet` to print nothing, but to exit with status 0 if it would
+    /// have printed some lines and 1 if not, like `grep -q` — stopping reading early when it can
+    quiet: bool,
+
+    #[arg(long, value_name = "PATH")]
+    /// The --socket flag tells the `serve` command to answer queries from connections to a
+    /// Unix socket at PATH, rather than from standard input
+    socket: Option<PathBuf>,
+
+    #[arg(long, alias("at-least"), value_name = "N|P%")]
+    /// The --in-at-least flag (or --at-least) tells the `intersect` command to print the lines
+    /// present in at least N files, or P percent of the files, rather than in every file
+    in_at_least: Option<Threshold>,
+
+    #[arg(long, conflicts_with_all(["count_distinct", "stream"]))]
+    /// The --invert flag tells `zet` to print the lines of the input that the operation would
+    /// leave out, rather than those it selects
+    invert: bool,
+
+    #[arg(long, conflicts_with_all(["invert", "locate", "format", "count_distinct", "stream"]))]
+    /// The --grouped flag tells `single --files` to print the lines found in just one file
+    /// grouped by that file, each group under a heading naming the file
+    grouped: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all([
+            "grouped", "locate", "count_distinct", "stream", "hashed", "output_fields", "csv"
+        ])
+    )]
+    /// The --show-files flag tells `zet` to print before each line a comma-separated list of
+    /// the files it occurs in
+    show_files: bool,
+
+    #[arg(
+        short = 'H',
+        long,
+        conflicts_with_all([
+            "show_files", "grouped", "locate", "count_distinct", "stream", "hashed", "format"
+        ])
+    )]
+    /// The -H or --with-filename flag tells `zet` to print before each line the name of the
+    /// file it was first seen in, and a colon, like `grep -H`
+    with_filename: bool,
+
+    #[arg(long, value_name = "N", conflicts_with_all(["count_distinct", "stream"]))]
+    /// The --min-files flag tells `zet` to print only the lines present in at least N files
+    min_files: Option<u32>,
+
+    #[arg(long, value_name = "N", conflicts_with_all(["count_distinct", "stream"]))]
+    /// The --max-files flag tells `zet` to print only the lines present in at most N files
+    max_files: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with_all(["min_files", "max_files", "count_distinct", "stream"])
+    )]
+    /// The --min-count flag tells `zet` to print only the lines occurring at least N times in
+    /// all of the input
+    min_count: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with_all(["min_files", "max_files", "count_distinct", "stream"])
+    )]
+    /// The --max-count flag tells `zet` to print only the lines occurring at most N times in
+    /// all of the input
+    max_count: Option<u32>,
+
+    #[arg(long)]
+    /// The --fail-fast flag tells the `equal`, `is-subset`, and `is-disjoint` commands to stop
+    /// reading input as soon as the verdict is certain
+    fail_fast: bool,
+
+    #[arg(long = "where", conflicts_with = "format")]
+    /// The --where flag tells `zet` to list every place each output line occurs, as
+    /// `file:line_number:line`, like `grep -n`
+    locate: bool,
+
+    #[arg(long, value_name = "N", requires = "locate")]
+    /// The --where-limit flag tells `zet` to list at most N places for each output line
+    where_limit: Option<NonZeroUsize>,
+
+    #[arg(long, value_names = ["A", "B"], num_args = 2)]
+    /// The --assert-equal flag (which may be repeated) gives the `check` command two files
+    /// that should have the same set of lines
+    assert_equal: Vec<PathBuf>,
+
+    #[arg(long, value_names = ["A", "B"], num_args = 2)]
+    /// The --assert-subset flag (which may be repeated) gives the `check` command two files,
+    /// A and B, such that every line of A should be in B
+    assert_subset: Vec<PathBuf>,
+
+    #[arg(long, value_names = ["A", "B"], num_args = 2)]
+    /// The --assert-disjoint flag (which may be repeated) gives the `check` command two files
+    /// that should have no lines in common
+    assert_disjoint: Vec<PathBuf>,
+
+    #[arg(long, value_name = "N")]
+    /// The --top flag tells the `top` command to print only the N most frequent lines
+    top: Option<usize>,
+
+    #[arg(long, value_name = "K", conflicts_with = "key_cmd")]
+    /// The --approx flag tells the `top` command to estimate the most frequent lines using K
+    /// counters, rather than counting every distinct line exactly. Each line's estimated count
+    /// (which may be too high) is followed by the least its true count can be
+    approx: Option<NonZeroUsize>,
+
+    #[arg(long)]
+    /// The --si flag tells `zet` to abbreviate counts with SI suffixes, like `1.2k` or `3.4M`
+    si: bool,
+
+    #[arg(long)]
+    /// The --shell-quote flag tells `zet` to quote each output line for a POSIX shell
+    shell_quote: bool,
+
+    #[arg(long, value_enum, conflicts_with_all(["shell_quote", "record_bytes"]))]
+    /// The --format flag tells `zet` how to lay out its output: `plain` (the default), or as
+    /// comma- or tab-separated values (`csv` or `tsv`). With `matrix`, `union` prints a column
+    /// for each file, headed by its name, marking with `x` the files each line occurs in; with
+    /// `comm`, `union` of two files prints their lines in three columns, as `comm` does.
+    format: Option<CliFormat>,
+
+    #[arg(
+        long,
+        visible_alias = "count-delimiter",
+        value_name = "DELIM",
+        value_parser = parse_column_delimiter,
+        conflicts_with_all(["format", "shell_quote", "record_bytes"])
+    )]
+    /// The --column-delimiter flag tells `zet` to separate its output's columns (the counts
+    /// and the line) with DELIM, a single byte like `|` or `\t`, without padding — like
+    /// `--format tsv`, but with a delimiter of your choosing. As --count-delimiter, it makes counts
+    /// easy to split from lines that start with spaces or digits.
+    column_delimiter: Option<u8>,
+
+    #[arg(long)]
+    /// The --header-row flag tells `zet` to start csv or tsv output with a row of column names
+    header_row: bool,
+
     #[arg(short, long)]
     /// Like the `help` command, the `-h` or `--help` flags tell us to print the help message
     /// and exit
@@ -149,6 +1637,15 @@ struct CliArgs {
     /// The `-V` or `--version` flags tell us to print our name and version, then exit
     version: bool,
 
+    #[arg(short, long)]
+    /// The -v or --verbose flag asks for more detail: with `--version`, we also print the
+    /// enabled cargo features, the target triple, and the git commit `zet` was built from;
+    /// with `--sniff-encoding`, the encoding chosen for each operand; with `equal`, the lines
+    /// that aren't in every file; with `is-subset`, the
+    /// lines of the first file that aren't in any other; and with `is-disjoint`, the first line
+    /// found in two files
+    verbose: bool,
+
     #[arg(long)]
     /// The `color` flag tells us whether to print color or not (Auto means Yes, if
     /// stdout is a terminal that supports color)
@@ -176,6 +1673,141 @@ enum CliName {
     Single,
     /// Print the lines present in two or more files
     Multiple,
+    /// Print the lines present in more than half of the files
+    Majority,
+    /// Print lines with their counts, most frequent first
+    #[value(alias = "frequency")]
+    Top,
+    /// Print statistics of how often lines occur: entropy, Gini coefficient, percentiles
+    Stats,
+    /// Print each line of counted input (as from --count) with the sum of its counts
+    MergeCounts,
+    /// Print the lines of the set described by an expression over files, like
+    /// '(a.txt & b.txt) - c.txt'
+    Expr,
+    /// Print the lines of the files prefixed by +, less those of the files prefixed by -,
+    /// taking them from left to right
+    Eval,
+    /// Exit with status 0 if every file has the same set of lines, 1 if not
+    Equal,
+    /// Exit with status 0 if every line of the first file is in another file, 1 if not
+    IsSubset,
+    /// Exit with status 0 if no line is in more than one file, 1 if not
+    IsDisjoint,
+    /// Test each --assert-equal, --assert-subset, and --assert-disjoint assertion, reporting
+    /// on each
+    Check,
+    /// Print the lines in each region of a Venn diagram of the files, under a heading for each
+    Venn,
+    /// Print the Jaccard similarity of each pair of files
+    Similarity,
+    /// Browse the regions of a Venn diagram of the files, and export them
+    #[cfg(feature = "tui")]
+    Tui,
+    /// Load the files as a baseline, and answer queries about it
+    Serve,
     /// Print a help message
     Help,
 }
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, ValueEnum)]
+/// The ways `--ignore-case` can fold case
+enum CaseFolding {
+    Ascii,
+    Unicode,
+}
+
+impl From<CaseFolding> for Normalizer {
+    fn from(folding: CaseFolding) -> Self {
+        match folding {
+            CaseFolding::Ascii => Normalizer::Lowercase,
+            CaseFolding::Unicode => Normalizer::FoldCase,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, ValueEnum)]
+/// The lines `--keep` can keep, one for each `Keep`
+enum CliKeep {
+    #[default]
+    First,
+    Last,
+}
+
+impl From<CliKeep> for Keep {
+    fn from(keep: CliKeep) -> Self {
+        match keep {
+            CliKeep::First => Keep::First,
+            CliKeep::Last => Keep::Last,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, ValueEnum)]
+/// The orders `--order` accepts, one for each `Order`
+enum CliOrder {
+    #[default]
+    FirstSeen,
+    LastSeen,
+}
+
+impl From<CliOrder> for Order {
+    fn from(order: CliOrder) -> Self {
+        match order {
+            CliOrder::FirstSeen => Order::FirstSeen,
+            CliOrder::LastSeen => Order::LastSeen,
+        }
+    }
+}
+
+/// Where `--stats` writes the run's statistics
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StatsTo {
+    Stderr,
+    File(PathBuf),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, ValueEnum)]
+/// The formats `--stats` accepts
+enum CliStatsFormat {
+    Json,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, ValueEnum)]
+/// The alignments `--count-align` accepts, one for each `CountAlign`
+enum CliCountAlign {
+    Right,
+    Left,
+    None,
+}
+
+impl From<CliCountAlign> for CountAlign {
+    fn from(align: CliCountAlign) -> Self {
+        match align {
+            CliCountAlign::Right => CountAlign::Right,
+            CliCountAlign::Left => CountAlign::Left,
+            CliCountAlign::None => CountAlign::None,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, ValueEnum)]
+/// The layouts `--format` accepts, one for each `OutputFormat`
+enum CliFormat {
+    Plain,
+    Csv,
+    Tsv,
+    Matrix,
+    Comm,
+}
+
+impl From<CliFormat> for OutputFormat {
+    fn from(format: CliFormat) -> Self {
+        match format {
+            // The matrix and comm layouts are written by `Venn`, not as `options` says
+            CliFormat::Plain | CliFormat::Matrix | CliFormat::Comm => OutputFormat::Plain,
+            CliFormat::Csv => OutputFormat::Csv,
+            CliFormat::Tsv => OutputFormat::Tsv,
+        }
+    }
+}
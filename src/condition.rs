@@ -0,0 +1,66 @@
+//! The `check` command tests several `Assertion`s — conditions (see
+//! `zet_core::condition`) on different sets of files — in one process,
+//! reporting on each, and passing only if they all pass.
+use crate::operands::{first_and_rest, ReadOptions};
+use crate::operations::Options;
+use anyhow::Result;
+use std::path::PathBuf;
+pub use zet_core::condition::Condition;
+
+/// An assertion for the `check` command: that `condition` holds for the files
+/// at `paths`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Assertion {
+    pub condition: Condition,
+    pub paths: Vec<PathBuf>,
+}
+
+impl Assertion {
+    /// Does the assertion hold? (See `Condition::holds`.)
+    pub fn holds(&self, options: &Options, read: &ReadOptions, fail_fast: bool) -> Result<bool> {
+        match first_and_rest(&self.paths, read) {
+            None => Ok(true), // No files, as when a directory operand is empty
+            Some((first, rest)) => self.condition.holds(options, &first?, rest, fail_fast),
+        }
+    }
+}
+
+/// Test each of the `assertions`, writing a line to `out` for each, saying
+/// whether it passed, failed, or couldn't be tested (as when a file can't be
+/// read), and finally a line with the number that didn't pass. Returns `true`
+/// if they all passed.
+pub fn check(
+    assertions: &[Assertion],
+    options: &Options,
+    read: &ReadOptions,
+    fail_fast: bool,
+    mut out: impl std::io::Write,
+) -> Result<bool> {
+    let mut not_passed = 0;
+    for assertion in assertions {
+        let files = assertion.paths.iter().map(|path| read.display(path));
+        let files: Vec<_> = files.collect();
+        let described = format!("{} {}", assertion.condition.name(), files.join(" "));
+        match assertion.holds(options, read, fail_fast) {
+            Ok(true) => writeln!(out, "pass   {described}")?,
+            Ok(false) => {
+                not_passed += 1;
+                writeln!(out, "FAIL   {described}")?;
+            }
+            Err(err) => {
+                not_passed += 1;
+                writeln!(out, "ERROR  {described}: {err:#}")?;
+            }
+        }
+        if options.interrupt.requested() {
+            break;
+        }
+    }
+    let s = if assertions.len() == 1 { "" } else { "s" };
+    match not_passed {
+        0 => writeln!(out, "All {} assertion{s} passed", assertions.len())?,
+        n => writeln!(out, "{n} of {} assertion{s} did not pass", assertions.len())?,
+    }
+    out.flush()?;
+    Ok(not_passed == 0)
+}
@@ -30,6 +30,15 @@ pub(crate) fn version() -> String {
     format!("{name} {version}")
 }
 
+/// The version, followed by the build information recorded by `build.rs`
+pub(crate) fn verbose_version() -> String {
+    let features = std::env!("ZET_BUILD_FEATURES");
+    let features = if features.is_empty() { "(none)" } else { features };
+    let target = std::env!("ZET_BUILD_TARGET");
+    let commit = std::env!("ZET_BUILD_COMMIT");
+    format!("{}\nfeatures: {features}\ntarget: {target}\ncommit: {commit}", version())
+}
+
 pub(crate) fn print(color_choice: &ColorChoice) -> Result<()> {
     let color_choice = match color_choice {
         ColorChoice::Always => anstream::ColorChoice::Always,
@@ -58,12 +67,12 @@ fn fallable_print(stdout: &mut dyn std::io::Write) -> std::io::Result<usize> {
                 writeln!(stdout, "{}", as_title(s.title))?;
                 s.print_entries(stdout)?;
             }
-        };
+        }
     }
     Ok(0)
 }
 
-fn parse(text: &str) -> Vec<HelpItem> {
+fn parse(text: &str) -> Vec<HelpItem<'_>> {
     const USAGE: &str = "Usage: ";
     let mut help = Vec::new();
     let mut lines = text.lines().fuse();
@@ -0,0 +1,19 @@
+//! The `zet` command's side of `--partial-on-interrupt`: a Ctrl-C handler that
+//! records the interrupt (see `zet_core::interrupt`) rather than exiting. A
+//! second Ctrl-C kills `zet` immediately, in case it's blocked waiting for
+//! input.
+#[cfg(feature = "cli")]
+use anyhow::{Context, Result};
+pub use zet_core::interrupt::{Interrupt, EXIT_CODE};
+
+/// Install a Ctrl-C handler that records the interrupt in `interrupt` rather
+/// than exiting (unless an interrupt has already been recorded).
+#[cfg(feature = "cli")]
+pub fn catch_ctrl_c(interrupt: Interrupt) -> Result<()> {
+    ctrlc::set_handler(move || {
+        if interrupt.request() {
+            std::process::exit(EXIT_CODE);
+        }
+    })
+    .context("Can't install a Ctrl-C handler")
+}
@@ -0,0 +1,220 @@
+//! The `zet` command's `--key-cmd`: a `KeyCommand` that runs a shell command,
+//! writing the lines of each operand to the command's standard input, one per
+//! line, and reading the corresponding keys, one per line, from its standard
+//! output. And its `--key-regex` and `--json-key`: `KeyExtractor`s taking each
+//! line's key from a regular expression's match, or from the value at a JSON
+//! pointer. Its `--ignore-pattern`, another `KeyExtractor`, edits keys rather
+//! than extracting them. Everything else about keys lives in `zet_core::key`.
+use anyhow::{bail, Context, Result};
+use bstr::io::BufReadExt;
+use regex::bytes::Regex;
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+pub use zet_core::key::*;
+
+/// A regular expression whose first capture group (or if it has none, whole
+/// match) is the key of each line it matches. A line it doesn't match is its
+/// own key, unless `--skip-unmatched` leaves such lines out.
+#[derive(Clone, Debug)]
+pub struct KeyRegex(pub Regex);
+
+impl KeyRegex {
+    /// Does the regular expression match `line`?
+    #[must_use]
+    pub fn matches(&self, line: &[u8]) -> bool {
+        self.0.is_match(line)
+    }
+}
+
+impl KeyExtractor for KeyRegex {
+    fn key_of<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.0.captures_len() == 1 {
+            return Cow::Borrowed(self.0.find(line).map_or(line, |found| found.as_bytes()));
+        }
+        match self.0.captures(line) {
+            None => Cow::Borrowed(line),
+            Some(captures) => Cow::Borrowed(captures.get(1).map_or(&[], |group| group.as_bytes())),
+        }
+    }
+}
+
+impl FromStr for KeyRegex {
+    type Err = regex::Error;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Regex::new(text).map(KeyRegex)
+    }
+}
+
+/// A regular expression whose matches are removed from each key, so that lines
+/// differing only in, say, their timestamps compare equal.
+#[derive(Clone, Debug)]
+pub struct IgnorePattern(pub Regex);
+
+impl KeyExtractor for IgnorePattern {
+    fn key_of<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        self.0.replace_all(line, &b""[..])
+    }
+}
+
+impl FromStr for IgnorePattern {
+    type Err = regex::Error;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Regex::new(text).map(IgnorePattern)
+    }
+}
+
+/// A JSON pointer, like `/user/id`, picking out the key of each line of JSON (as
+/// in a JSON Lines file): the value it points to, written compactly and with
+/// the members of objects sorted, so that neither whitespace nor member order
+/// matters. A line that isn't JSON, or has nothing at the pointer, is its own
+/// key.
+#[cfg(feature = "json")]
+#[derive(Clone, Debug)]
+pub struct JsonPointer(pub String);
+
+#[cfg(feature = "json")]
+impl KeyExtractor for JsonPointer {
+    fn key_of<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        let Ok(value) = serde_json::from_slice::<serde_json::Value>(line) else {
+            return Cow::Borrowed(line);
+        };
+        match value.pointer(&self.0) {
+            Some(found) => Cow::Owned(found.to_string().into_bytes()),
+            None => Cow::Borrowed(line),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl FromStr for JsonPointer {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if text.is_empty() || text.starts_with('/') {
+            Ok(JsonPointer(text.to_string()))
+        } else {
+            Err(format!("'{text}' is not a JSON pointer, which starts with '/'"))
+        }
+    }
+}
+
+/// Regular expressions are equal if their patterns are, as far as
+/// `ReadOptions` cares.
+impl PartialEq for KeyRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for KeyRegex {}
+
+/// A shell command that prints the key of each line it reads
+#[derive(Clone, Debug)]
+pub struct ShellCommand(pub String);
+
+impl KeyCommand for ShellCommand {
+    fn keys_of(&self, lines: &[&[u8]]) -> Result<Vec<Vec<u8>>> {
+        run_key_command(&self.0, lines)
+    }
+}
+
+/// Run `command` with the shell, feeding it `lines` and returning its output
+/// lines. It must print exactly one key for each line.
+fn run_key_command(command: &str, lines: &[&[u8]]) -> Result<Vec<Vec<u8>>> {
+    let mut child = shell(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Can't run key command: {command}"))?;
+    let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        bail!("Can't connect to key command: {command}")
+    };
+    let mut keys = Vec::with_capacity(lines.len());
+    // We must write to the command while we read from it, or it may block
+    // with its output buffer full while we block waiting to write.
+    let (read, written) = std::thread::scope(|scope| {
+        let writer = scope.spawn(move || -> io::Result<()> {
+            let mut stdin = io::BufWriter::new(stdin);
+            for line in lines {
+                stdin.write_all(line)?;
+                stdin.write_all(b"\n")?;
+            }
+            stdin.flush()
+        });
+        let read = io::BufReader::new(stdout).for_byte_line(|key| {
+            keys.push(key.to_vec());
+            Ok(true)
+        });
+        let written = writer.join().unwrap_or_else(|_| Err(io::Error::other("writer panicked")));
+        (read, written)
+    });
+    let status = child.wait().with_context(|| format!("Key command failed: {command}"))?;
+    if !status.success() {
+        bail!("Key command failed ({status}): {command}");
+    }
+    read.with_context(|| format!("Error reading from key command: {command}"))?;
+    written.with_context(|| format!("Error writing to key command: {command}"))?;
+    if keys.len() != lines.len() {
+        bail!(
+            "Key command printed {} lines for {} input lines: {command}",
+            keys.len(),
+            lines.len()
+        );
+    }
+    Ok(keys)
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn a_key_command_is_run_once_for_all_lines() {
+        let keys_of =
+            |command: &str, lines: &[&[u8]]| ShellCommand(command.to_string()).keys_of(lines);
+        let keys = keys_of("tr a-z A-Z", &[b"abc", b"Def", b""]).unwrap();
+        assert_eq!(keys, vec![b"ABC".to_vec(), b"DEF".to_vec(), b"".to_vec()]);
+        assert!(keys_of("head -n 1", &[b"a", b"b"]).is_err());
+        assert!(keys_of("cat; exit 3", &[b"a"]).is_err());
+    }
+
+    #[test]
+    fn a_key_regex_keys_lines_by_their_first_capture_group() {
+        let regex: KeyRegex = r#""GET (\S+)"#.parse().unwrap();
+        assert_eq!(regex.key_of(br#"1.2.3.4 "GET /index.html HTTP/1.1""#).as_ref(), b"/index.html");
+        assert_eq!(regex.key_of(b"no request here").as_ref(), b"no request here");
+        assert!(!regex.matches(b"no request here"));
+        let regex: KeyRegex = "[0-9]+".parse().unwrap();
+        assert_eq!(regex.key_of(b"id 42, x").as_ref(), b"42");
+        assert!("(".parse::<KeyRegex>().is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn a_json_pointer_keys_lines_by_the_value_it_points_to() {
+        let pointer: JsonPointer = "/user".parse().unwrap();
+        let key_of = |line: &str| String::from_utf8(pointer.key_of(line.as_bytes()).into_owned());
+        let key = key_of(r#"{"user": {"name": "x", "id": 1}, "n": 2}"#).unwrap();
+        assert_eq!(key, key_of(r#"{"n":3,"user":{"id":1,"name":"x"}}"#).unwrap());
+        assert_eq!(key, r#"{"id":1,"name":"x"}"#);
+        assert_eq!(key_of(r#"{"id": 1}"#).unwrap(), r#"{"id": 1}"#);
+        assert_eq!(key_of("not json").unwrap(), "not json");
+        assert!("user".parse::<JsonPointer>().is_err());
+    }
+}
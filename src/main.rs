@@ -1,44 +1,346 @@
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+#[cfg(feature = "tui")]
 use is_terminal::IsTerminal;
 use std::io;
-use zet::args::OpName;
+use zet::args::{OpName, StatsTo};
+use zet::condition;
+use zet::interrupt;
 use zet::operands::first_and_rest;
 use zet::operations::calculate;
+use zet::output::Output;
+use zet::summary::{self, Stats};
 
 fn main() -> Result<()> {
     let args = zet::args::parsed();
+    let mut report = Report {
+        summary: args.summary,
+        stats: args.stats.clone(),
+        names: Vec::new(),
+        #[cfg(feature = "json")]
+        started: std::time::Instant::now(),
+    };
+    let interrupt = args.options.interrupt.clone();
+    if args.partial_on_interrupt {
+        interrupt::catch_ctrl_c(interrupt.clone())?;
+    }
+
+    if !args.assertions.is_empty() {
+        let mut assertions = args.assertions;
+        if let Some(recursion) = &args.recursion {
+            for assertion in &mut assertions {
+                assertion.paths = recursion.expand(std::mem::take(&mut assertion.paths))?;
+            }
+        }
+        let (options, read_options) = (&args.options, &args.read_options);
+        let mut out = Output::to(args.output.as_deref())?;
+        let passed =
+            condition::check(&assertions, options, read_options, args.fail_fast, &mut out)?;
+        out.commit()?;
+        let files = assertions.iter().map(|assertion| assertion.paths.len()).sum();
+        report.write(&options.tally.stats(), files, false)?;
+        if interrupt.requested() {
+            eprintln!("zet: interrupted — not every assertion was tested");
+            std::process::exit(interrupt::EXIT_CODE);
+        }
+        std::process::exit(if passed { 0 } else { 1 });
+    }
 
-    let paths = first_and_rest(&args.paths).or_else(|| first_and_rest(&["-".into()]));
+    let paths = match &args.recursion {
+        // Directories with no files in them are empty operands, not a request
+        // to read standard input
+        Some(recursion) if !args.paths.is_empty() => {
+            let paths = recursion.expand(args.paths)?;
+            if paths.is_empty() {
+                if args.quiet {
+                    exit_quietly(false);
+                }
+                return Ok(());
+            }
+            paths
+        }
+        _ => args.paths,
+    };
+    let mut options = args.options;
+    let mut read_options = args.read_options;
+    // The state file remembers all the lines read so far, so we can save the
+    // new state before using it
+    if let Some(path) = &args.state {
+        let mut state = zet::state::State::load(path)?;
+        read_options.contents = state.catch_up(&paths)?;
+        state.save()?;
+    }
+    let read_options = &read_options;
+    let mut out = if args.quiet { Output::quiet() } else { Output::to(args.output.as_deref())? };
+    if args.stream {
+        let files = paths.len().max(1);
+        let paths = if paths.is_empty() { vec!["-".into()] } else { paths };
+        let operands = zet::operands::all(paths, read_options).and_literals(args.literals);
+        zet::stream::multiple(&options, operands, &mut out)?;
+        return finish(out, &report, &interrupt, &options.tally.stats(), files, true);
+    }
+    if args.hashed {
+        let files = paths.len().max(1);
+        let paths = if paths.is_empty() { vec!["-".into()] } else { paths };
+        let first = paths[0].clone();
+        let operands = zet::operands::all(paths, read_options).and_literals(args.literals);
+        let first_again = || {
+            let mut again = zet::operands::all(vec![first], read_options);
+            again.next().context("This can't happen: a path should give an operand")?
+        };
+        zet::hashed::calculate(&options, operands, first_again, &mut out)?;
+        return finish(out, &report, &interrupt, &options.tally.stats(), files, true);
+    }
+    let mut names: Vec<String> = match paths.as_slice() {
+        [] => vec![read_options.display("-".as_ref())],
+        paths => paths.iter().map(|path| read_options.display(path)).collect(),
+    };
+    names.extend(args.literals.iter().map(|_| zet::operands::LITERAL_DISPLAY.to_string()));
+    if report.wanted() {
+        report.names.clone_from(&names);
+    }
+    if let Some(locations) = &mut options.locations {
+        locations.names.clone_from(&names);
+    }
+    if let Some(grouped) = &mut options.grouped {
+        grouped.clone_from(&names);
+    }
+    if let Some(show_files) = &mut options.show_files {
+        show_files.clone_from(&names);
+    }
+    if let Some(count_per_file) = &mut options.count_per_file {
+        count_per_file.clone_from(&names);
+    }
+    let paths = first_and_rest(&paths, read_options)
+        .or_else(|| first_and_rest(&["-".into()], read_options));
     let (first_operand, rest) = match paths {
         None => {
             bail!("This can't happen: with no file arguments, zet should read from standard input")
         }
-        Some((first, others)) => (first?, others),
+        Some((first, others)) => (first?, others.and_literals(args.literals)),
     };
 
-    let mut op = args.op;
+    if let Some(expression) = &args.expression {
+        let files = rest.len() + 1;
+        expression.evaluate(&options, &first_operand, rest, &mut out)?;
+        return finish(out, &report, &interrupt, &options.tally.stats(), files, true);
+    }
+
+    if args.venn {
+        let files = rest.len() + 1;
+        let venn = zet::venn::Venn::new(&options, &first_operand, rest)?;
+        match &args.out_prefix {
+            None => venn.write_sections(&names, &mut out)?,
+            Some(prefix) => {
+                for region in venn.regions() {
+                    let mut path = prefix.clone().into_os_string();
+                    path.push(format!("{}.txt", region.label().replace(' ', "")));
+                    let path = std::path::PathBuf::from(path);
+                    let file = std::fs::File::create(&path)
+                        .with_context(|| format!("Can't create file: {}", path.display()))?;
+                    venn.write_region(&region, io::BufWriter::new(file))?;
+                }
+            }
+        }
+        return finish(out, &report, &interrupt, &options.tally.stats(), files, true);
+    }
+
+    if args.matrix || args.comm {
+        let files = rest.len() + 1;
+        let venn = zet::venn::Venn::new(&options, &first_operand, rest)?;
+        if args.comm {
+            venn.write_comm(&mut out)?;
+        } else {
+            venn.write_matrix(&names, &mut out)?;
+        }
+        return finish(out, &report, &interrupt, &options.tally.stats(), files, true);
+    }
+
+    if args.similarity {
+        let files = rest.len() + 1;
+        let venn = zet::venn::Venn::new(&options, &first_operand, rest)?;
+        let matrix = zet::similarity::jaccard(&venn);
+        let (_, terminator) = venn.output_info();
+        zet::similarity::write(&matrix, &names, &options, terminator, &mut out)?;
+        // Like `stats`, `similarity` prints numbers, not lines
+        return finish(out, &report, &interrupt, &options.tally.stats(), files, false);
+    }
+
+    #[cfg(feature = "tui")]
+    if args.tui {
+        if !io::stdout().is_terminal() {
+            bail!("The tui command needs a terminal")
+        }
+        let venn = zet::venn::Venn::new(&options, &first_operand, rest)?;
+        return zet::tui::browse(&venn, &names);
+    }
+
+    if let Some(endpoint) = &args.serve {
+        let baseline = zet::baseline::Baseline::new(&options, &first_operand, rest)?;
+        return zet::serve::run(&baseline, endpoint, read_options);
+    }
+
+    let files = rest.len() + 1;
+    if let Some(condition) = args.condition {
+        use condition::Condition;
+        let holds = match condition {
+            Condition::Equal if args.explain => {
+                Condition::write_unequal(&options, &first_operand, rest, &mut out)?
+            }
+            Condition::Subset if args.explain => {
+                Condition::write_unseen(&options, &first_operand, rest, &mut out)?
+            }
+            Condition::Disjoint if args.explain => {
+                Condition::write_first_shared(&options, &first_operand, rest, &mut out)?
+            }
+            Condition::Equal | Condition::Subset | Condition::Disjoint => {
+                condition.holds(&options, &first_operand, rest, args.fail_fast)?
+            }
+        };
+        out.commit()?;
+        report.write(&options.tally.stats(), files, args.explain)?;
+        if interrupt.requested() {
+            eprintln!("zet: interrupted — no verdict, since not all the input was read");
+            std::process::exit(interrupt::EXIT_CODE);
+        }
+        std::process::exit(if holds { 0 } else { 1 });
+    }
+
     if rest.len() == 0 {
         use OpName::*;
-        match op {
+        match options.op {
+            // With --grouped, SingleByFile still writes a heading for the file
+            SingleByFile if options.grouped.is_some() => {}
+
             // For a single operand, Union is slightly more efficient, and its
-            // result is identical to Intersect, Diff, and SingleByFile
-            Union | Intersect | Diff | SingleByFile => op = Union,
+            // result is identical to Intersect, Diff, SingleByFile, and
+            // Majority
+            Union | Intersect | Diff | SingleByFile | Majority => options.op = Union,
 
             // No line can occur in multiple files if there is only one file
-            MultipleByFile => return Ok(()),
+            // (though for --summary or --stats, we still count the lines read,
+            // with --invert, every line is printed, and with -q, we must say
+            // that none is)
+            MultipleByFile if !report.wanted() && !options.invert && !args.quiet => return Ok(()),
 
-            // Even for a single operand, the results of Single and Multiple
-            // differ from that of Union
-            Single | Multiple => {}
+            // Even for a single operand, the results of Single, Multiple, Top,
+            // Stats, and MergeCounts differ from that of Union
+            Single | Multiple | MultipleByFile | Top | Stats | MergeCounts => {}
         }
     }
 
     let first = first_operand.as_slice();
-    //panic!("\n\n\n\n\n\n###########################{op:?}                {:?}\n", args.log_type);
-    if io::stdout().is_terminal() {
-        calculate(op, args.log_type, first, rest, io::stdout().lock())?;
+    // With -q, we needn't calculate the result, just whether it has any lines —
+    // which is often a condition we can stop testing as soon as it's settled
+    if args.quiet {
+        if let Some(condition) = condition::Condition::of_no_lines(&options, files) {
+            let none = condition.holds(&options, first, rest, true)?;
+            if interrupt.requested() {
+                eprintln!("zet: interrupted — no verdict, since not all the input was read");
+                std::process::exit(interrupt::EXIT_CODE);
+            }
+            exit_quietly(!none);
+        }
+    }
+    //panic!("\n\n\n\n\n\n###########################{:?}                {:?}\n", options.op, options.log_type);
+    let stats = calculate(&options, first, rest, &mut out)?;
+    // `stats` prints statistics, not lines
+    finish(out, &report, &interrupt, &stats, files, options.op != OpName::Stats)
+}
+
+/// Commit the output, write the summary of the run's `stats`, if asked for
+/// (saying whether we `wrote_lines`), and exit with `interrupt::EXIT_CODE` if
+/// `interrupt` was requested
+fn finish(
+    out: Output,
+    report: &Report,
+    interrupt: &interrupt::Interrupt,
+    stats: &Stats,
+    files: usize,
+    wrote_lines: bool,
+) -> Result<()> {
+    let wrote_quietly = out.wrote_quietly();
+    out.commit()?;
+    report.write(stats, files, wrote_lines)?;
+    if interrupt.requested() {
+        eprintln!("zet: interrupted — output is PARTIAL, calculated from the input read so far");
+        std::process::exit(interrupt::EXIT_CODE);
+    }
+    if let Some(wrote) = wrote_quietly {
+        exit_quietly(wrote);
+    }
+    Ok(())
+}
+
+/// For `-q`, exit like `grep -q`: with status 0 if there was output to
+/// suppress, and 1 if there wasn't
+fn exit_quietly(wrote: bool) -> ! {
+    std::process::exit(if wrote { 0 } else { 1 })
+}
+
+/// What to report about the run when it's done: the `--summary` line, and the
+/// `--stats json` object — which name the operands by `names`, and give the
+/// time since `started`
+struct Report {
+    summary: bool,
+    stats: Option<StatsTo>,
+    names: Vec<String>,
+    #[cfg(feature = "json")]
+    started: std::time::Instant,
+}
+
+impl Report {
+    /// Do we need to keep track of the run's statistics?
+    fn wanted(&self) -> bool {
+        self.summary || self.stats.is_some()
+    }
+
+    /// Write the summary and statistics asked for, for a run with statistics
+    /// `stats` that read `files` files (and wrote lines, if `wrote_lines`)
+    fn write(&self, stats: &Stats, files: usize, wrote_lines: bool) -> Result<()> {
+        if self.summary {
+            eprintln!("{}", summary::message(stats, files, wrote_lines));
+        }
+        #[cfg(feature = "json")]
+        if let Some(to) = &self.stats {
+            let elapsed = self.started.elapsed();
+            let json = stats_json(stats, &self.names, elapsed, files, wrote_lines).to_string();
+            match to {
+                StatsTo::Stderr => eprintln!("{json}"),
+                StatsTo::File(path) => std::fs::write(path, json + "\n")
+                    .with_context(|| format!("Can't write statistics file: {}", path.display()))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The statistics `stats` of a run that read `files` operands, named `names`,
+/// in `elapsed` time, as a JSON object: the lines read, in all and from each
+/// operand (as far as we can tell which is which); the lines written (if
+/// `wrote_lines` is set); the distinct lines, which is also the most lines the
+/// set held at once; and the seconds taken. Statistics we don't know are
+/// `null`.
+#[cfg(feature = "json")]
+fn stats_json(
+    stats: &Stats,
+    names: &[String],
+    elapsed: std::time::Duration,
+    files: usize,
+    wrote_lines: bool,
+) -> serde_json::Value {
+    let read = &stats.read_by_operand;
+    let operands: Vec<_> = if names.len() == read.len() {
+        let operand = |(name, lines)| serde_json::json!({ "name": name, "lines": lines });
+        names.iter().zip(read).map(operand).collect()
     } else {
-        calculate(op, args.log_type, first, rest, io::BufWriter::new(io::stdout().lock()))?;
+        Vec::new()
     };
-    Ok(())
+    serde_json::json!({
+        "files": files,
+        "lines_read": stats.lines_read(),
+        "operands": operands,
+        "lines_written": wrote_lines.then_some(stats.lines_written),
+        "distinct_lines": stats.distinct_lines,
+        "elapsed_seconds": elapsed.as_secs_f64(),
+    })
 }
@@ -4,48 +4,358 @@
 //! remaining operands has the unfortunate result of requiring different code
 //! paths for translating UTF16 files into UTF8. That currently seems worth the
 //! cost.
-use crate::set::LaterOperand;
+//!
+//! Lines of text operands may optionally be checked for valid UTF-8, with
+//! invalid lines reported or skipped; see `Utf8Check`. And Byte Order Marks at
+//! the start of lines other than the first (as when several files are `cat`-ed
+//! together) may optionally be stripped, with a warning. Blank lines, comment
+//! lines, and lines a `--key-regex` doesn't match, may be left out too; see
+//! `Skipped`. These
+//! choices are
+//! gathered in a `ReadOptions` structure, along with any labels to use in
+//! messages in place of operands' paths.
+use crate::interrupt::Interrupt;
+use crate::key::KeyRegex;
+use crate::set::{csv_record_end, Framing, LaterOperand};
 use anyhow::{Context, Result};
-use bstr::io::BufReadExt;
-use encoding_rs_io::{DecodeReaderBytes, DecodeReaderBytesBuilder};
+use bstr::{io::BufReadExt, ByteSlice};
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use encoding_rs::Encoding;
+use encoding_rs_io::DecodeReaderBytesBuilder;
 use std::{
+    borrow::Cow,
+    collections::HashMap,
     fs,
     fs::File,
     io::{self, Read},
     ops::FnMut,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
+/// What `--validate-utf8` should do with a line that isn't valid UTF-8
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum Utf8Check {
+    /// Print a warning giving the file and line number, but keep the line
+    Warn,
+    /// Leave the line out of the calculation
+    Skip,
+}
+
+impl Utf8Check {
+    /// Should we keep line `number` of `path_display`? Warns if appropriate.
+    fn keeps(self, line: &[u8], path_display: &str, number: usize) -> bool {
+        if std::str::from_utf8(line).is_ok() {
+            return true;
+        }
+        match self {
+            Utf8Check::Warn => {
+                eprintln!("zet: {path_display}:{number}: invalid UTF-8");
+                true
+            }
+            Utf8Check::Skip => false,
+        }
+    }
+}
+
+/// Check each line of the first operand, `contents`, as `check` requires.
+fn check_first_operand(contents: Vec<u8>, path_display: &str, check: Utf8Check) -> Vec<u8> {
+    if std::str::from_utf8(&contents).is_ok() {
+        return contents;
+    }
+    let mut checked = Vec::with_capacity(contents.len());
+    for (n, line) in contents.lines_with_terminator().enumerate() {
+        if check.keeps(line, path_display, n + 1) {
+            checked.extend_from_slice(line);
+        }
+    }
+    checked
+}
+
+/// Which lines `--ignore-blank` leaves out: empty ones, or (with `--trim`)
+/// those holding nothing but whitespace too
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Blank {
+    Empty,
+    Whitespace,
+}
+
+impl Blank {
+    /// Is `line` (without its terminator) blank?
+    fn is(self, line: &[u8]) -> bool {
+        match self {
+            Blank::Empty => line.is_empty(),
+            Blank::Whitespace => line.iter().all(u8::is_ascii_whitespace),
+        }
+    }
+}
+
+/// The lines left out of every operand: `blank` ones, if set; comments, whose
+/// first non-whitespace characters are the `comments` prefix, if set; and
+/// those the `unmatched` regular expression doesn't match, if set
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Skipped {
+    pub blank: Option<Blank>,
+    pub comments: Option<Vec<u8>>,
+    pub unmatched: Option<KeyRegex>,
+}
+
+impl Skipped {
+    /// Are any lines left out?
+    fn any(&self) -> bool {
+        self.blank.is_some() || self.comments.is_some() || self.unmatched.is_some()
+    }
+
+    /// Is `line` (without its terminator) left out?
+    fn includes(&self, line: &[u8]) -> bool {
+        self.blank.is_some_and(|blank| blank.is(line))
+            || self
+                .comments
+                .as_ref()
+                .is_some_and(|prefix| line.trim_start_with(char::is_whitespace).starts_with(prefix))
+            || self.unmatched.as_ref().is_some_and(|regex| !regex.matches(line))
+    }
+}
+
+/// The records of the first operand, `contents`, that `skipped` doesn't
+/// include: its lines, or with `Framing::Csv`, its CSV records (and with
+/// `Framing::Separated`, the records its delimiter separates)
+fn keep_unskipped(contents: &[u8], framing: &Framing, skipped: &Skipped) -> Vec<u8> {
+    let mut kept = Vec::with_capacity(contents.len());
+    let mut rest = contents;
+    while !rest.is_empty() {
+        let end = match framing {
+            Framing::Csv => csv_record_end(rest),
+            _ => rest.find(framing.terminator()),
+        };
+        let terminated = |n| n + framing.terminator().len();
+        let (record, tail) = rest.split_at(end.map_or(rest.len(), terminated));
+        let unterminated = match framing {
+            Framing::Separated(delimiter) => record.strip_suffix(&delimiter[..]).unwrap_or(record),
+            _ => record.trim_end_with(|c| c == '\n' || c == '\r'),
+        };
+        if !skipped.includes(unterminated) {
+            kept.extend_from_slice(record);
+        }
+        rest = tail;
+    }
+    kept
+}
+
+/// How to read operands:
+/// * `framing` says how to divide them into records. Unless `framing` is text
+///   (`Framing::Lines` or `Framing::Csv`), the input is opaque bytes, so we
+///   don't decode UTF-16.
+/// * With `utf8` set, each line is checked for valid UTF-8.
+/// * With `strip_boms` set, Byte Order Marks are stripped from the start of
+///   each text line but the first.
+/// * The lines `skipped` includes are left out.
+/// * `labels` maps operand paths to names for messages to use instead (helpful
+///   when the path is something like `/dev/fd/63`).
+/// * `contents` maps operand paths to contents to use instead of reading the
+///   files (as `--state` does; see the `state` module).
+/// * With `sniff_encoding` set, text without a Byte Order Mark is decoded from
+///   the encoding it seems to be in, rather than assumed to be UTF-8; with
+///   `report_encodings` set too, we say on stderr which encoding we chose.
+/// * Once `interrupt` is requested, we stop reading.
+#[derive(Clone, Debug, Default)]
+pub struct ReadOptions {
+    pub framing: Framing,
+    pub utf8: Option<Utf8Check>,
+    pub strip_boms: bool,
+    pub skipped: Skipped,
+    pub labels: HashMap<PathBuf, String>,
+    pub contents: HashMap<PathBuf, Arc<[u8]>>,
+    pub sniff_encoding: bool,
+    pub report_encodings: bool,
+    pub interrupt: Interrupt,
+}
+
+impl ReadOptions {
+    /// How messages should refer to the operand at `path`: by its label, if it
+    /// has one
+    #[must_use]
+    pub fn display(&self, path: &Path) -> String {
+        match self.labels.get(path) {
+            Some(label) => label.clone(),
+            None if use_stdin(path) => "<stdin>".to_string(),
+            None => path.display().to_string(),
+        }
+    }
+
+    /// We only look for Byte Order Marks in text
+    fn strips_boms(&self) -> bool {
+        self.strip_boms && self.framing.is_text()
+    }
+}
+
+/// The UTF-8 Byte Order Mark
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Remove the Byte Order Mark from the start of each line of `contents` but the
+/// first, warning if there were any.
+fn strip_inner_boms(contents: Vec<u8>, path_display: &str) -> Vec<u8> {
+    if contents.find(b"\n\xEF\xBB\xBF").is_none() {
+        return contents;
+    }
+    let mut stripped = Vec::with_capacity(contents.len());
+    let mut count = 0;
+    for (n, line) in contents.lines_with_terminator().enumerate() {
+        match line.strip_prefix(BOM) {
+            Some(rest) if n > 0 => {
+                stripped.extend_from_slice(rest);
+                count += 1;
+            }
+            _ => stripped.extend_from_slice(line),
+        }
+    }
+    warn_of_inner_boms(path_display, count);
+    stripped
+}
+
+fn warn_of_inner_boms(path_display: &str, count: usize) {
+    if count > 0 {
+        let s = if count == 1 { "" } else { "s" };
+        eprintln!(
+            "zet: {path_display}: stripped {count} Byte Order Mark{s} from the middle of the input"
+        );
+    }
+}
+
 /// The Unix convention: if a file argument is `-`, use `stdin`.
 fn use_stdin(path: &Path) -> bool {
     path.to_string_lossy() == "-"
 }
+/// Windows limits ordinary paths to `MAX_PATH` (260) characters; to open a file
+/// with a longer path, we must give it in extended-length form (`\\?\C:\...`).
+#[cfg(windows)]
+fn openable(path: &Path) -> Cow<'_, Path> {
+    const MAX_PATH: usize = 260;
+    let Ok(cwd) = std::env::current_dir() else { return Cow::Borrowed(path) };
+    let absolute = cwd.join(path);
+    if absolute.as_os_str().len() < MAX_PATH {
+        return Cow::Borrowed(path);
+    }
+    match absolute.to_str().and_then(extended_length) {
+        Some(long) => Cow::Owned(PathBuf::from(long)),
+        None => Cow::Borrowed(path),
+    }
+}
+#[cfg(not(windows))]
+fn openable(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+/// The extended-length form of an `absolute` Windows path, either `C:\...` or
+/// `\\server\share\...`, or `None` if it's neither. Windows passes extended-length
+/// paths to the file system unaltered, so we do its usual cleanup ourselves:
+/// turning `/` into `\`, and removing `.` and `..` components.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn extended_length(absolute: &str) -> Option<String> {
+    let absolute = absolute.replace('/', r"\");
+    let (prefix, rest) = if let Some(unc) = absolute.strip_prefix(r"\\") {
+        if unc.starts_with(r"?\") || unc.starts_with(r".\") {
+            return None; // Already extended-length, or a device path
+        }
+        let mut parts = unc.splitn(3, '\\');
+        let (server, share) = (parts.next()?, parts.next()?);
+        (format!(r"\\?\UNC\{server}\{share}"), parts.next().unwrap_or(""))
+    } else {
+        match absolute.as_bytes() {
+            [drive, b':', b'\\', ..] if drive.is_ascii_alphabetic() => {
+                (format!(r"\\?\{}", &absolute[..2]), &absolute[3..])
+            }
+            _ => return None,
+        }
+    };
+    let mut components = Vec::new();
+    for component in rest.split('\\') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    Some(format!(r"{prefix}\{}", components.join(r"\")))
+}
+
 /// Return the contents of the first file named in `files` as a `Vec<u8>`, and
-/// an `ExactSizeIterator` over the subsequent arguments.
+/// an `ExactSizeIterator` over the subsequent arguments, each read as `options`
+/// says.
 #[must_use]
-pub fn first_and_rest(files: &[PathBuf]) -> Option<(Result<Vec<u8>>, Remaining)> {
-    fn all_of_stdin() -> Result<Vec<u8>> {
+pub fn first_and_rest(
+    files: &[PathBuf],
+    options: &ReadOptions,
+) -> Option<(Result<Vec<u8>>, Remaining)> {
+    fn all_of_stdin() -> io::Result<Vec<u8>> {
         let mut buffer = Vec::new();
-        io::stdin().read_to_end(&mut buffer).context("Can't read file: <stdin>")?;
-        Ok(decode_if_utf16(buffer))
+        io::stdin().read_to_end(&mut buffer)?;
+        Ok(buffer)
     }
 
     match files {
         [] => None,
         [first, rest @ ..] => {
-            let first_operand = if use_stdin(first) {
-                all_of_stdin()
+            let path_display = options.display(first);
+            let first_operand = match options.contents.get(first) {
+                Some(contents) => Ok(contents.to_vec()),
+                None if use_stdin(first) => all_of_stdin(),
+                None => fs::read(openable(first)),
+            };
+            let first_operand =
+                first_operand.with_context(|| format!("Can't read file: {path_display}"));
+            let (framing, utf8) = (&options.framing, options.utf8);
+            let first_operand = match framing {
+                Framing::Lines | Framing::Csv if options.sniff_encoding => {
+                    first_operand.map(|contents| {
+                        sniff_and_decode(contents, &path_display, options.report_encodings)
+                    })
+                }
+                Framing::Lines | Framing::Csv => first_operand.map(decode_if_utf16),
+                Framing::RawLines | Framing::Separated(_) | Framing::FixedWidth(_) => first_operand,
+            };
+            let first_operand = match utf8 {
+                Some(check) if !matches!(framing, Framing::FixedWidth(_)) => first_operand
+                    .map(|contents| check_first_operand(contents, &path_display, check)),
+                _ => first_operand,
+            };
+            let first_operand = if options.strips_boms() {
+                first_operand.map(|contents| strip_inner_boms(contents, &path_display))
             } else {
-                fs::read(first)
-                    .with_context(|| format!("Can't read file: {}", first.display()))
-                    .map(decode_if_utf16)
+                first_operand
+            };
+            let first_operand = if options.skipped.any() {
+                first_operand.map(|contents| keep_unskipped(&contents, framing, &options.skipped))
+            } else {
+                first_operand
             };
             let rest = rest.to_vec();
-            Some((first_operand, Remaining::from(rest)))
+            let rest = Remaining {
+                files: rest.into_iter(),
+                literals: Vec::new().into_iter(),
+                options: options.clone(),
+            };
+            Some((first_operand, rest))
         }
     }
 }
 
+/// An `ExactSizeIterator` over all the operands in `files`, each read as
+/// `options` says — for `--stream`, which can't wait for the first operand to
+/// be read into memory.
+#[must_use]
+pub fn all(files: Vec<PathBuf>, options: &ReadOptions) -> Remaining {
+    Remaining {
+        files: files.into_iter(),
+        literals: Vec::new().into_iter(),
+        options: options.clone(),
+    }
+}
+
 /// Decode UTF-16 to UTF-8 if we see a UTF-16 Byte Order Mark at the beginning of `candidate`.
 /// Otherwise return `candidate` unchanged
 fn decode_if_utf16(candidate: Vec<u8>) -> Vec<u8> {
@@ -55,7 +365,7 @@ fn decode_if_utf16(candidate: Vec<u8>) -> Vec<u8> {
     //
     // "with BOM handling" means that the UTF-16 BOM is translated to a UTF-8 BOM
     //
-    if let Some((enc, _)) = encoding_rs::Encoding::for_bom(&candidate) {
+    if let Some((enc, _)) = Encoding::for_bom(&candidate) {
         if [encoding_rs::UTF_16LE, encoding_rs::UTF_16BE].contains(&enc) {
             let (translated, _had_malformed_sequences) =
                 enc.decode_without_bom_handling(&candidate);
@@ -65,79 +375,314 @@ fn decode_if_utf16(candidate: Vec<u8>) -> Vec<u8> {
     candidate
 }
 
+/// How many bytes at the start of each operand `--sniff-encoding` looks at
+const SNIFF_SAMPLE: usize = 64 * 1024;
+
+/// For `--sniff-encoding`: decode `contents` from the encoding `sniffed` picks
+/// for it (if it isn't UTF-8)
+fn sniff_and_decode(contents: Vec<u8>, path_display: &str, report: bool) -> Vec<u8> {
+    let sample = &contents[..contents.len().min(SNIFF_SAMPLE)];
+    match sniffed(sample, path_display, report) {
+        None => decode_if_utf16(contents),
+        Some(encoding) => {
+            encoding.decode_without_bom_handling(&contents).0.into_owned().into_bytes()
+        }
+    }
+}
+
+/// The encoding to decode an operand from, judging by `sample`, its first few
+/// bytes — or `None` if it has a Byte Order Mark (which decides the encoding)
+/// or looks like UTF-8. With `report` set, we say on stderr what we decided.
+fn sniffed(sample: &[u8], path_display: &str, report: bool) -> Option<&'static Encoding> {
+    let (encoding, sniffed) = match Encoding::for_bom(sample) {
+        Some((encoding, _)) => (encoding, false),
+        None => (sniff(sample), true),
+    };
+    if report {
+        let why = if sniffed { "sniffed" } else { "from its Byte Order Mark" };
+        eprintln!("zet: {path_display}: {} ({why})", encoding.name());
+    }
+    (sniffed && encoding != encoding_rs::UTF_8).then_some(encoding)
+}
+
+/// The most likely encoding of `sample`, which has no Byte Order Mark. Text
+/// that's mostly ASCII has a zero byte in every other position when encoded in
+/// UTF-16, so we look for that first (zero bytes being valid UTF-8). Otherwise
+/// it's UTF-8 if it's valid UTF-8 (allowing for a character cut off at the
+/// end of the sample), and if not, windows-1252, the usual encoding of text
+/// from older Western systems.
+fn sniff(sample: &[u8]) -> &'static Encoding {
+    let zeros_at = |parity| sample.iter().skip(parity).step_by(2).filter(|&&b| b == 0).count();
+    let (even, odd, pairs) = (zeros_at(0), zeros_at(1), sample.len() / 2);
+    // Zeros in over half the pairs' second bytes, and in at most 5% of their
+    // first bytes, mean UTF-16LE (and vice versa for UTF-16BE)
+    let mostly_zeros = |zeros: usize, others: usize| zeros * 2 > pairs && others * 20 <= pairs;
+    if mostly_zeros(odd, even) {
+        return encoding_rs::UTF_16LE;
+    }
+    if mostly_zeros(even, odd) {
+        return encoding_rs::UTF_16BE;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => encoding_rs::UTF_8,
+        Err(err) if err.error_len().is_none() => encoding_rs::UTF_8,
+        Err(_) => encoding_rs::WINDOWS_1252,
+    }
+}
+
 /// The first operand is read into memory in its entirety, but that's not
 /// efficient for the second and subsequent operands.  The `Remaining`
-/// structure is an `ExactSizeIterator` over those operands.
+/// structure is an `ExactSizeIterator` over those operands: files, then any
+/// `--literal` operands.
 pub struct Remaining {
     files: std::vec::IntoIter<PathBuf>,
+    literals: std::vec::IntoIter<Vec<u8>>,
+    options: ReadOptions,
+}
+
+impl Remaining {
+    /// Follow the files with `literals`, operands given on the command line as
+    /// newline-separated lines
+    #[must_use]
+    pub fn and_literals(self, literals: Vec<Vec<u8>>) -> Self {
+        Remaining { literals: literals.into_iter(), ..self }
+    }
 }
 
 impl From<Vec<PathBuf>> for Remaining {
     fn from(files: Vec<PathBuf>) -> Self {
-        Remaining { files: files.into_iter() }
+        all(files, &ReadOptions::default())
     }
 }
 
 impl Iterator for Remaining {
     type Item = Result<NextOperand>;
     fn next(&mut self) -> Option<Self::Item> {
-        self.files.next().map(|path| reader_for(&path))
+        match self.files.next() {
+            Some(path) => Some(reader_for(&path, &self.options)),
+            None => self.literals.next().map(|literal| Ok(literal_reader(literal, &self.options))),
+        }
     }
 }
 
 impl ExactSizeIterator for Remaining {
     fn len(&self) -> usize {
-        self.files.len()
+        self.files.len() + self.literals.len()
     }
 }
 
+/// How messages refer to `--literal` operands
+pub const LITERAL_DISPLAY: &str = "<literal>";
+
 /// `NextOperand` is the `Item` type for the `Remaining` iterator. For a given
-/// file path, the `reader` field is a reader for the file with that path, and
-/// `path_display` is the path formatted for use in error messages.
+/// file path, the `reader` field is a reader for the file with that path,
+/// `path_display` is the path (or label) formatted for use in error messages,
+/// `framing` says how to divide the file into records, `utf8` says whether
+/// to check lines for valid UTF-8, `strip_boms` says whether to strip Byte
+/// Order Marks from the start of lines after the first, and `skipped` says
+/// which lines to leave out.
 pub struct NextOperand {
     path_display: String,
     reader: Box<dyn io::BufRead>,
+    framing: Framing,
+    utf8: Option<Utf8Check>,
+    strip_boms: bool,
+    skipped: Skipped,
+    interrupt: Interrupt,
 }
 
 /// The reader for a second or subsequent operand is a buffered reader with the
 /// ability to decode UTF-16 files. I think this results in double-buffering,
 /// with one buffer within the `DecodeReaderBytes` value, and another in the
 /// `BufReader` that wraps it. I don't know how to work around that.
+///
+/// Binary (fixed-width) records and raw lines aren't decoded; the
+/// `bom_sniffing(false)` and `strip_bom(false)` settings make the decoder pass
+/// them through unchanged. With `--sniff-encoding`, we read a sample of the
+/// operand to choose the encoding, then put the sample back in front of the
+/// rest.
 #[allow(trivial_casts)]
-fn reader_for(path: &Path) -> Result<NextOperand> {
-    fn decoder<R: Read>(f: R) -> DecodeReaderBytes<R, Vec<u8>> {
-        DecodeReaderBytesBuilder::new()
-            .bom_sniffing(true)
-            .strip_bom(true)
-            .utf8_passthru(true)
-            .build(f)
-    }
-    let (path_display, reader) = if use_stdin(path) {
-        let path_display = "<stdin>".to_string();
-        let reader = decoder(io::stdin().lock());
-        (path_display, Box::new(io::BufReader::new(reader)) as Box<dyn io::BufRead>)
+fn reader_for(path: &Path, options: &ReadOptions) -> Result<NextOperand> {
+    let framing = options.framing.clone();
+    let text = framing.is_text();
+    let path_display = options.display(path);
+    let source = if let Some(contents) = options.contents.get(path) {
+        Box::new(io::Cursor::new(Arc::clone(contents))) as Box<dyn Read>
+    } else if use_stdin(path) {
+        Box::new(io::stdin().lock()) as Box<dyn Read>
+    } else {
+        let file = File::open(openable(path))
+            .with_context(|| format!("Can't open file: {path_display}"))?;
+        Box::new(file) as Box<dyn Read>
+    };
+    let (source, encoding) = if text && options.sniff_encoding {
+        let mut sample = Vec::new();
+        let mut source = source;
+        (&mut source)
+            .take(SNIFF_SAMPLE as u64)
+            .read_to_end(&mut sample)
+            .with_context(|| format!("Error reading file: {path_display}"))?;
+        let encoding = sniffed(&sample, &path_display, options.report_encodings);
+        (Box::new(io::Cursor::new(sample).chain(source)) as Box<dyn Read>, encoding)
     } else {
-        let path_display = format!("{}", path.display());
-        let reader =
-            decoder(File::open(path).with_context(|| format!("Can't open file: {path_display}"))?);
-        (path_display, Box::new(io::BufReader::new(reader)) as Box<dyn io::BufRead>)
+        (source, None)
     };
-    Ok(NextOperand { path_display, reader })
+    let decoder = DecodeReaderBytesBuilder::new()
+        .encoding(encoding)
+        .bom_sniffing(text)
+        .strip_bom(text)
+        .utf8_passthru(true)
+        .build(source);
+    let reader = Box::new(io::BufReader::new(decoder)) as Box<dyn io::BufRead>;
+    let (utf8, strip_boms) = (options.utf8, options.strips_boms());
+    let (skipped, interrupt) = (options.skipped.clone(), options.interrupt.clone());
+    Ok(NextOperand { path_display, reader, framing, utf8, strip_boms, skipped, interrupt })
 }
+/// A `--literal` operand is already in memory, and is plain text
+fn literal_reader(literal: Vec<u8>, options: &ReadOptions) -> NextOperand {
+    NextOperand {
+        path_display: LITERAL_DISPLAY.to_string(),
+        reader: Box::new(io::Cursor::new(literal)),
+        framing: options.framing.clone(),
+        utf8: options.utf8,
+        strip_boms: false,
+        skipped: options.skipped.clone(),
+        interrupt: options.interrupt.clone(),
+    }
+}
+
 impl LaterOperand for NextOperand {
-    /// A convenience wrapper around `bstr::for_byte_line`
+    /// A convenience wrapper around `bstr::for_byte_line`, or for raw lines
+    /// `bstr::for_byte_record`, or for fixed-width records, `for_each_record`
     fn for_byte_line(self, mut for_each_line: impl FnMut(&[u8])) -> Result<()> {
-        let NextOperand { mut reader, path_display } = self;
-        reader
-            .for_byte_line(|line| {
-                for_each_line(line);
-                Ok(true)
-            })
-            .with_context(|| format!("Error reading file: {path_display}"))?;
+        self.for_byte_line_while(|line| {
+            for_each_line(line);
+            true
+        })
+    }
+
+    /// Stops reading as soon as `for_each_line` returns `false` (or the user
+    /// interrupts us)
+    fn for_byte_line_while(self, mut for_each_line: impl FnMut(&[u8]) -> bool) -> Result<()> {
+        let NextOperand { mut reader, path_display, framing, utf8, strip_boms, skipped, interrupt } =
+            self;
+        let (mut line_number, mut boms) = (0, 0);
+        let mut each_line = |mut line: &[u8]| {
+            line_number += 1;
+            if strip_boms && line_number > 1 {
+                if let Some(rest) = line.strip_prefix(BOM) {
+                    line = rest;
+                    boms += 1;
+                }
+            }
+            let keep = utf8.map_or(true, |check| check.keeps(line, &path_display, line_number))
+                && !skipped.includes(line);
+            let going = if keep { for_each_line(line) } else { true };
+            Ok(going && !interrupt.requested())
+        };
+        match framing {
+            Framing::Lines => reader.for_byte_line(&mut each_line),
+            Framing::RawLines => reader.for_byte_record(b'\n', &mut each_line),
+            Framing::Separated(delimiter) => match delimiter[..] {
+                [byte] => reader.for_byte_record(byte, &mut each_line),
+                _ => for_each_separated_record(&mut reader, &delimiter, &mut each_line),
+            },
+            Framing::Csv => for_each_csv_record(&mut reader, &mut each_line),
+            Framing::FixedWidth(width) => {
+                for_each_record(&mut reader, width.get(), &interrupt, |record| {
+                    line_number += 1;
+                    for_each_line(record)
+                })
+            }
+        }
+        .with_context(|| format!("Error reading file: {path_display}"))?;
+        warn_of_inner_boms(&path_display, boms);
         Ok(())
     }
 }
 
+/// Call `for_each_record` on each record from `reader` that `delimiter` (of
+/// more than one byte) ends, without the delimiter, until it returns `false`.
+/// We split at the delimiter's last byte, and gather the pieces until the
+/// record gathered ends with the whole delimiter.
+fn for_each_separated_record(
+    mut reader: &mut dyn io::BufRead,
+    delimiter: &[u8],
+    mut for_each_record: impl FnMut(&[u8]) -> io::Result<bool>,
+) -> io::Result<()> {
+    let Some((&last, _)) = delimiter.split_last() else {
+        return Ok(());
+    };
+    let (mut record, mut going) = (Vec::new(), true);
+    reader.for_byte_record_with_terminator(last, |piece| {
+        record.extend_from_slice(piece);
+        if let Some(complete) = record.strip_suffix(delimiter) {
+            going = for_each_record(complete)?;
+            record.clear();
+        }
+        Ok(going)
+    })?;
+    if going && !record.is_empty() {
+        for_each_record(&record)?;
+    }
+    Ok(())
+}
+
+/// Call `for_each_record` on each CSV record from `reader` (without its `\n` or
+/// `\r\n` terminator), until it returns `false`. A record goes on past the end
+/// of a line that leaves a double-quoted field open.
+fn for_each_csv_record(
+    mut reader: &mut dyn io::BufRead,
+    mut for_each_record: impl FnMut(&[u8]) -> io::Result<bool>,
+) -> io::Result<()> {
+    let (mut record, mut quoted) = (Vec::new(), false);
+    let mut going = true;
+    reader.for_byte_record(b'\n', |line| {
+        record.extend_from_slice(line);
+        quoted = line.iter().fold(quoted, |quoted, &b| quoted ^ (b == b'"'));
+        if quoted {
+            record.push(b'\n');
+            return Ok(true);
+        }
+        going = for_each_record(record.strip_suffix(b"\r").unwrap_or(&record))?;
+        record.clear();
+        Ok(going)
+    })?;
+    if going && !record.is_empty() {
+        for_each_record(&record)?;
+    }
+    Ok(())
+}
+
+/// Call `for_each_record` on each `width`-byte record from `reader`, until it
+/// returns `false`. The last record may be shorter, if the input length isn't a
+/// multiple of `width` — or until `interrupt` is requested.
+fn for_each_record(
+    reader: &mut dyn io::BufRead,
+    width: usize,
+    interrupt: &Interrupt,
+    mut for_each_record: impl FnMut(&[u8]) -> bool,
+) -> io::Result<()> {
+    let mut record = vec![0; width];
+    loop {
+        let mut len = 0;
+        while len < width {
+            match reader.read(&mut record[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if len > 0 && !for_each_record(&record[..len]) {
+            return Ok(());
+        }
+        if len < width || interrupt.requested() {
+            return Ok(());
+        }
+    }
+}
+
 #[allow(clippy::pedantic)]
 #[cfg(test)]
 mod test {
@@ -173,9 +718,74 @@ mod test {
         assert_eq!(decode_if_utf16(to_utf_16le(expected)), abominate(expected).as_bytes());
     }
 
+    #[test]
+    fn skipping_invalid_utf8_keeps_the_valid_lines_and_their_terminators() {
+        let contents = b"good\r\nbad \xff\nalso good\n\xc3".to_vec();
+        assert_eq!(check_first_operand(contents, "x", Utf8Check::Skip), b"good\r\nalso good\n");
+        let valid = "caf\u{e9}\n".as_bytes().to_vec();
+        assert_eq!(check_first_operand(valid.clone(), "x", Utf8Check::Skip), valid);
+    }
+
+    #[test]
+    fn skipped_lines_of_the_first_operand_are_left_out_with_their_terminators() {
+        let contents = b"a\r\n\n \t\r\nb\n";
+        let blank = |blank| Skipped { blank: Some(blank), ..Skipped::default() };
+        let lines = |skipped| keep_unskipped(contents, &Framing::Lines, &skipped);
+        assert_eq!(lines(blank(Blank::Empty)), b"a\r\n \t\r\nb\n");
+        assert_eq!(lines(blank(Blank::Whitespace)), b"a\r\nb\n");
+        let unmatched = Skipped { unmatched: Some("^b".parse().unwrap()), ..Skipped::default() };
+        assert_eq!(lines(unmatched), b"b\n");
+        let comments = Skipped { comments: Some(b"//".to_vec()), ..Skipped::default() };
+        assert_eq!(keep_unskipped(b"a\n// b\n  //c\n/d\n", &Framing::Lines, &comments), b"a\n/d\n");
+        let comments = Skipped { comments: Some(b"#".to_vec()), ..Skipped::default() };
+        let csv = b"# x\n\"a\n# b\"\nc\n";
+        assert_eq!(keep_unskipped(csv, &Framing::Csv, &comments), b"\"a\n# b\"\nc\n");
+    }
+
+    #[test]
+    fn only_boms_at_the_start_of_later_lines_are_stripped() {
+        let contents = abominate("one\n\u{FEFF}two\r\nthree \u{FEFF}\n\u{FEFF}");
+        let expected = abominate("one\ntwo\r\nthree \u{FEFF}\n");
+        assert_eq!(strip_inner_boms(contents.into_bytes(), "x"), expected.as_bytes());
+    }
+
+    #[test]
+    fn extended_length_paths_are_absolute_and_normalized() {
+        let long = |path| extended_length(path).unwrap();
+        assert_eq!(long(r"C:\Users\me\.\build\..\src/a.txt"), r"\\?\C:\Users\me\src\a.txt");
+        assert_eq!(long(r"\\server\share\dir\..\b.txt"), r"\\?\UNC\server\share\b.txt");
+        assert_eq!(long(r"C:\.."), r"\\?\C:\");
+        assert_eq!(extended_length(r"\\?\C:\already\long"), None);
+        assert_eq!(extended_length(r"relative\path"), None);
+        assert_eq!(extended_length(r"\\server"), None);
+    }
+
+    #[test]
+    fn sniffing_tells_utf8_from_utf16_and_windows_1252() {
+        assert_eq!(sniff("caf\u{e9}\n".as_bytes()), encoding_rs::UTF_8);
+        assert_eq!(sniff(&"caf\u{e9}".as_bytes()[..4]), encoding_rs::UTF_8);
+        assert_eq!(sniff(b"caf\xe9\n"), encoding_rs::WINDOWS_1252);
+        assert_eq!(sniff(&to_utf_16le("line\n")[2..]), encoding_rs::UTF_16LE);
+        assert_eq!(sniff(&to_utf_16be("line\n")[2..]), encoding_rs::UTF_16BE);
+        let decoded = sniff_and_decode(b"caf\xe9\n".to_vec(), "x", false);
+        assert_eq!(decoded, "caf\u{e9}\n".as_bytes());
+    }
+
     #[test]
     fn utf_16be_is_translated_to_utf8() {
         let expected = "The cute red crab\n jumps over the lazy blue gopher\n";
         assert_eq!(decode_if_utf16(to_utf_16be(expected)), abominate(expected).as_bytes());
     }
+
+    #[test]
+    fn separated_records_end_only_at_the_whole_delimiter() {
+        let mut records = Vec::new();
+        let mut reader = &b"a;b;;c;;;d;"[..];
+        for_each_separated_record(&mut reader, b";;", |record| {
+            records.push(record.to_vec());
+            Ok(true)
+        })
+        .unwrap();
+        assert_eq!(records, [&b"a;b"[..], b"c", b";d;"]);
+    }
 }
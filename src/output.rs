@@ -0,0 +1,116 @@
+//! Where the `zet` command writes its output: to standard output, or with
+//! `--output`, to a file. A file is written atomically: the output goes to a
+//! temporary file in the same directory, which is renamed into place only
+//! once all the output is written. So a run that fails or is killed partway
+//! through never leaves a truncated result behind, and the file's old contents
+//! survive until the new ones are complete. That also makes it safe for the
+//! output file to be one of the input files.
+//!
+//! With `-q`, the output goes nowhere: we just note whether there was any.
+use anyhow::{Context, Result};
+use is_terminal::IsTerminal;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// A destination for output. Call `commit` once it's all written.
+pub enum Output {
+    /// Standard output (buffered, unless it's a terminal)
+    Stdout(Box<dyn Write>),
+    /// A temporary file, to be renamed to `path`
+    File { path: PathBuf, temp: BufWriter<NamedTempFile> },
+    /// Nowhere, for `-q`, noting whether anything was written
+    Quiet { wrote: bool },
+}
+
+impl Output {
+    /// Output to the file at `path`, or if it's `None`, to standard output
+    pub fn to(path: Option<&Path>) -> Result<Self> {
+        let Some(path) = path else {
+            let stdout = io::stdout();
+            if stdout.is_terminal() {
+                return Ok(Output::Stdout(Box::new(stdout.lock())));
+            }
+            return Ok(Output::Stdout(Box::new(BufWriter::new(stdout.lock()))));
+        };
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let temp = temp_file_for(path, dir)
+            .with_context(|| format!("Can't create a file in: {}", dir.display()))?;
+        Ok(Output::File { path: path.to_path_buf(), temp: BufWriter::new(temp) })
+    }
+
+    /// Output nowhere, for `-q`
+    #[must_use]
+    pub fn quiet() -> Self {
+        Output::Quiet { wrote: false }
+    }
+
+    /// For `-q` output, whether anything was written; otherwise `None`
+    #[must_use]
+    pub fn wrote_quietly(&self) -> Option<bool> {
+        match self {
+            Output::Quiet { wrote } => Some(*wrote),
+            _ => None,
+        }
+    }
+
+    /// Finish writing: flush standard output, or move the temporary file into
+    /// place
+    pub fn commit(self) -> Result<()> {
+        match self {
+            Output::Quiet { .. } => Ok(()),
+            Output::Stdout(mut out) => Ok(out.flush()?),
+            Output::File { path, temp } => {
+                let temp = temp.into_inner().map_err(io::IntoInnerError::into_error)?;
+                temp.persist(&path)
+                    .map_err(|err| err.error)
+                    .with_context(|| format!("Can't write file: {}", path.display()))?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A temporary file in `dir` for output to `path`. If `path` exists, the
+/// temporary file gets its permissions; otherwise (on Unix) it gets those of
+/// a newly created file, rather than the owner-only permissions temporary files
+/// usually have.
+fn temp_file_for(path: &Path, dir: &Path) -> io::Result<NamedTempFile> {
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(".zet-output-");
+    if let Ok(metadata) = path.metadata() {
+        let temp = builder.tempfile_in(dir)?;
+        temp.as_file().set_permissions(metadata.permissions())?;
+        return Ok(temp);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(std::fs::Permissions::from_mode(0o666));
+    }
+    builder.tempfile_in(dir)
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(out) => out.write(buf),
+            Output::File { temp, .. } => temp.write(buf),
+            Output::Quiet { wrote } => {
+                *wrote |= !buf.is_empty();
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(out) => out.flush(),
+            Output::File { temp, .. } => temp.flush(),
+            Output::Quiet { .. } => Ok(()),
+        }
+    }
+}
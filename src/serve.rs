@@ -0,0 +1,147 @@
+//! `zet serve` loads a baseline set of lines once (see `zet_core::baseline`)
+//! and then answers queries about it, so that a large baseline needn't be read
+//! again for every small batch of new lines. Queries come one per line, on
+//! standard input or (with `--socket PATH`) from each connection to a Unix
+//! socket, and each gets a response:
+//!
+//! * `contains LINE` is answered by `yes` or `no`.
+//! * `diff-against FILE` is answered by `lines N`, followed by the `N` distinct
+//!   lines of `FILE` that aren't in the baseline.
+//! * `quit` ends the session (or for standard input, the server).
+//!
+//! A query that can't be answered gets `error MESSAGE`. Lines are compared as
+//! the command line says — with `--key`, for instance, `contains` asks whether
+//! any baseline line has the same key.
+use crate::baseline::Baseline;
+use crate::operands::{first_and_rest, ReadOptions};
+use anyhow::{bail, Context, Result};
+use bstr::{io::BufReadExt, ByteSlice};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+/// Where queries come from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    /// Standard input, with responses on standard output
+    Stdio,
+    /// Connections to a Unix socket at this path
+    Socket(PathBuf),
+}
+
+/// Answer queries about `baseline` from `endpoint` until told to stop (or
+/// forever, for a socket)
+pub fn run(baseline: &Baseline, endpoint: &Endpoint, read: &ReadOptions) -> Result<()> {
+    match endpoint {
+        Endpoint::Stdio => session(baseline, read, io::stdin().lock(), io::stdout().lock()),
+        Endpoint::Socket(path) => listen(baseline, read, path),
+    }
+}
+
+#[cfg(unix)]
+fn listen(baseline: &Baseline, read: &ReadOptions, path: &std::path::Path) -> Result<()> {
+    use std::os::unix::net::UnixListener;
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Can't listen on socket: {}", path.display()))?;
+    std::thread::scope(|scope| {
+        for stream in listener.incoming() {
+            let stream = stream.context("Can't accept a connection")?;
+            scope.spawn(move || {
+                let input = io::BufReader::new(&stream);
+                if let Err(err) = session(baseline, read, input, &stream) {
+                    eprintln!("zet: {err:#}");
+                }
+            });
+        }
+        Ok(())
+    })
+}
+
+#[cfg(not(unix))]
+fn listen(_baseline: &Baseline, _read: &ReadOptions, _path: &std::path::Path) -> Result<()> {
+    bail!("--socket needs Unix domain sockets, which this platform doesn't have")
+}
+
+/// Answer each query from `input` on `out`, until `input` ends or has a `quit`
+/// query
+fn session(
+    baseline: &Baseline,
+    read: &ReadOptions,
+    mut input: impl BufRead,
+    mut out: impl Write,
+) -> Result<()> {
+    input.for_byte_line(|query| respond(baseline, read, query, &mut out))?;
+    Ok(())
+}
+
+/// Write the response to `query`, returning `false` if it asks us to stop
+fn respond(
+    baseline: &Baseline,
+    read: &ReadOptions,
+    query: &[u8],
+    out: &mut impl Write,
+) -> io::Result<bool> {
+    let (verb, argument) = query.split_once_str(" ").unwrap_or((query, b""));
+    let answer = match verb {
+        b"quit" => return Ok(false),
+        b"contains" => baseline
+            .contains(argument)
+            .map(|yes| out.write_all(if yes { b"yes\n" } else { b"no\n" })),
+        b"diff-against" => diff_against(baseline, read, argument).map(|lines| {
+            writeln!(out, "lines {}", lines.len())?;
+            lines.iter().try_for_each(|line| {
+                out.write_all(line)?;
+                out.write_all(b"\n")
+            })
+        }),
+        _ => Err(anyhow::anyhow!("Unknown query: {}", verb.as_bstr())),
+    };
+    match answer {
+        Ok(written) => written?,
+        Err(err) => writeln!(out, "error {}", format!("{err:#}").replace('\n', " "))?,
+    }
+    out.flush()?;
+    Ok(true)
+}
+
+/// The lines of the file at `path` that aren't in `baseline`
+fn diff_against(baseline: &Baseline, read: &ReadOptions, path: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let path = PathBuf::from(path.to_str().context("File names must be UTF-8")?);
+    if path.as_os_str().is_empty() || path.as_os_str() == "-" {
+        bail!("diff-against needs a file name (standard input can't be read)")
+    }
+    let Some((contents, _)) = first_and_rest(&[path], read) else {
+        bail!("This can't happen: first_and_rest was given a path")
+    };
+    baseline.missing(&contents?)
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::operands::NextOperand;
+    use crate::operations::{OpName, Options};
+    use crate::set::Framing;
+
+    #[test]
+    fn each_query_gets_a_response_until_quit() {
+        let options = Options::new(OpName::Union);
+        let baseline =
+            Baseline::new(&options, b"a\nb\n", std::iter::empty::<Result<NextOperand>>()).unwrap();
+        let dir = std::env::temp_dir().join(format!("zet-serve-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let batch = dir.join("batch.txt");
+        std::fs::write(&batch, "c\na\nd\nc\n").unwrap();
+
+        let read = ReadOptions { framing: Framing::Lines, ..ReadOptions::default() };
+        let queries = format!(
+            "contains a\ncontains z\ndiff-against {}\nfrobnicate\nquit\ncontains b\n",
+            batch.display()
+        );
+        let mut out = Vec::new();
+        session(&baseline, &read, queries.as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, "yes\nno\nlines 2\nc\nd\nerror Unknown query: frobnicate\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
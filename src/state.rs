@@ -0,0 +1,224 @@
+//! With `--state FILE`, `zet` follows append-only files (logs, say) from one
+//! run to the next. The state file records, for each file, how many of its
+//! bytes have been read, and the distinct lines found in them. The next run
+//! reads just the bytes appended since, merges their lines into the saved ones,
+//! and treats the merged lines as the file's contents — so the result is what
+//! reading each whole file again would give, at the cost of reading only what's
+//! new.
+//!
+//! Since a file's lines are remembered as a set, `--state` is only for
+//! operations in which occurrence counts and line numbers don't matter. A file
+//! that's shorter than when it was last read is taken to have been truncated or
+//! rotated, and is read from the start. A partial last line (one still being
+//! written) is left for the next run.
+//!
+//! The state file starts with a `zet-state 1` line. Then for each file comes a
+//! line `OFFSET COUNT PATH`, followed by the file's `COUNT` distinct lines.
+use anyhow::{bail, Context, Result};
+use bstr::ByteSlice;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The first line of a state file
+const HEADER: &[u8] = b"zet-state 1";
+
+/// The files followed so far, and where their state is saved
+#[derive(Debug)]
+pub struct State {
+    path: PathBuf,
+    files: Vec<Followed>,
+}
+
+/// What we know of one followed file
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Followed {
+    path: PathBuf,
+    /// The number of bytes read so far
+    offset: u64,
+    /// The distinct lines in those bytes (without their `\n` terminators), in
+    /// order of first appearance
+    lines: Vec<Vec<u8>>,
+}
+
+impl State {
+    /// The state saved at `path`, or an empty state if there's no file there
+    /// yet
+    pub fn load(path: &Path) -> Result<State> {
+        let files = match fs::read(path) {
+            Ok(contents) => parse(&contents)
+                .with_context(|| format!("Can't use state file: {}", path.display()))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Can't read file: {}", path.display()))
+            }
+        };
+        Ok(State { path: path.to_path_buf(), files })
+    }
+
+    /// Read what's been appended to each of `paths` since it was last read,
+    /// and return the contents each should be treated as having: the distinct
+    /// lines read from it so far. (See `ReadOptions::contents`.)
+    pub fn catch_up(&mut self, paths: &[PathBuf]) -> Result<HashMap<PathBuf, Arc<[u8]>>> {
+        let mut contents = HashMap::new();
+        for path in paths {
+            let known = self.files.iter().position(|followed| &followed.path == path);
+            let index = known.unwrap_or_else(|| {
+                self.files.push(Followed { path: path.clone(), ..Followed::default() });
+                self.files.len() - 1
+            });
+            let followed = &mut self.files[index];
+            followed.catch_up()?;
+            contents.insert(path.clone(), followed.contents());
+        }
+        Ok(contents)
+    }
+
+    /// Write the state back to its file. We write a new file and rename it, so
+    /// the old state survives if we're interrupted.
+    pub fn save(&self) -> Result<()> {
+        let mut out = HEADER.to_vec();
+        out.push(b'\n');
+        for followed in &self.files {
+            let Followed { path, offset, lines } = followed;
+            out.extend_from_slice(
+                format!("{offset} {} {}\n", lines.len(), path.display()).as_bytes(),
+            );
+            for line in lines {
+                out.extend_from_slice(line);
+                out.push(b'\n');
+            }
+        }
+        let mut new = self.path.clone().into_os_string();
+        new.push(".new");
+        let display = self.path.display();
+        fs::write(&new, out).with_context(|| format!("Can't write state file: {display}"))?;
+        fs::rename(&new, &self.path).with_context(|| format!("Can't write state file: {display}"))
+    }
+}
+
+impl Followed {
+    /// Read the complete lines appended since `offset`, adding those we
+    /// haven't seen to `lines`
+    fn catch_up(&mut self) -> Result<()> {
+        let display = self.path.display();
+        let mut file =
+            File::open(&self.path).with_context(|| format!("Can't open file: {display}"))?;
+        let len = file.metadata().with_context(|| format!("Can't read file: {display}"))?.len();
+        if len < self.offset {
+            eprintln!("zet: {display}: shorter than when last read, so reading it from the start");
+            self.offset = 0;
+            self.lines.clear();
+        }
+        let mut appended = Vec::new();
+        file.seek(SeekFrom::Start(self.offset))
+            .and_then(|_| file.read_to_end(&mut appended))
+            .with_context(|| format!("Can't read file: {display}"))?;
+        if self.offset == 0
+            && (appended.starts_with(b"\xFF\xFE") || appended.starts_with(b"\xFE\xFF"))
+        {
+            bail!("{display}: --state can't follow UTF-16 files");
+        }
+        let complete = appended.rfind_byte(b'\n').map_or(0, |n| n + 1);
+        let mut seen: HashSet<&[u8]> = self.lines.iter().map(Vec::as_slice).collect();
+        let mut new = Vec::new();
+        for line in appended[..complete].lines_with_terminator() {
+            let line = line.strip_suffix(b"\n").unwrap_or(line);
+            if seen.insert(line) {
+                new.push(line.to_vec());
+            }
+        }
+        self.lines.extend(new);
+        self.offset += complete as u64;
+        Ok(())
+    }
+
+    /// The lines read so far, each with a `\n` terminator
+    fn contents(&self) -> Arc<[u8]> {
+        let mut contents = Vec::with_capacity(self.lines.iter().map(|line| line.len() + 1).sum());
+        for line in &self.lines {
+            contents.extend_from_slice(line);
+            contents.push(b'\n');
+        }
+        contents.into()
+    }
+}
+
+/// The followed files described by the `contents` of a state file
+fn parse(contents: &[u8]) -> Result<Vec<Followed>> {
+    let mut lines =
+        contents.lines_with_terminator().map(|line| line.strip_suffix(b"\n").unwrap_or(line));
+    if lines.next() != Some(HEADER) {
+        bail!("it doesn't start with `{}`", HEADER.as_bstr());
+    }
+    let mut files = Vec::new();
+    while let Some(header) = lines.next() {
+        let mut parts = header.splitn_str(3, " ").map(|part| part.to_str().ok());
+        let (Some(Some(offset)), Some(Some(count)), Some(Some(path))) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            bail!("`{}` should be `OFFSET COUNT PATH`", header.as_bstr());
+        };
+        let (Ok(offset), Ok(count)) = (offset.parse(), count.parse()) else {
+            bail!("`{}` should be `OFFSET COUNT PATH`", header.as_bstr());
+        };
+        let followed: Vec<_> = lines.by_ref().take(count).map(<[u8]>::to_vec).collect();
+        if followed.len() != count {
+            bail!("it ends before the last of the lines of {path}");
+        }
+        files.push(Followed { path: PathBuf::from(path), offset, lines: followed });
+    }
+    Ok(files)
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_complete_appended_lines_are_merged() {
+        let dir = std::env::temp_dir().join(format!("zet-state-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log.txt");
+        fs::write(&log, "a\nb\na\npart").unwrap();
+        let mut followed = Followed { path: log.clone(), ..Followed::default() };
+        followed.catch_up().unwrap();
+        assert_eq!((followed.offset, &*followed.contents()), (6, &b"a\nb\n"[..]));
+
+        fs::write(&log, "a\nb\na\npartial\nb\nc\n").unwrap();
+        followed.catch_up().unwrap();
+        assert_eq!((followed.offset, &*followed.contents()), (18, &b"a\nb\npartial\nc\n"[..]));
+
+        fs::write(&log, "z\n").unwrap();
+        followed.catch_up().unwrap();
+        assert_eq!((followed.offset, &*followed.contents()), (2, &b"z\n"[..]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn state_files_round_trip() {
+        let dir = std::env::temp_dir().join(format!("zet-state-round-trip-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state");
+        let files = vec![
+            Followed {
+                path: "x y.log".into(),
+                offset: 7,
+                lines: vec![b"".to_vec(), b"a b".to_vec()],
+            },
+            Followed { path: "z.log".into(), offset: 0, lines: vec![] },
+        ];
+        State { path: path.clone(), files }.save().unwrap();
+        let loaded = State::load(&path).unwrap();
+        assert_eq!(loaded.files[0].path, PathBuf::from("x y.log"));
+        assert_eq!(loaded.files[0].lines, [b"".to_vec(), b"a b".to_vec()]);
+        assert_eq!((loaded.files[1].offset, loaded.files.len()), (0, 2));
+
+        fs::write(&path, "zet-state 1\n7 3 x.log\na\n").unwrap();
+        assert!(State::load(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
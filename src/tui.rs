@@ -0,0 +1,237 @@
+//! `zet tui` sorts the lines of its operands into the regions of a Venn diagram
+//! (see `zet_core::venn`) and lets you browse them: how many distinct lines
+//! each operand has, how many lines are in each region, and the lines
+//! themselves, with a key to export the selected region to a file.
+//! Operands and regions are labelled as `zet_core::venn` says.
+use crate::venn::{label, Region, Venn};
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::borrow::Cow;
+use std::path::Path;
+
+const KEYS: &str = "↑/↓ region   PgUp/PgDn/Home/End scroll   e export   q quit";
+
+/// Browse the regions of `venn`, whose operands are called `names`, until the
+/// user quits
+pub fn browse(venn: &Venn, names: &[String]) -> Result<()> {
+    let mut browser = Browser::new(venn, names);
+    let mut terminal = ratatui::init();
+    let result = browser.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+/// The state of the browser
+struct Browser<'a> {
+    /// The operands' names and numbers of distinct lines
+    operands: Vec<(&'a str, usize)>,
+    regions: Vec<Region<'a>>,
+    venn: &'a Venn<'a>,
+    /// The index of the region whose lines are shown
+    selected: usize,
+    /// The index of the first line shown
+    scroll: usize,
+    /// The number of lines that fit on the screen, as of the last redraw
+    page: usize,
+    /// While exporting, the path typed so far
+    prompt: Option<String>,
+    /// A message for the status line, replacing the list of keys
+    message: Option<String>,
+    done: bool,
+}
+
+impl<'a> Browser<'a> {
+    fn new(venn: &'a Venn<'a>, names: &'a [String]) -> Self {
+        let operands = names.iter().map(String::as_str).zip(venn.cardinalities()).collect();
+        Browser {
+            operands,
+            regions: venn.regions(),
+            venn,
+            selected: 0,
+            scroll: 0,
+            page: 1,
+            prompt: None,
+            message: None,
+            done: false,
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        while !self.done {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.handle(key.code);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The lines of the selected region
+    fn lines(&self) -> &[&'a [u8]] {
+        self.regions.get(self.selected).map_or(&[], |region| &region.lines)
+    }
+
+    fn handle(&mut self, key: KeyCode) {
+        if let Some(prompt) = &mut self.prompt {
+            match key {
+                KeyCode::Char(c) => prompt.push(c),
+                KeyCode::Backspace => {
+                    prompt.pop();
+                }
+                KeyCode::Enter => {
+                    let path = std::mem::take(prompt);
+                    self.prompt = None;
+                    self.message = Some(match self.export(path.as_ref()) {
+                        Ok(1) => format!("Wrote 1 line to {path}"),
+                        Ok(lines) => format!("Wrote {lines} lines to {path}"),
+                        Err(err) => format!("Can't write {path}: {err}"),
+                    });
+                }
+                KeyCode::Esc => self.prompt = None,
+                _ => {}
+            }
+            return;
+        }
+        self.message = None;
+        let last_line = self.lines().len().saturating_sub(1);
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => self.done = true,
+            KeyCode::Up | KeyCode::Char('k') if self.selected > 0 => {
+                self.selected -= 1;
+                self.scroll = 0;
+            }
+            KeyCode::Down | KeyCode::Char('j') if self.selected + 1 < self.regions.len() => {
+                self.selected += 1;
+                self.scroll = 0;
+            }
+            KeyCode::PageDown | KeyCode::Char(' ') => {
+                self.scroll = (self.scroll + self.page).min(last_line);
+            }
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(self.page),
+            KeyCode::Home => self.scroll = 0,
+            KeyCode::End => self.scroll = last_line.saturating_sub(self.page - 1),
+            KeyCode::Char('e') if !self.regions.is_empty() => {
+                let label = self.regions[self.selected].label().replace(' ', "");
+                self.prompt = Some(format!("region-{label}.txt"));
+            }
+            _ => {}
+        }
+    }
+
+    /// Write the lines of the selected region to `path`, returning the number
+    /// of lines written
+    fn export(&self, path: &Path) -> Result<usize> {
+        let Some(region) = self.regions.get(self.selected) else { return Ok(0) };
+        let file = std::fs::File::create(path)?;
+        self.venn.write_region(region, std::io::BufWriter::new(file))?;
+        Ok(region.lines.len())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let operand_rows = u16::try_from(self.operands.len().min(8)).unwrap_or(8);
+        let [operands_area, main_area, status_area] = Layout::vertical([
+            Constraint::Length(operand_rows + 2),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [regions_area, lines_area] =
+            Layout::horizontal([Constraint::Percentage(30), Constraint::Percentage(70)])
+                .areas(main_area);
+
+        let operands = self.operands.iter().enumerate().map(|(n, (name, distinct))| {
+            Line::from(format!("{:>3}  {distinct:>10} distinct lines  {name}", label(n)))
+        });
+        let operands = Paragraph::new(operands.collect::<Vec<_>>());
+        frame.render_widget(operands.block(Block::bordered().title(" Operands ")), operands_area);
+
+        let regions = self
+            .regions
+            .iter()
+            .map(|region| Line::from(format!("{:<12} {:>10}", region.label(), region.lines.len())));
+        let regions = List::new(regions.collect::<Vec<_>>())
+            .block(Block::bordered().title(" Regions "))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(regions, regions_area, &mut state);
+
+        self.page = usize::from(lines_area.height.saturating_sub(2)).max(1);
+        let lines = self.lines();
+        let shown = lines.iter().skip(self.scroll).take(self.page);
+        let shown = shown.map(|line| Line::from(String::from_utf8_lossy(line).into_owned()));
+        let title = match self.regions.get(self.selected) {
+            None => Cow::Borrowed(" No lines "),
+            Some(region) => Cow::Owned(format!(
+                " {}: lines {}–{} of {} ",
+                region.label(),
+                self.scroll + 1,
+                (self.scroll + self.page).min(lines.len()),
+                lines.len()
+            )),
+        };
+        let lines = Paragraph::new(shown.collect::<Vec<_>>()).block(Block::bordered().title(title));
+        frame.render_widget(lines, lines_area);
+
+        let status = match (&self.prompt, &self.message) {
+            (Some(path), _) => format!("Export to (Enter to write, Esc to cancel): {path}"),
+            (None, Some(message)) => message.clone(),
+            (None, None) => KEYS.to_string(),
+        };
+        frame.render_widget(Paragraph::new(status), status_area);
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::operations::{OpName, Options};
+    use crate::set::LaterOperand;
+
+    struct Text(&'static str);
+    impl LaterOperand for Text {
+        fn for_byte_line(self, mut for_each_line: impl FnMut(&[u8])) -> Result<()> {
+            self.0.lines().for_each(|line| for_each_line(line.as_bytes()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn keys_move_between_regions_and_export_them() {
+        let rest = [Ok(Text("b\nc\n"))].into_iter();
+        let venn = Venn::new(&Options::new(OpName::Union), b"a\nb\n", rest).unwrap();
+        let names = ["x.txt".to_string(), "y.txt".to_string()];
+        let mut browser = Browser::new(&venn, &names);
+        assert_eq!(browser.operands, [("x.txt", 2), ("y.txt", 2)]);
+        let labels: Vec<_> = browser.regions.iter().map(Region::label).collect();
+        assert_eq!(labels, ["A", "B", "A B"]);
+
+        browser.handle(KeyCode::Down);
+        browser.handle(KeyCode::Down);
+        browser.handle(KeyCode::Down);
+        assert_eq!(browser.lines(), [b"b"]);
+        browser.handle(KeyCode::Up);
+        assert_eq!(browser.lines(), [b"c"]);
+
+        let dir = std::env::temp_dir().join(format!("zet-tui-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("region.txt");
+        browser.handle(KeyCode::Char('e'));
+        assert_eq!(browser.prompt.as_deref(), Some("region-B.txt"));
+        browser.prompt = Some(path.display().to_string());
+        browser.handle(KeyCode::Enter);
+        assert_eq!(std::fs::read(&path).unwrap(), b"c\n");
+        assert!(browser.message.as_ref().unwrap().starts_with("Wrote 1 line to"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        browser.handle(KeyCode::Char('q'));
+        assert!(browser.done);
+    }
+}
@@ -0,0 +1,116 @@
+//! With `--recursive`, directory operands stand for the files within them. The
+//! `Recursion` structure holds the traversal options, and its `expand` method
+//! replaces each directory in the list of operands with the files found by
+//! walking it (using the `ignore` crate, as `ripgrep` does, and like `ripgrep`
+//! respecting `.gitignore` and `.ignore` files by default).
+//!
+//! A directory tree may hold hundreds of thousands of files, so we walk it in
+//! parallel. The files are found in no particular order, so we then sort them:
+//! paths compare component by component, so the sorted order is that of a
+//! depth-first walk visiting each directory's entries in order of file name.
+use anyhow::{Context, Result};
+use ignore::{overrides::OverrideBuilder, WalkBuilder, WalkState};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+
+/// How to walk directory operands
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recursion {
+    /// Should we follow symbolic links found while walking a directory? If
+    /// not, they're skipped. (Operands named on the command line are always
+    /// followed.)
+    pub follow: bool,
+    /// Should we include hidden files and directories (those whose names start
+    /// with `.`)? If not, they're skipped, as `ripgrep` and `fd` skip them.
+    pub hidden: bool,
+    /// Should we disregard `.gitignore` and `.ignore` files? If not, files they
+    /// exclude are skipped. (As with `git`, `.gitignore` files count only
+    /// within a git repository.)
+    pub no_ignore: bool,
+    /// If any of these glob patterns are given, only files matching them are
+    /// included; files matching a pattern starting with `!` are excluded.
+    /// Patterns are matched against paths relative to the directory operand.
+    pub globs: Vec<String>,
+    /// If set, how many levels of directories to descend: with `Some(1)`, just
+    /// the files directly within each directory operand.
+    pub max_depth: Option<usize>,
+}
+
+impl Recursion {
+    /// Replace each directory in `paths` with the files beneath it. Other
+    /// operands (including `-` for standard input) are left alone. A symbolic
+    /// link that leads back to one of its own ancestors is reported and
+    /// skipped, rather than followed forever.
+    pub fn expand(&self, paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+        let mut expanded = Vec::with_capacity(paths.len());
+        for path in paths {
+            if path.is_dir() {
+                self.walk(&path, &mut expanded)?;
+            } else {
+                expanded.push(path);
+            }
+        }
+        Ok(expanded)
+    }
+
+    fn walk(&self, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        let mut globs = OverrideBuilder::new(dir);
+        for glob in &self.globs {
+            globs.add(glob).with_context(|| format!("Invalid --glob pattern: {glob}"))?;
+        }
+        let walker = WalkBuilder::new(dir)
+            .overrides(globs.build()?)
+            .max_depth(self.max_depth)
+            .standard_filters(false)
+            .follow_links(self.follow)
+            .hidden(!self.hidden)
+            .ignore(!self.no_ignore)
+            .git_ignore(!self.no_ignore)
+            .git_global(!self.no_ignore)
+            .git_exclude(!self.no_ignore)
+            .parents(!self.no_ignore)
+            .build_parallel();
+
+        let (sender, receiver) = mpsc::channel();
+        let failure = Mutex::new(None);
+        walker.run(|| {
+            let (sender, failure) = (sender.clone(), &failure);
+            Box::new(move |entry| match entry {
+                Ok(entry) => {
+                    if entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+                        let _ = sender.send(entry.into_path());
+                    }
+                    WalkState::Continue
+                }
+                Err(err) if is_loop(&err) => {
+                    eprintln!("zet: {err}");
+                    WalkState::Continue
+                }
+                Err(err) => {
+                    if let Ok(mut failure) = failure.lock() {
+                        failure.get_or_insert(err);
+                    }
+                    WalkState::Quit
+                }
+            })
+        });
+        drop(sender);
+
+        if let Some(err) = failure.into_inner().ok().flatten() {
+            return Err(err).with_context(|| format!("Can't read directory: {}", dir.display()));
+        }
+        let mut found: Vec<PathBuf> = receiver.into_iter().collect();
+        found.sort_unstable();
+        files.append(&mut found);
+        Ok(())
+    }
+}
+
+/// Is `err` a report of a symbolic link loop?
+fn is_loop(err: &ignore::Error) -> bool {
+    match err {
+        ignore::Error::Loop { .. } => true,
+        ignore::Error::WithPath { err, .. } | ignore::Error::WithDepth { err, .. } => is_loop(err),
+        _ => false,
+    }
+}
@@ -1,3 +1,4 @@
+#![cfg(feature = "cli")]
 use std::fs::File;
 use std::process::Command;
 
@@ -41,8 +42,20 @@ fn prints_version_with_V_or_version_flag() {
         assert!(output.lines().collect::<Vec<_>>().len() == 1);
     }
 }
-const OP_NAMES: [OpName; 7] =
-    [Intersect, Union, Diff, Single, SingleByFile, Multiple, MultipleByFile];
+
+#[test]
+fn prints_build_information_with_version_and_verbose_flags() {
+    let output = run(["--version --verbose"]).unwrap();
+    let output = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].contains(std::env!("CARGO_PKG_VERSION")));
+    assert!(lines[1].starts_with("features: ") && lines[1].contains("cli"));
+    assert!(lines[2].starts_with("target: "));
+    assert!(lines[3].starts_with("commit: "));
+}
+const OP_NAMES: [OpName; 8] =
+    [Intersect, Union, Diff, Single, SingleByFile, Multiple, MultipleByFile, Majority];
 fn subcommand_for(op: OpName) -> &'static str {
     match op {
         Union => "union",
@@ -52,9 +65,13 @@ fn subcommand_for(op: OpName) -> &'static str {
         SingleByFile => "single --file",
         Multiple => "multiple",
         MultipleByFile => "multiple --files",
+        Majority => "majority",
+        Top => "top",
+        Stats => "stats",
+        MergeCounts => "merge-counts",
     }
 }
-fn subcommands() -> [&'static str; 7] {
+fn subcommands() -> [&'static str; 8] {
     OP_NAMES.map(subcommand_for)
 }
 fn flagged_subcommands_for(op: OpName) -> Vec<String> {
@@ -74,6 +91,10 @@ fn flagged_subcommands_for(op: OpName) -> Vec<String> {
         SingleByFile => flag("single --file"),
         Multiple => flag("multiple"),
         MultipleByFile => flag("multiple --files"),
+        Majority => flag("majority"),
+        Top => flag("top"),
+        Stats => flag("stats"),
+        MergeCounts => flag("merge-counts"),
     }
 }
 
@@ -272,18 +293,18 @@ impl fmt::Debug for TestInput {
 //
 static INPUT: Lazy<Vec<TestInput>> = Lazy::new(|| {
     use OpName::{
-        Diff as D, Intersect as I, Multiple as M, MultipleByFile as MBF, Single as S,
-        SingleByFile as SBF, Union as U,
+        Diff as D, Intersect as I, Majority as MAJ, Multiple as M, MultipleByFile as MBF,
+        Single as S, SingleByFile as SBF, Union as U,
     };
     vec![
-        TestInput { x: 1, y: 1, z: 1, tag: "In xyz", expect: vec![U, I, MBF, M] },
+        TestInput { x: 1, y: 1, z: 1, tag: "In xyz", expect: vec![U, I, MBF, M, MAJ] },
         TestInput { x: 3, y: 0, z: 0, tag: "In x 3 times", expect: vec![U, D, SBF, M] },
         TestInput { x: 1, y: 0, z: 0, tag: "In x once", expect: vec![U, D, S, SBF] },
-        TestInput { x: 1, y: 1, z: 0, tag: "In xy", expect: vec![U, MBF, M] },
-        TestInput { x: 1, y: 2, z: 0, tag: "In x. In y twice", expect: vec![U, MBF, M] },
-        TestInput { x: 1, y: 0, z: 1, tag: "In xz", expect: vec![U, MBF, M] },
-        TestInput { x: 1, y: 1, z: 1, tag: "In xyz also", expect: vec![U, I, MBF, M] },
-        TestInput { x: 0, y: 1, z: 1, tag: "In yz", expect: vec![U, MBF, M] },
+        TestInput { x: 1, y: 1, z: 0, tag: "In xy", expect: vec![U, MBF, M, MAJ] },
+        TestInput { x: 1, y: 2, z: 0, tag: "In x. In y twice", expect: vec![U, MBF, M, MAJ] },
+        TestInput { x: 1, y: 0, z: 1, tag: "In xz", expect: vec![U, MBF, M, MAJ] },
+        TestInput { x: 1, y: 1, z: 1, tag: "In xyz also", expect: vec![U, I, MBF, M, MAJ] },
+        TestInput { x: 0, y: 1, z: 1, tag: "In yz", expect: vec![U, MBF, M, MAJ] },
         TestInput { x: 0, y: 1, z: 0, tag: "In y once", expect: vec![U, S, SBF] },
         TestInput { x: 0, y: 0, z: 1, tag: "In z once", expect: vec![U, S, SBF] },
     ]
@@ -466,10 +487,11 @@ fn the_optimize_to_union_code_in_main_only_does_so_when_its_ok() {
         let output = run([subcommand_for(op), x.path().to_str().unwrap()]).unwrap();
         let result = String::from_utf8(output.stdout).unwrap();
         let expected = match op {
-            Intersect | Union | Diff | SingleByFile => "a3\nb2\nc1\nd1\n",
+            Intersect | Union | Diff | SingleByFile | Majority => "a3\nb2\nc1\nd1\n",
             Single => "c1\nd1\n",
             Multiple => "a3\nb2\n",
             MultipleByFile => "",
+            Top | Stats | MergeCounts => unreachable!("{op:?} isn't in OP_NAMES"),
         };
         assert_eq!(result, expected, "Expected {op:?} result to be '{expected}'");
     }
@@ -503,3 +525,1568 @@ fn zet_terminates_every_output_line_with_the_line_terminator_of_the_first_input_
         }
     }
 }
+
+#[cfg(unix)]
+#[test]
+fn partial_on_interrupt_prints_the_result_so_far_and_says_so() {
+    use std::io::Write;
+    use std::process::Stdio;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    let temp = TempDir::new().unwrap();
+    let x_path = &path_with(&temp, "x.txt", "a\nb\n", Encoding::Plain);
+    let z_path = &path_with(&temp, "z.txt", "z\n", Encoding::Plain);
+    let mut zet = main_binary()
+        .args(["union", "--partial-on-interrupt", x_path, "-", z_path])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = zet.stdin.take().unwrap();
+    stdin.write_all(b"c\n").unwrap();
+    sleep(Duration::from_millis(500));
+    Command::new("kill").args(["-INT", &zet.id().to_string()]).status().unwrap();
+    sleep(Duration::from_millis(200));
+    // Closing standard input lets `zet` finish with its second operand; having
+    // been interrupted, it shouldn't go on to read the third.
+    drop(stdin);
+    let output = zet.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\nc\n");
+    assert!(String::from_utf8(output.stderr).unwrap().contains("PARTIAL"));
+}
+
+#[test]
+fn key_compares_lines_by_fields_split_on_a_multi_byte_delimiter() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "1||x|y||p\n2||z||q\n3||w||r\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "9||x|y||s\n8||w||t\n", Encoding::Plain);
+    for delimiter in ["||", "|\\x7c"] {
+        let output = main_binary()
+            .args(["intersect", "--key", "2", "--field-delimiter", delimiter, a_path, b_path])
+            .unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "1||x|y||p\n3||w||r\n");
+    }
+    let tabbed = &path_with(&temp, "tabbed.txt", "a\tb c\tz\nd\tb c\tz\n", Encoding::Plain);
+    for fields in ["2,3", "2-3"] {
+        let args = ["union", "--key", fields, "--field-delimiter", "\\t", tabbed];
+        let output = main_binary().args(args).unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\tb c\tz\n");
+    }
+}
+
+#[test]
+fn record_bytes_treats_input_as_fixed_width_binary_records() {
+    let temp = TempDir::new().unwrap();
+    let a = temp.child("a.bin");
+    // The first record would look like a UTF-16 Byte Order Mark to a text file
+    a.write_binary(b"\xff\xfe\n\0abcd\r\n\0\0\xff\xfe\n\0wxyz").unwrap();
+    let b = temp.child("b.bin");
+    b.write_binary(b"\xfe\xff\0\0abcdwxy").unwrap();
+    let (a, b) = (a.path().to_str().unwrap(), b.path().to_str().unwrap());
+
+    let output = main_binary().args(["union", "--record-bytes", "4", a, b]).unwrap();
+    assert_eq!(output.stdout, b"\xff\xfe\n\0abcd\r\n\0\0wxyz\xfe\xff\0\0wxy");
+    let output = main_binary().args(["intersect", "--record-bytes", "4", a, b]).unwrap();
+    assert_eq!(output.stdout, b"abcd");
+    let output = main_binary().args(["diff", "--record-bytes", "4", a, b]).unwrap();
+    assert_eq!(output.stdout, b"\xff\xfe\n\0\r\n\0\0wxyz");
+}
+
+#[test]
+fn no_decode_processes_input_bytes_exactly_as_they_are() {
+    let temp = TempDir::new().unwrap();
+    let a = temp.child("a.bin");
+    // Looks like UTF-16LE, but isn't
+    a.write_binary(b"\xff\xfeab\ncd\r\ncd\n").unwrap();
+    let b = temp.child("b.bin");
+    b.write_binary(b"\xff\xfeab\ncd\r\n").unwrap();
+    let (a, b) = (a.path().to_str().unwrap(), b.path().to_str().unwrap());
+
+    let output = main_binary().args(["union", "--no-decode", a]).unwrap();
+    assert_eq!(output.stdout, b"\xff\xfeab\ncd\r\ncd\n");
+    let output = main_binary().args(["intersect", "--no-decode", a, b]).unwrap();
+    assert_eq!(output.stdout, b"\xff\xfeab\ncd\r\n");
+    let output = main_binary().args(["diff", "--no-decode", a, b]).unwrap();
+    assert_eq!(output.stdout, b"cd\n");
+    main_binary().args(["union", "--no-decode", "--record-bytes", "2", a]).assert().failure();
+}
+
+#[test]
+fn strip_inner_boms_strips_boms_from_concatenated_files() {
+    let temp = TempDir::new().unwrap();
+    let cat = temp.child("cat.txt");
+    // Two files with BOMs, `cat`-ed together
+    cat.write_str("\u{FEFF}a\nb\n\u{FEFF}b\nc\n").unwrap();
+    let b = temp.child("b.txt");
+    b.write_str("\u{FEFF}a\n\u{FEFF}c\n").unwrap();
+    let b = b.to_str().unwrap();
+
+    let output = main_binary().args(["union"]).stdin(File::open(cat.path()).unwrap()).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "\u{FEFF}a\nb\n\u{FEFF}b\nc\n");
+    assert!(output.stderr.is_empty());
+
+    let output = main_binary()
+        .args(["intersect", "--strip-inner-boms", "-", b])
+        .stdin(File::open(cat.path()).unwrap())
+        .unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "\u{FEFF}a\nc\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        stderr,
+        format!(
+            "zet: <stdin>: stripped 1 Byte Order Mark from the middle of the input\n\
+             zet: {b}: stripped 1 Byte Order Mark from the middle of the input\n"
+        )
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn recursive_reads_files_beneath_directories_and_follows_links_only_if_asked() {
+    use std::os::unix::fs::symlink;
+    let temp = TempDir::new().unwrap();
+    temp.child("dir/1.txt").write_str("a\nb\n").unwrap();
+    temp.child("dir/sub/2.txt").write_str("b\nc\n").unwrap();
+    temp.child("outside.txt").write_str("d\n").unwrap();
+    symlink(temp.child("outside.txt").path(), temp.child("dir/file-link").path()).unwrap();
+    symlink(temp.child("dir").path(), temp.child("dir/sub/loop").path()).unwrap();
+    let dir = temp.child("dir");
+    let dir = dir.to_str().unwrap();
+
+    let output = main_binary().args(["union", "-r", dir]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\nc\n");
+    let output = main_binary().args(["union", "--recursive", "--no-follow", dir]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\nc\n");
+
+    let output = main_binary().args(["union", "--recursive", "--follow", dir]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\nd\nc\n");
+    assert!(String::from_utf8(output.stderr).unwrap().contains("loop"));
+
+    let empty = temp.child("empty");
+    empty.create_dir_all().unwrap();
+    let output = main_binary().args(["union", "-r", empty.to_str().unwrap()]).unwrap();
+    assert!(output.stdout.is_empty());
+
+    main_binary().args(["union", "--follow", dir]).assert().failure();
+}
+
+#[test]
+fn recursive_skips_hidden_files_unless_asked() {
+    let temp = TempDir::new().unwrap();
+    temp.child("dir/a.txt").write_str("a\n").unwrap();
+    temp.child("dir/.b.txt").write_str("b\n").unwrap();
+    temp.child("dir/.git/HEAD").write_str("ref: refs/heads/main\n").unwrap();
+    let dir = temp.child("dir");
+    let dir = dir.to_str().unwrap();
+
+    let output = main_binary().args(["union", "-r", dir]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\n");
+    let output = main_binary().args(["union", "-r", "--hidden", dir]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "b\nref: refs/heads/main\na\n");
+}
+
+#[test]
+fn recursive_respects_ignore_files_unless_asked_not_to() {
+    let temp = TempDir::new().unwrap();
+    temp.child("repo/.git").create_dir_all().unwrap();
+    temp.child("repo/.gitignore").write_str("build/\n").unwrap();
+    temp.child("repo/build/out.txt").write_str("built\n").unwrap();
+    temp.child("repo/vendor/.ignore").write_str("*.blob\n").unwrap();
+    temp.child("repo/vendor/big.blob").write_str("blob\n").unwrap();
+    temp.child("repo/src.txt").write_str("source\n").unwrap();
+    let repo = temp.child("repo");
+    let repo = repo.to_str().unwrap();
+
+    let output = main_binary().args(["union", "-r", repo]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "source\n");
+    let output = main_binary().args(["union", "-r", "--no-ignore", repo]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "built\nsource\nblob\n");
+}
+
+#[test]
+fn recursive_includes_only_files_matching_globs() {
+    let temp = TempDir::new().unwrap();
+    temp.child("dir/a.csv").write_str("a\n").unwrap();
+    temp.child("dir/b_tmp.csv").write_str("b\n").unwrap();
+    temp.child("dir/sub/c.csv").write_str("c\n").unwrap();
+    temp.child("dir/sub/d.txt").write_str("d\n").unwrap();
+    let dir = temp.child("dir");
+    let dir = dir.to_str().unwrap();
+
+    let output = main_binary().args(["union", "-r", "--glob", "*.csv", dir]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\nc\n");
+    let globs = ["--glob", "*.csv", "--glob", "!*_tmp*"];
+    let output = main_binary().args(["union", "-r"]).args(globs).arg(dir).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nc\n");
+    let output = main_binary().args(["union", "-r", "--glob", "!sub", dir]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\n");
+
+    main_binary().args(["union", "-r", "--glob", "a{", dir]).assert().failure();
+}
+
+#[test]
+fn recursive_descends_at_most_max_depth_levels() {
+    let temp = TempDir::new().unwrap();
+    temp.child("log/a.log").write_str("a\n").unwrap();
+    temp.child("log/old/b.log").write_str("b\n").unwrap();
+    temp.child("log/old/older/c.log").write_str("c\n").unwrap();
+    let log = temp.child("log");
+    let log = log.to_str().unwrap();
+
+    for (depth, expected) in [("0", ""), ("1", "a\n"), ("2", "a\nb\n"), ("3", "a\nb\nc\n")] {
+        let output = main_binary().args(["union", "-r", "--max-depth", depth, log]).unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    }
+}
+
+#[test]
+fn recursive_orders_files_as_a_depth_first_walk_sorted_by_name() {
+    let temp = TempDir::new().unwrap();
+    let mut expected = String::new();
+    for name in ["a/x.txt", "a.txt", "b/c/d/e.txt", "b/c/f.txt", "b/g.txt", "b.txt", "c.txt"] {
+        temp.child("dir").child(name).write_str(&format!("{name}\n")).unwrap();
+        expected.push_str(&format!("{name}\n"));
+    }
+    for n in 0..100 {
+        let name = format!("many/{n:03}.txt");
+        temp.child("dir").child(&name).write_str(&format!("{name}\n")).unwrap();
+        expected.push_str(&format!("{name}\n"));
+    }
+    let dir = temp.child("dir");
+    let output = main_binary().args(["union", "-r", dir.to_str().unwrap()]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
+
+#[test]
+fn labels_name_operands_in_messages() {
+    let temp = TempDir::new().unwrap();
+    let a = temp.child("a.txt");
+    a.write_binary(b"ok\nbad\xff\n").unwrap();
+    let b = temp.child("b.txt");
+    b.write_binary(b"bad\xff\n").unwrap();
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+    let missing = temp.child("missing.txt");
+    let missing = missing.to_str().unwrap();
+
+    let (a_label, b_label) = (format!("old={a}"), format!("new={b}"));
+    let labels = ["--label", &a_label, "--label", &b_label];
+    let output =
+        main_binary().args(["union", "--validate-utf8"]).args(labels).args([a, b]).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr, "zet: old:2: invalid UTF-8\nzet: new:1: invalid UTF-8\n");
+
+    let output = main_binary()
+        .args(["union", "--label", &format!("gone={missing}"), a, missing])
+        .output()
+        .unwrap();
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Can't open file: gone"));
+
+    main_binary().args(["union", "--label", &format!("b={b}"), a]).assert().failure();
+    main_binary().args(["union", "--label", "no-equals-sign", a]).assert().failure();
+}
+
+#[cfg(unix)]
+#[test]
+fn key_cmd_compares_lines_by_the_keys_an_external_command_prints() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "Apple\nbanana\nCherry\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "APPLE\ncherry\ndate\n", Encoding::Plain);
+    let lowercase = "tr A-Z a-z";
+    let output = main_binary().args(["intersect", "--key-cmd", lowercase, a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "Apple\nCherry\n");
+    let output = main_binary().args(["union", "--key-cmd", lowercase, a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "Apple\nbanana\nCherry\ndate\n");
+
+    main_binary().args(["union", "--key-cmd", "head -n 1", a_path]).assert().failure();
+}
+
+#[test]
+fn normalize_compares_trimmed_collapsed_lowercased_lines_and_prints_the_first_seen() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "  Hello   World\nfoo\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "hello world  \nFOO BAR\n", Encoding::Plain);
+    let output = main_binary().args(["intersect", "--normalize", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "  Hello   World\n");
+    let output = main_binary().args(["union", "--normalize", b_path, a_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hello world  \nFOO BAR\nfoo\n");
+}
+
+#[test]
+fn validate_utf8_warns_about_or_skips_invalid_lines() {
+    let temp = TempDir::new().unwrap();
+    let a = temp.child("a.txt");
+    a.write_binary(b"ok\nbad\xff\nfine\n").unwrap();
+    let b = temp.child("b.txt");
+    b.write_binary(b"fine\n\xc0oops\nbad\xff\n").unwrap();
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    let output = main_binary().args(["union", "--validate-utf8", a, b]).unwrap();
+    assert_eq!(output.stdout, b"ok\nbad\xff\nfine\n\xc0oops\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        stderr,
+        format!(
+            "zet: {a}:2: invalid UTF-8\nzet: {b}:2: invalid UTF-8\nzet: {b}:3: invalid UTF-8\n"
+        )
+    );
+
+    let output = main_binary().args(["union", "--validate-utf8=skip", a, b]).unwrap();
+    assert_eq!(output.stdout, b"ok\nfine\n");
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn count_split_shows_counts_for_the_first_file_and_the_rest() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "x\ny\nx\nz\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "x\nw\n", Encoding::Plain);
+    let c_path = &path_with(&temp, "c.txt", "x\ny\n", Encoding::Plain);
+    let output = main_binary().args(["union", "--count-split", a_path, b_path, c_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "2 2 x\n1 1 y\n1 0 z\n0 1 w\n");
+    let output = main_binary().args(["diff", "--count-split", a_path, b_path, c_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1 0 z\n");
+}
+
+#[test]
+fn top_prints_lines_by_count_exactly_or_approximately() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "b\na\nc\na\nb\na\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "d\nc\nc\n", Encoding::Plain);
+    let output = main_binary().args(["top", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3 a\n3 c\n2 b\n1 d\n");
+    let output = main_binary().args(["top", "--top", "2", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3 a\n3 c\n");
+    let output = main_binary().args(["frequency", "--top", "2", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3 a\n3 c\n");
+    let output =
+        main_binary().args(["top", "--approx", "4", "--top", "1", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3 3 a\n");
+
+    main_binary().args(["union", "--top", "2", a_path]).assert().failure();
+}
+
+#[test]
+fn approximate_top_counts_show_how_much_they_may_overestimate() {
+    let temp = TempDir::new().unwrap();
+    let x_path = &path_with(&temp, "x.txt", "a\nb\nc\n", Encoding::Plain);
+    let y_path = &path_with(&temp, "y.txt", "d\ne\nf\n", Encoding::Plain);
+    let z_path = &path_with(&temp, "z.txt", "g\n", Encoding::Plain);
+    // Every line occurs once, but with just two counters, the counts pile up
+    let output = main_binary().args(["top", "--approx", "2", x_path, y_path, z_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "4 1 g\n3 1 e\n");
+}
+
+#[test]
+fn stats_describes_how_skewed_line_frequencies_are() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a\nb\na\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "a\nc\na\n", Encoding::Plain);
+    let output = main_binary().args(["stats", a_path, b_path]).unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "lines: 6\ndistinct: 3\nentropy: 1.2516\ngini: 0.3333\np50: 1\np90: 4\np99: 4\nmax: 4\n"
+    );
+    let output = main_binary().args(["stats", "--format", "csv", "--header-row", a_path]).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("metric,value\nlines,3\ndistinct,2\n"), "{stdout}");
+}
+
+#[test]
+fn condition_commands_report_their_verdict_in_the_exit_code() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a\nb\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "b\na\nc\n", Encoding::Plain);
+    let c_path = &path_with(&temp, "c.txt", "c\nd\n", Encoding::Plain);
+    for (args, status) in [
+        (["equal", a_path, a_path], 0),
+        (["equal", a_path, b_path], 1),
+        (["is-subset", a_path, b_path], 0),
+        (["is-subset", b_path, a_path], 1),
+        (["is-disjoint", a_path, c_path], 0),
+        (["is-disjoint", b_path, c_path], 1),
+    ] {
+        let output = main_binary().args(args).output().unwrap();
+        assert_eq!(output.status.code(), Some(status), "{args:?}");
+        assert!(output.stdout.is_empty());
+    }
+
+    let output = main_binary().args(["is-subset", "-v", b_path, a_path]).output().unwrap();
+    assert_eq!((output.status.code(), &output.stdout[..]), (Some(1), &b"c\n"[..]));
+    let output = main_binary().args(["is-subset", "-v", a_path, c_path, b_path]).output().unwrap();
+    assert_eq!((output.status.code(), &output.stdout[..]), (Some(0), &b""[..]));
+    let output = main_binary().args(["equal", "-v", a_path, b_path, a_path]).output().unwrap();
+    assert_eq!((output.status.code(), &output.stdout[..]), (Some(1), &b"c\n"[..]));
+    let output =
+        main_binary().args(["is-disjoint", "-v", a_path, c_path, b_path]).output().unwrap();
+    assert_eq!((output.status.code(), &output.stdout[..]), (Some(1), &b"b\n"[..]));
+}
+
+#[test]
+fn fail_fast_stops_reading_once_the_verdict_is_certain() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a\nb\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "b\nc\n", Encoding::Plain);
+    let missing = temp.child("missing.txt");
+    let missing = missing.path().to_str().unwrap();
+
+    let args = ["is-disjoint", "--fail-fast", a_path, b_path, missing];
+    let output = main_binary().args(args).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+
+    let output = main_binary().args(["is-disjoint", a_path, b_path, missing]).output().unwrap();
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Can't open file"));
+
+    main_binary().args(["union", "--fail-fast", a_path]).assert().failure();
+}
+
+#[test]
+fn check_tests_every_assertion_and_reports_on_each() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a\nb\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "b\na\nc\n", Encoding::Plain);
+    let c_path = &path_with(&temp, "c.txt", "c\nd\n", Encoding::Plain);
+    let mut args = vec!["check", "--assert-subset", a_path, b_path];
+    args.extend(["--assert-disjoint", a_path, c_path, "--assert-equal", a_path, a_path]);
+    let output = main_binary().args(&args).unwrap();
+    let expected = format!(
+        "pass   equal {a_path} {a_path}\npass   is-subset {a_path} {b_path}\n\
+         pass   is-disjoint {a_path} {c_path}\nAll 3 assertions passed\n"
+    );
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+
+    let label = format!("c={c_path}");
+    let args = ["check", "--label", &label, "--assert-disjoint", b_path, c_path];
+    let output = main_binary().args(args).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let expected = format!("FAIL   is-disjoint {b_path} c\n1 of 1 assertion did not pass\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+
+    main_binary().args(["check", a_path, b_path]).assert().failure();
+    main_binary().args(["union", "--assert-equal", a_path, b_path]).assert().failure();
+}
+
+#[test]
+fn summary_reports_lines_written_and_read_on_stderr() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", &"a\nb\n".repeat(600), Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "b\nc\n", Encoding::Plain);
+    let output = main_binary().args(["union", "--summary", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\nc\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr, "zet: 3 lines out of 1,202 read (2 files)\n");
+
+    let output = main_binary().args(["multiple", "--files", "--summary", b_path]).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr, "zet: 0 lines out of 2 read (1 file)\n");
+
+    let output = main_binary().args(["union", b_path]).unwrap();
+    assert!(output.stderr.is_empty());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn stats_json_reports_the_run_on_stderr_or_to_a_file() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a\nb\na\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "b\nc\n", Encoding::Plain);
+    let output = main_binary().args(["union", "--stats", "json", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\nc\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.ends_with("}\n"), "{stderr}");
+    let mut stats: serde_json::Value = serde_json::from_str(&stderr).unwrap();
+    assert!(stats["elapsed_seconds"].as_f64().is_some(), "{stats}");
+    stats["elapsed_seconds"] = serde_json::Value::Null;
+    let expected = serde_json::json!({
+        "files": 2,
+        "lines_read": 5,
+        "operands": [{ "name": a_path, "lines": 3 }, { "name": b_path, "lines": 2 }],
+        "lines_written": 3,
+        "distinct_lines": 3,
+        "elapsed_seconds": null,
+    });
+    assert_eq!(stats, expected);
+
+    let stats_path = temp.path().join("stats.json");
+    let stats_file = stats_path.to_str().unwrap();
+    let args = ["union", "--stats", "json", "--stats-file", stats_file, a_path, b_path];
+    let output = main_binary().args(args).unwrap();
+    assert!(output.stderr.is_empty());
+    let mut stats: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&stats_path).unwrap()).unwrap();
+    stats["elapsed_seconds"] = serde_json::Value::Null;
+    assert_eq!(stats, expected);
+
+    main_binary().args(["union", "--stats-file", stats_file, a_path]).assert().failure();
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn stats_json_escapes_operand_names() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "b\n", Encoding::Plain);
+    let name = "say \"hi\"\\\t\u{1}.txt";
+    let label = format!("{name}={b_path}");
+    let args = ["union", "--stats", "json", "--label", &label, a_path, b_path];
+    let output = main_binary().args(args).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(r#""name":"say \"hi\"\\\t\u0001.txt""#), "{stderr}");
+    let stats: serde_json::Value = serde_json::from_str(&stderr).unwrap();
+    assert_eq!(stats["operands"][1]["name"], name);
+}
+
+#[cfg(not(feature = "json"))]
+#[test]
+fn stats_json_needs_the_json_feature() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a\n", Encoding::Plain);
+    main_binary().args(["union", "--stats", "json", a_path]).assert().failure().code(2);
+}
+
+#[test]
+fn where_lists_every_place_each_output_line_occurs() {
+    let temp = TempDir::new().unwrap();
+    let x_path = &path_with(&temp, "x.txt", "a\nb\na\nc\n", Encoding::Plain);
+    let y_path = &path_with(&temp, "y.txt", "c\na\nd\n", Encoding::Plain);
+    let output = main_binary().args(["intersect", "--where", x_path, y_path]).unwrap();
+    let expected =
+        format!("{x_path}:1:a\n{x_path}:3:a\n{y_path}:2:a\n{x_path}:4:c\n{y_path}:1:c\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+
+    let args = ["single", "--where", "--where-limit", "1", "--label", "y=", x_path, y_path];
+    let label = format!("y={y_path}");
+    let args = args.map(|arg| if arg == "y=" { label.as_str() } else { arg });
+    let output = main_binary().args(args).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{x_path}:2:b\ny:3:d\n"));
+
+    main_binary().args(["union", "--where", "--count", x_path]).assert().failure();
+    main_binary().args(["top", "--where", x_path]).assert().failure();
+}
+
+#[test]
+fn merge_counts_combines_counted_output() {
+    let temp = TempDir::new().unwrap();
+    let host1 = &path_with(&temp, "host1.txt", &"GET /\n".repeat(12), Encoding::Plain);
+    let host2 = &path_with(&temp, "host2.txt", "POST /login\nGET /\n", Encoding::Plain);
+    let counted = |path: &str| {
+        let output = main_binary().args(["union", "--count", path]).unwrap();
+        let name = format!("{path}.counted");
+        std::fs::write(&name, output.stdout).unwrap();
+        name
+    };
+    let (counted1, counted2) = (counted(host1), counted(host2));
+    let output = main_binary().args(["merge-counts", &counted1, &counted2]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "13 GET /\n 1 POST /login\n");
+
+    let zero = &path_with(&temp, "zero.txt", "0 GET /\n0 HEAD /\n", Encoding::Plain);
+    let output = main_binary().args(["merge-counts", &counted1, zero]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "12 GET /\n");
+
+    main_binary().args(["merge-counts", host1]).assert().failure();
+}
+
+#[test]
+fn counted_input_weights_each_line_by_its_count() {
+    let temp = TempDir::new().unwrap();
+    let x_path = &path_with(&temp, "x.txt", "  12 GET /\n   1 POST /login\n", Encoding::Plain);
+    let y_path = &path_with(&temp, "y.txt", "3 GET /\n1 HEAD /\n", Encoding::Plain);
+    let run = |args: &[&str]| {
+        let output = main_binary().arg("--counted-input").args(args).unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(run(&["union", "--count", x_path, y_path]), "15 GET /\n 1 POST /login\n 1 HEAD /\n");
+    assert_eq!(run(&["single", x_path, y_path]), "POST /login\nHEAD /\n");
+    assert_eq!(run(&["diff", "--count-split", x_path, y_path]), "1 0 POST /login\n");
+    assert_eq!(run(&["top", "--approx", "2", "--top", "1", x_path, y_path]), "15 15 GET /\n");
+
+    let zero_path = &path_with(&temp, "zero.txt", "0 HEAD /\n2 PUT /\n", Encoding::Plain);
+    assert_eq!(
+        run(&["union", "--count", zero_path, x_path]),
+        " 2 PUT /\n12 GET /\n 1 POST /login\n"
+    );
+    assert_eq!(run(&["diff", y_path, zero_path]), "GET /\nHEAD /\n");
+    assert_eq!(run(&["top", "--top", "5", zero_path]), "2 PUT /\n");
+
+    let uncounted = &path_with(&temp, "uncounted.txt", "GET /\n", Encoding::Plain);
+    main_binary().args(["union", "--counted-input", "--count", uncounted]).assert().failure();
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn tui_needs_a_terminal() {
+    let temp = TempDir::new().unwrap();
+    let x_path = &path_with(&temp, "x.txt", "a\nb\n", Encoding::Plain);
+    let output = main_binary().args(["tui", x_path, x_path]).assert().failure();
+    let stderr = String::from_utf8(output.get_output().stderr.clone()).unwrap();
+    assert!(stderr.contains("needs a terminal"), "{stderr}");
+    main_binary().args(["tui", "--count", x_path]).assert().failure();
+}
+
+#[test]
+fn serve_answers_queries_about_a_baseline() {
+    use std::io::Write;
+    let temp = TempDir::new().unwrap();
+    let base_path = &path_with(&temp, "base.txt", "a\nb\n", Encoding::Plain);
+    let batch_path = &path_with(&temp, "batch.txt", "c\nb\nd\nc\n", Encoding::Plain);
+    let mut server = main_binary()
+        .args(["serve", base_path])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let queries = format!("contains a\ncontains c\ndiff-against {batch_path}\nquit\ncontains b\n");
+    server.stdin.take().unwrap().write_all(queries.as_bytes()).unwrap();
+    let output = server.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "yes\nno\nlines 2\nc\nd\n");
+
+    // Without --socket, queries come from standard input, so the baseline must come from files
+    main_binary().args(["serve"]).assert().failure();
+    main_binary().args(["union", "--socket", "zet.sock", base_path]).assert().failure();
+}
+
+#[cfg(unix)]
+#[test]
+fn serve_answers_queries_on_a_unix_socket() {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+    let temp = TempDir::new().unwrap();
+    let base_path = &path_with(&temp, "base.txt", "a\nb\n", Encoding::Plain);
+    let socket = temp.path().join("zet.sock");
+    let mut server = main_binary()
+        .args(["serve", "--socket", socket.to_str().unwrap(), base_path])
+        .spawn()
+        .unwrap();
+    let mut stream = None;
+    for _ in 0..100 {
+        match UnixStream::connect(&socket) {
+            Ok(connected) => {
+                stream = Some(connected);
+                break;
+            }
+            Err(_) => std::thread::sleep(std::time::Duration::from_millis(50)),
+        }
+    }
+    let mut stream = stream.expect("the server should be listening");
+    stream.write_all(b"contains b\ncontains z\nquit\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    server.kill().unwrap();
+    server.wait().unwrap();
+    assert_eq!(response, "yes\nno\n");
+}
+
+#[test]
+fn output_fields_prints_just_the_key_fields() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.csv", "1,x,p\n2,y,q\n3,x,r\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.csv", "9,y,q\n", Encoding::Plain);
+    let args =
+        ["diff", "--key", "2,3", "--field-delimiter", ",", "--output-fields", a_path, b_path];
+    let output = main_binary().args(args).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "x,p\nx,r\n");
+    let output = main_binary()
+        .args(["union", "--key", "2", "--field-delimiter", ",", "--output-fields", a_path])
+        .unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "x\ny\n");
+    let output = main_binary().args(args).args(["--output-delimiter", "\\t"]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "x\tp\nx\tr\n");
+    main_binary().args(["union", "--output-fields", a_path]).assert().failure();
+}
+
+#[test]
+fn state_reads_only_what_was_appended_since_the_last_run() {
+    let temp = TempDir::new().unwrap();
+    let log_path = &path_with(&temp, "log.txt", "a\nb\nx\n", Encoding::Plain);
+    let allowed_path = &path_with(&temp, "allowed.txt", "a\nb\n", Encoding::Plain);
+    let state = temp.path().join("state");
+    let state = state.to_str().unwrap();
+    let diff = || main_binary().args(["diff", "--state", state, log_path, allowed_path]).unwrap();
+    assert_eq!(String::from_utf8(diff().stdout).unwrap(), "x\n");
+
+    // The partial last line waits for the next run
+    std::fs::write(log_path, "a\nb\nx\ny\nb\nz\npart").unwrap();
+    assert_eq!(String::from_utf8(diff().stdout).unwrap(), "x\ny\nz\n");
+    let saved = std::fs::read_to_string(state).unwrap();
+    assert!(saved.contains(&format!("\n12 5 {log_path}\na\nb\nx\ny\nz\n")), "{saved}");
+
+    // A file that shrinks is read again from the start
+    std::fs::write(log_path, "w\n").unwrap();
+    assert_eq!(String::from_utf8(diff().stdout).unwrap(), "w\n");
+
+    main_binary().args(["single", "--state", state, log_path]).assert().failure();
+}
+
+#[test]
+fn stream_prints_multiples_before_the_input_ends() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::Stdio;
+    let mut zet = main_binary()
+        .args(["multiple", "--stream"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = zet.stdin.take().unwrap();
+    stdin.write_all(b"a\nb\nb\n").unwrap();
+    stdin.flush().unwrap();
+    let mut stdout = BufReader::new(zet.stdout.take().unwrap());
+    let mut first = String::new();
+    stdout.read_line(&mut first).unwrap();
+    assert_eq!(first, "b\n");
+    stdin.write_all(b"c\na\nb\n").unwrap();
+    drop(stdin);
+    let mut rest = String::new();
+    std::io::Read::read_to_string(&mut stdout, &mut rest).unwrap();
+    assert_eq!(rest, "a\n");
+    assert!(zet.wait().unwrap().success());
+    main_binary().args(["single", "--stream"]).assert().failure();
+}
+
+#[test]
+fn count_distinct_counts_the_distinct_values_of_a_field_for_each_key() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.csv", "u1,/a\nu2,/a\nu1,/b\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.csv", "u1,/a\nu3,/b\nU2,/a\n", Encoding::Plain);
+    let args = ["union", "--key", "2", "--field-delimiter", ",", "--count-distinct", "1"];
+    let output = main_binary().args(args).args([a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "/a\t3\n/b\t2\n");
+    let output = main_binary().args(args).args(["--normalize", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "/a\t2\n/b\t2\n");
+    main_binary().args(["union", "--count-distinct", "1", a_path]).assert().failure();
+    main_binary().args(args).args(["--count", a_path]).assert().failure();
+}
+
+#[test]
+fn literal_operands_follow_the_input_files() {
+    let temp = TempDir::new().unwrap();
+    let big_path = &path_with(&temp, "big.txt", "foo\nbaz\nbar\nqux\n", Encoding::Plain);
+    let output = main_binary().args(["diff", big_path, "--literal", "foo,bar"]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "baz\nqux\n");
+    // With no input files, standard input comes first
+    let output = assert_cmd::Command::cargo_bin("zet")
+        .unwrap()
+        .args(["intersect", "--literal", "qux\nfoo", "--literal", "foo,qux,x"])
+        .write_stdin("qux\nfoo\nbar\n")
+        .unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "qux\nfoo\n");
+}
+
+#[test]
+fn sniff_encoding_decodes_legacy_text() {
+    let temp = TempDir::new().unwrap();
+    let legacy = temp.child("legacy.txt");
+    legacy.write_binary(b"caf\xe9\nna\xefve\n").unwrap();
+    let modern_path = &path_with(&temp, "modern.txt", "na\u{ef}ve\nplain\n", Encoding::Plain);
+    let legacy_path = legacy.path().to_str().unwrap();
+    for (first, second) in [(legacy_path, modern_path.as_str()), (modern_path, legacy_path)] {
+        let output = main_binary().args(["intersect", "--sniff-encoding", first, second]).unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "na\u{ef}ve\n");
+    }
+    let output =
+        main_binary().args(["union", "--sniff-encoding", "-v", legacy_path, modern_path]).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("legacy.txt: windows-1252 (sniffed)"), "{stderr}");
+    assert!(stderr.contains("modern.txt: UTF-8 (sniffed)"), "{stderr}");
+}
+
+#[test]
+fn min_and_max_files_bound_the_number_of_files_a_line_is_in() {
+    let temp = TempDir::new().unwrap();
+    let paths: Vec<String> = ["a\nb\nc\n", "b\nc\n", "c\nd\n", "c\nd\ne\n"]
+        .iter()
+        .enumerate()
+        .map(|(n, contents)| path_with(&temp, &format!("{n}.txt"), contents, Encoding::Plain))
+        .collect();
+    let output = main_binary().args(["union", "--min-files", "2"]).args(&paths).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "b\nc\nd\n");
+    let output = main_binary().args(["union", "--max-files", "2", "-c"]).args(&paths).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1 a\n2 b\n2 d\n1 e\n");
+    let output =
+        main_binary().args(["multiple", "--files", "--max-files", "2"]).args(&paths).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "b\nd\n");
+
+    main_binary().args(["multiple", "--min-files", "2"]).args(&paths).assert().failure();
+    main_binary().args(["top", "--max-files", "2"]).args(&paths).assert().failure();
+    let backwards = ["union", "--min-files", "3", "--max-files", "2"];
+    main_binary().args(backwards).args(&paths).assert().failure();
+}
+
+#[test]
+fn min_and_max_count_bound_the_number_of_times_a_line_occurs() {
+    let temp = TempDir::new().unwrap();
+    let paths: Vec<String> = ["a\nb\nb\nc\nc\nc\n", "c\nd\nd\nd\nd\n"]
+        .iter()
+        .enumerate()
+        .map(|(n, contents)| path_with(&temp, &format!("{n}.txt"), contents, Encoding::Plain))
+        .collect();
+    let output = main_binary().args(["multiple", "--min-count", "4"]).args(&paths).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "c\nd\n");
+    let bounded = ["union", "--min-count", "2", "--max-count", "3", "-c"];
+    let output = main_binary().args(bounded).args(&paths).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "2 b\n");
+
+    main_binary().args(["multiple", "--files", "--min-count", "2"]).args(&paths).assert().failure();
+    let both = ["union", "--min-count", "2", "--min-files", "2"];
+    main_binary().args(both).args(&paths).assert().failure();
+}
+
+#[test]
+fn majority_prints_the_lines_in_more_than_half_of_the_files() {
+    let temp = TempDir::new().unwrap();
+    let paths: Vec<String> = ["a\nb\nc\n", "b\nc\n", "c\nd\n", "c\nd\ne\n"]
+        .iter()
+        .enumerate()
+        .map(|(n, contents)| path_with(&temp, &format!("{n}.txt"), contents, Encoding::Plain))
+        .collect();
+    let output = main_binary().arg("majority").args(&paths).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "c\n");
+    let output = main_binary().args(["majority", "--count-files"]).args(&paths[1..]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3 c\n2 d\n");
+}
+
+#[test]
+fn expr_evaluates_an_expression_over_the_files_it_names() {
+    let temp = TempDir::new().unwrap();
+    let a = path_with(&temp, "a.txt", "a\nb\nc\nd\n", Encoding::Plain);
+    let b = path_with(&temp, "b-now.txt", "b\nc\ne\n", Encoding::Plain);
+    let c = path_with(&temp, "c.txt", "c\nd\ne\nf\n", Encoding::Plain);
+    let expression = format!("({a} & {b}) - {c}");
+    let output = main_binary().args(["expr", &expression]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "b\n");
+    let expression = format!("{a} ^ {b} | {c} - {a}");
+    let output = main_binary().args(["expr", &expression]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nd\ne\nf\n");
+
+    main_binary().args(["expr", &format!("({a} & {b}")]).assert().failure();
+    main_binary().args(["expr", &a, &b]).assert().failure();
+    main_binary().args(["expr", "--count-lines", &a]).assert().failure();
+}
+
+#[test]
+fn invert_prints_the_lines_a_command_leaves_out() {
+    let temp = TempDir::new().unwrap();
+    let paths: Vec<String> = ["a\nb\nb\nc\n", "c\nd\n", "c\nb\ne\n"]
+        .iter()
+        .enumerate()
+        .map(|(n, contents)| path_with(&temp, &format!("{n}.txt"), contents, Encoding::Plain))
+        .collect();
+    let stdout = |command: &[&str]| {
+        let output = main_binary().args(command).arg("--invert").args(&paths).unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(stdout(&["intersect"]), "a\nb\nd\ne\n");
+    assert_eq!(stdout(&["diff"]), "b\nc\nd\ne\n");
+    assert_eq!(stdout(&["single"]), "b\nc\n");
+    assert_eq!(stdout(&["multiple", "--files", "-c"]), "1 a\n1 d\n1 e\n");
+    let output = main_binary().args(["multiple", "--files", "--invert", &paths[0]]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\nc\n");
+
+    main_binary().args(["top", "--invert"]).args(&paths).assert().failure();
+}
+
+#[test]
+fn venn_prints_or_writes_each_region() {
+    let temp = TempDir::new().unwrap();
+    let x_path = &path_with(&temp, "x.txt", "a\nab\nabc\n", Encoding::Plain);
+    let y_path = &path_with(&temp, "y.txt", "abc\nab\nb\n", Encoding::Plain);
+    let z_path = &path_with(&temp, "z.txt", "abc\n", Encoding::Plain);
+    let output = main_binary().args(["venn", x_path, y_path, z_path]).unwrap();
+    let expected = format!(
+        "== only {x_path} ==\na\n== only {y_path} ==\nb\n== {x_path} ∩ {y_path} ==\nab\n\
+         == {x_path} ∩ {y_path} ∩ {z_path} ==\nabc\n"
+    );
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+
+    let prefix = temp.path().join("region-");
+    let prefix = prefix.to_str().unwrap();
+    let args = ["venn", "--out-prefix", prefix, x_path, y_path, z_path];
+    assert!(main_binary().args(args).unwrap().stdout.is_empty());
+    for (label, contents) in [("A", "a\n"), ("B", "b\n"), ("AB", "ab\n"), ("ABC", "abc\n")] {
+        let written = std::fs::read_to_string(format!("{prefix}{label}.txt")).unwrap();
+        assert_eq!(written, contents);
+    }
+    assert!(!temp.path().join("region-C.txt").exists());
+
+    main_binary().args(["venn", "--count", x_path]).assert().failure();
+    main_binary().args(["union", "--out-prefix", prefix, x_path]).assert().failure();
+}
+
+#[test]
+fn grouped_lists_the_single_lines_of_each_file_under_its_name() {
+    let temp = TempDir::new().unwrap();
+    let x_path = &path_with(&temp, "x.txt", "a\nb\nshared\na\n", Encoding::Plain);
+    let y_path = &path_with(&temp, "y.txt", "shared\n", Encoding::Plain);
+    let z_path = &path_with(&temp, "z.txt", "c\nshared\n", Encoding::Plain);
+    let output =
+        main_binary().args(["single", "--by-file", "--grouped", x_path, y_path, z_path]).unwrap();
+    let expected =
+        format!("== only {x_path} ==\na\nb\n== only {y_path} ==\n== only {z_path} ==\nc\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    let output = main_binary().args(["single", "--files", "--grouped", x_path]).unwrap();
+    let expected = format!("== only {x_path} ==\na\nb\nshared\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+
+    main_binary().args(["single", "--grouped", x_path, y_path]).assert().failure();
+    main_binary().args(["single", "--files", "--grouped", "-c", x_path]).assert().failure();
+}
+
+#[test]
+fn similarity_prints_a_matrix_of_jaccard_similarities() {
+    let temp = TempDir::new().unwrap();
+    let x_path = &path_with(&temp, "x.txt", "a\nb\nc\nd\n", Encoding::Plain);
+    let y_path = &path_with(&temp, "y.txt", "c\nd\ne\nf\n", Encoding::Plain);
+    let output = main_binary().args(["similarity", x_path, y_path]).unwrap();
+    let expected =
+        format!("   A       B\nA  1.0000  0.3333  {x_path}\nB  0.3333  1.0000  {y_path}\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    let args = ["similarity", "--format", "csv", "--header-row", x_path, y_path];
+    let output = main_binary().args(args).unwrap();
+    let expected =
+        format!("file,{x_path},{y_path}\n{x_path},1.0000,0.3333\n{y_path},0.3333,1.0000\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+
+    main_binary().args(["similarity", "--count", x_path, y_path]).assert().failure();
+}
+
+#[test]
+fn eval_adds_and_subtracts_signed_operands_from_left_to_right() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a\nb\nc\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "b\nd\n", Encoding::Plain);
+    let c_path = &path_with(&temp, "c.txt", "c\nd\n", Encoding::Plain);
+    let (a, b, c) = (&format!("+{a_path}"), &format!("+{b_path}"), &format!("-{c_path}"));
+    let output = main_binary().args(["eval", a, b, c]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\n");
+    let output = main_binary().args(["--shell-quote", "eval", a, c, b]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "'a'\n'b'\n'd'\n");
+
+    main_binary().args(["eval", c, a]).assert().failure();
+    main_binary().args(["eval", a, "--count"]).assert().failure();
+    main_binary().args(["--count", "eval", a, b]).assert().failure();
+    let output = main_binary().args(["-f", "0", "eval", a, c]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\n");
+}
+
+#[test]
+fn an_operand_named_eval_is_just_an_operand() {
+    let temp = TempDir::new().unwrap();
+    path_with(&temp, "eval", "a\nb\n", Encoding::Plain);
+    path_with(&temp, "other", "-b\nc\n", Encoding::Plain);
+    let args = ["union", "eval", "other", "--count"];
+    let output = main_binary().current_dir(temp.path()).args(args).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1 a\n1 b\n1 -b\n1 c\n");
+}
+
+#[test]
+fn operand_groups_between_separators_act_as_single_files() {
+    let temp = TempDir::new().unwrap();
+    let a = &path_with(&temp, "a.txt", "a\nb\nc\nd\n", Encoding::Plain);
+    let b = &path_with(&temp, "b.txt", "b\nx\n", Encoding::Plain);
+    let c = &path_with(&temp, "c.txt", "c\n", Encoding::Plain);
+    let d = &path_with(&temp, "d.txt", "x\ny\n", Encoding::Plain);
+    let output = main_binary().args(["diff", a, b, "--", c, d]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\nd\n");
+    let output = main_binary().args(["intersect", a, b, "--", b, d]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "b\nx\n");
+    let output = main_binary().args(["diff", "--", a, c]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\nd\n");
+
+    main_binary().args(["single", a, "--", b]).assert().failure();
+    main_binary().args(["diff", a, "--"]).assert().failure();
+    main_binary().args(["diff", "--count", a, "--", b]).assert().failure();
+}
+
+#[test]
+fn ignore_case_compares_lowercased_or_case_folded_lines_and_prints_the_first_seen() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "Apple\nÄRGER\nSTRASSE\nkiwi\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "APPLE\närger\nstraße\nKiwi \n", Encoding::Plain);
+    let output = main_binary().args(["intersect", "--ignore-case", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "Apple\n");
+    let output = main_binary().args(["union", "-i", b_path, a_path]).unwrap();
+    let union = "APPLE\närger\nstraße\nKiwi \nÄRGER\nSTRASSE\nkiwi\n";
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), union);
+    let args = ["intersect", "--ignore-case=unicode", a_path, b_path];
+    let output = main_binary().args(args).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "Apple\nÄRGER\nSTRASSE\n");
+}
+
+#[test]
+fn trim_compares_lines_without_surrounding_whitespace() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "  start\n\tstop \nAbort\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "start  \nstop\nabort\n", Encoding::Plain);
+    let output = main_binary().args(["intersect", "--trim", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "  start\n\tstop \n");
+    let output = main_binary().args(["diff", "--trim", "-i", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+}
+
+#[test]
+fn key_regex_compares_lines_by_a_capture_group() {
+    let temp = TempDir::new().unwrap();
+    let monday = "1.2.3.4 \"GET /a HTTP/1.1\"\n5.6.7.8 \"GET /b HTTP/1.1\"\n-- restart --\n";
+    let tuesday = "9.9.9.9 \"GET /b HTTP/1.0\"\n-- restart --\n";
+    let a_path = &path_with(&temp, "monday.log", monday, Encoding::Plain);
+    let b_path = &path_with(&temp, "tuesday.log", tuesday, Encoding::Plain);
+    let args = ["diff", "--key-regex", r#""GET (\S+)"#, a_path, b_path];
+    let output = main_binary().args(args).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1.2.3.4 \"GET /a HTTP/1.1\"\n");
+    let args = ["intersect", "--key-regex", r#""GET (\S+)"#, a_path, b_path];
+    let output = main_binary().args(args).unwrap();
+    let both = "5.6.7.8 \"GET /b HTTP/1.1\"\n-- restart --\n";
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), both);
+    let output = main_binary().args(args).arg("--skip-unmatched").unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "5.6.7.8 \"GET /b HTTP/1.1\"\n");
+
+    main_binary().args(["union", "--key-regex", "(", a_path]).assert().failure();
+    main_binary().args(["union", "--skip-unmatched", a_path]).assert().failure();
+}
+
+#[test]
+fn ignore_blank_leaves_out_empty_lines() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "x\n\n  \ny\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "\ny\n  \n", Encoding::Plain);
+    let output = main_binary().args(["intersect", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "\n  \ny\n");
+    let output = main_binary().args(["intersect", "--ignore-blank", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "  \ny\n");
+    let args = ["intersect", "--ignore-blank", "--trim", a_path, b_path];
+    let output = main_binary().args(args).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "y\n");
+    let output = main_binary().args(["union", "--ignore-blank", b_path, a_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "y\n  \nx\n");
+}
+
+#[test]
+fn skip_comments_leaves_out_comment_lines() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.conf", "# defaults\nport 80\n  # old\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.conf", "; local\n# defaults\nport 8080\n", Encoding::Plain);
+    let output = main_binary().args(["diff", "--skip-comments", b_path, a_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "; local\nport 8080\n");
+    let args = ["union", "--skip-comments=;", b_path, a_path];
+    let output = main_binary().args(args).unwrap();
+    let union = "# defaults\nport 8080\nport 80\n  # old\n";
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), union);
+    main_binary().args(["union", "--skip-comments=", a_path]).assert().failure();
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_key_compares_json_lines_by_the_value_at_a_pointer() {
+    let temp = TempDir::new().unwrap();
+    let a = "{\"user\": {\"id\": 1, \"org\": 7}, \"at\": 10}\n{\"user\": {\"id\": 2}}\n";
+    let b = "{\"at\":11,\"user\":{\"org\":7,\"id\":1}}\n";
+    let a_path = &path_with(&temp, "a.jsonl", a, Encoding::Plain);
+    let b_path = &path_with(&temp, "b.jsonl", b, Encoding::Plain);
+    let output = main_binary().args(["diff", "--json-key", "/user", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "{\"user\": {\"id\": 2}}\n");
+    main_binary().args(["union", "--json-key", "user", a_path]).assert().failure();
+}
+
+#[test]
+fn csv_records_are_keyed_by_column_number_or_header_name() {
+    let temp = TempDir::new().unwrap();
+    let a = "id,note\r\n1,\"two\nlines\"\r\n\"2\",x\r\n3,y\r\n";
+    let b = "id,note\n1,\"z, \"\"quoted\"\"\"\n2,w\n";
+    let a_path = &path_with(&temp, "a.csv", a, Encoding::Plain);
+    let b_path = &path_with(&temp, "b.csv", b, Encoding::Plain);
+    let output = main_binary().args(["diff", "--csv", "--key", "id", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3,y\r\n");
+    let output = main_binary().args(["diff", "--csv", "--key", "1", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3,y\r\n");
+    let output = main_binary().args(["diff", "--csv", a_path, b_path]).unwrap();
+    let diff = "1,\"two\nlines\"\r\n\"2\",x\r\n3,y\r\n";
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), diff);
+    let output = main_binary().args(["single", "--csv", a_path, a_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    // The header rows are skipped whatever the key, so they're neither
+    // compared nor printed
+    let union = "1,\"two\nlines\"\r\n\"2\",x\r\n3,y\r\n";
+    let output = main_binary().args(["union", "--csv", "--key", "1", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), union);
+    let output = main_binary().args(["intersect", "--csv", "--key", "1", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1,\"two\nlines\"\r\n\"2\",x\r\n");
+    let output = main_binary().args(["union", "--csv", a_path, a_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), union);
+    let output = main_binary().args(["intersect", "--csv", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    main_binary().args(["diff", "--csv", "--key", "name", a_path, b_path]).assert().failure();
+    main_binary().args(["diff", "--key", "id", a_path, b_path]).assert().failure();
+}
+
+#[test]
+fn csv_column_names_are_looked_up_in_each_operands_header() {
+    let temp = TempDir::new().unwrap();
+    let c1_path = &path_with(&temp, "c1.csv", "id,name\n1,y\n2,z\n", Encoding::Plain);
+    let c2_path = &path_with(&temp, "c2.csv", "name,id\nq,2\nr,5\n", Encoding::Plain);
+    let run = |args: &[&str]| {
+        let output = main_binary().arg("--csv").args(args).unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(run(&["intersect", "--key", "id", c1_path, c2_path]), "2,z\n");
+    assert_eq!(run(&["union", "--key", "id", c1_path, c2_path]), "1,y\n2,z\nr,5\n");
+    assert_eq!(run(&["diff", "--key", "id", c2_path, c1_path]), "r,5\n");
+    main_binary().args(["multiple", "--csv", "--stream", c1_path]).assert().failure();
+}
+
+#[test]
+fn ignore_pattern_removes_matches_before_comparing() {
+    let temp = TempDir::new().unwrap();
+    let a = "2024-01-02 12:00:01 req=a1 start\n2024-01-02 12:00:02 req=b2 stop\n";
+    let b = "2024-03-04 09:30:00 req=c3 start\n2024-03-04 09:30:01 req=d4 fail\n";
+    let a_path = &path_with(&temp, "a.log", a, Encoding::Plain);
+    let b_path = &path_with(&temp, "b.log", b, Encoding::Plain);
+    let args =
+        ["diff", "--ignore-pattern", r"^\S+ \S+ ", "--ignore-pattern", "req=\\w+ ", a_path, b_path];
+    let output = main_binary().args(args).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "2024-01-02 12:00:02 req=b2 stop\n");
+    main_binary().args(["union", "--ignore-pattern", "(", a_path]).assert().failure();
+}
+
+#[test]
+fn uniq_style_flags_choose_the_part_of_each_line_compared() {
+    let temp = TempDir::new().unwrap();
+    let a = "1 x-apple\n2 y-banana\n3 z-cherry\n";
+    let b = "9 q-applesauce\n8 r-bandana\n";
+    let a_path = &path_with(&temp, "a.txt", a, Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", b, Encoding::Plain);
+    let args =
+        ["diff", "--skip-fields", "1", "--skip-chars", "3", "--check-chars", "5", a_path, b_path];
+    let output = main_binary().args(args).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "2 y-banana\n3 z-cherry\n");
+    let output = main_binary().args(["union", "-c", "-f1", "-s3", "-w3", a_path, b_path]).unwrap();
+    let counts = "2 1 x-apple\n2 2 y-banana\n1 3 z-cherry\n";
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), counts);
+    main_binary().args(["union", "-f1", "--key", "1", a_path]).assert().failure();
+}
+
+#[test]
+fn numeric_compares_numbers_whatever_their_format() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "1.0\n0042\n7\nx01\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "01\n42.000\nx1\n", Encoding::Plain);
+    let output = main_binary().args(["diff", "--numeric", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "7\nx01\n");
+}
+
+#[test]
+fn hashed_operations_reread_the_first_file() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a\r\nb\r\nc\r\nb\r\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "c\nd\nA\n", Encoding::Plain);
+    let output = main_binary().args(["union", "--hashed", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\nc\nd\nA\n");
+    let output = main_binary().args(["diff", "--hashed", "-i", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "b\n");
+    let output = main_binary().args(["intersect", "--hashed", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "c\n");
+    main_binary().args(["diff", "--hashed", "-", b_path]).assert().failure();
+    main_binary().args(["single", "--hashed", a_path, b_path]).assert().failure();
+    main_binary().args(["union", "--hashed", "--count", a_path]).assert().failure();
+}
+
+#[test]
+fn keep_last_prints_the_last_line_with_each_key() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "Apple\nbanana\nAPPLE\ncherry\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "apple\nBanana\n", Encoding::Plain);
+    let output = main_binary().args(["union", "-i", "--keep", "last", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "apple\nBanana\ncherry\n");
+    let output = main_binary().args(["union", "-i", "--keep", "first", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "Apple\nbanana\ncherry\n");
+    let output = main_binary().args(["diff", "-i", "--keep=last", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "cherry\n");
+    let output = main_binary().args(["intersect", "-i", "--keep=last", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "apple\nBanana\n");
+}
+
+#[test]
+fn output_goes_to_the_file_named() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a\r\nb\r\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "b\nc\n", Encoding::Plain);
+    let out_path = temp.path().join("out.txt");
+    let out = out_path.to_str().unwrap();
+    let output = main_binary().args(["union", "-o", out, a_path, b_path]).unwrap();
+    assert!(output.stdout.is_empty());
+    assert_eq!(std::fs::read_to_string(&out_path).unwrap(), "a\r\nb\r\nc\r\n");
+    main_binary().args(["diff", "--output", out, b_path, a_path]).assert().success();
+    assert_eq!(std::fs::read_to_string(&out_path).unwrap(), "c\n");
+    main_binary().args(["union", "-o", out, a_path, "missing.txt"]).assert().failure();
+    assert_eq!(std::fs::read_to_string(&out_path).unwrap(), "c\n");
+    main_binary().args(["union", "-o", a_path, a_path, b_path]).assert().success();
+    assert_eq!(std::fs::read_to_string(a_path).unwrap(), "a\r\nb\r\nc\r\n");
+    let files = std::fs::read_dir(temp.path()).unwrap().count();
+    assert_eq!(files, 3, "no temporary files are left behind");
+}
+
+#[test]
+fn in_place_rewrites_the_first_file() {
+    let temp = TempDir::new().unwrap();
+    let list_path = &path_with(&temp, "list.txt", "b\na\nb\n", Encoding::Plain);
+    let new_path = &path_with(&temp, "new.txt", "c\na\n", Encoding::Plain);
+    let output = main_binary().args(["union", "--in-place", list_path, new_path]).unwrap();
+    assert!(output.stdout.is_empty());
+    assert_eq!(std::fs::read_to_string(list_path).unwrap(), "b\na\nc\n");
+    main_binary().args(["diff", "-I", list_path, new_path]).assert().success();
+    assert_eq!(std::fs::read_to_string(list_path).unwrap(), "b\n");
+    main_binary().args(["equal", "--in-place", list_path, new_path]).assert().failure();
+    main_binary().args(["union", "--in-place", "-"]).assert().failure();
+    assert_eq!(std::fs::read_to_string(list_path).unwrap(), "b\n");
+}
+
+#[test]
+fn sort_prints_lines_in_bytewise_order() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "pear\nBanana\napple\npear\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "cherry\napple\n", Encoding::Plain);
+    let output = main_binary().args(["union", "--sort", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "Banana\napple\ncherry\npear\n");
+    let output = main_binary().args(["union", "--sort", "--count-lines", a_path, b_path]).unwrap();
+    let expected = "1 Banana\n2 apple\n1 cherry\n2 pear\n";
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    let output = main_binary().args(["diff", "--sort", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "Banana\npear\n");
+    main_binary().args(["top", "--sort", a_path]).assert().failure();
+}
+
+#[test]
+fn sort_by_count_puts_the_most_frequent_lines_first() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "x\ny\nz\ny\nz\nz\n", Encoding::Plain);
+    let output = main_binary().args(["multiple", "--count", "--sort-by-count", a_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3 z\n2 y\n");
+    let args = ["union", "--count", "--sort-by-count", "--asc", a_path];
+    let output = main_binary().args(args).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1 x\n2 y\n3 z\n");
+    main_binary().args(["union", "--sort-by-count", a_path]).assert().failure();
+}
+
+#[test]
+fn order_last_seen_prints_lines_as_last_seen() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a\nb\nc\na\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "b\nd\n", Encoding::Plain);
+    let output = main_binary().args(["union", "--order", "last-seen", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "c\na\nb\nd\n");
+    let output = main_binary().args(["diff", "--order=last-seen", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "c\na\n");
+    let output = main_binary().args(["union", "--order", "first-seen", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\nc\nd\n");
+}
+
+#[test]
+fn column_delimiter_separates_counts_from_lines() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "x y\nz\nx y\n", Encoding::Plain);
+    let output =
+        main_binary().args(["union", "--count", "--column-delimiter", "|", a_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "2|x y\n1|z\n");
+    let args = ["union", "--count", "--column-delimiter=\\x1f", "--header-row", a_path];
+    let output = main_binary().args(args).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "count\x1fline\n2\x1fx y\n1\x1fz\n");
+    main_binary().args(["union", "--column-delimiter", "::", a_path]).assert().failure();
+}
+
+#[test]
+fn zero_terminated_records_end_with_nul() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a\nb\0c\0a\nb\0", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "c\0d", Encoding::Plain);
+    let output = main_binary().args(["union", "-z", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\0c\0d\0");
+    let output = main_binary().args(["diff", "-0", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\0");
+    let output = main_binary().args(["multiple", "--zero-terminated", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nb\0c\0");
+}
+
+#[test]
+fn record_delimiter_splits_records_at_any_string() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a=1;b=2\n;a=1", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "c=3;b=2\n;", Encoding::Plain);
+    let output = main_binary().args(["union", "--record-delimiter", ";", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a=1;b=2\n;c=3;");
+    let output = main_binary().args(["union", "--delimiter", "\\n;", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a=1;b=2\n;a=1\n;c=3;b=2\n;");
+    main_binary().args(["union", "--record-delimiter", "", a_path]).assert().failure();
+}
+
+#[test]
+fn matrix_format_marks_the_files_each_line_is_in() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "apple\nbanana\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "banana\ncherry\n", Encoding::Plain);
+    let output = main_binary().args(["union", "--format", "matrix", a_path, b_path]).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows: Vec<Vec<_>> = stdout.lines().map(|row| row.split_whitespace().collect()).collect();
+    assert_eq!(rows[0], [a_path.as_str(), b_path.as_str(), "line"]);
+    assert_eq!(rows[1..], [["x", ".", "apple"], ["x", "x", "banana"], [".", "x", "cherry"]]);
+    main_binary().args(["diff", "--format", "matrix", a_path, b_path]).assert().failure();
+}
+
+#[test]
+fn show_files_lists_the_files_each_line_is_in() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "apple\nbanana\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "banana\ncherry\nbanana\n", Encoding::Plain);
+    let labels = [format!("a={a_path}"), format!("b={b_path}")];
+    let args = ["union", "--show-files", "--label", &labels[0], "--label", &labels[1]];
+    let output = main_binary().args(args).args([a_path, b_path]).unwrap();
+    let expected = "a apple\na,b banana\nb cherry\n";
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    let output =
+        main_binary().args(["intersect", "--show-files", "--format=tsv", a_path, b_path]).unwrap();
+    let expected = format!("{a_path},{b_path}\tbanana\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    main_binary().args(["union", "--show-files", "--count", a_path]).assert().failure();
+}
+
+#[test]
+fn with_filename_prefixes_the_file_each_line_was_first_seen_in() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "apple\nbanana\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "banana\ncherry\n", Encoding::Plain);
+    let output = main_binary().args(["union", "-H", a_path, b_path]).unwrap();
+    let expected = format!("{a_path}:apple\n{a_path}:banana\n{b_path}:cherry\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    let output = main_binary().args(["diff", "--with-filename", b_path, a_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{b_path}:cherry\n"));
+    main_binary().args(["union", "-H", "--show-files", a_path]).assert().failure();
+}
+
+#[test]
+fn comm_format_prints_three_columns() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "pear\napple\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "apple\nfig\n", Encoding::Plain);
+    let output = main_binary().args(["union", "--format", "comm", a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "pear\n\t\tapple\n\tfig\n");
+    main_binary().args(["union", "--format", "comm", a_path]).assert().failure();
+}
+
+#[test]
+fn head_caps_the_lines_printed() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "x\ny\nz\ny\nz\nz\nw\n", Encoding::Plain);
+    let output = main_binary().args(["union", "--head", "2", a_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "x\ny\n");
+    let args = ["union", "--count", "--sort-by-count", "--head", "2", a_path];
+    let output = main_binary().args(args).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3 z\n2 y\n");
+    let output = main_binary().args(["multiple", "--sort", "--head=1", a_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "y\n");
+}
+
+#[test]
+fn sample_prints_a_reproducible_random_subset() {
+    let temp = TempDir::new().unwrap();
+    let input: String = (0..100).map(|n| format!("{n}\n")).collect();
+    let a_path = &path_with(&temp, "a.txt", &input, Encoding::Plain);
+    let args = ["union", "--sample", "5", "--seed", "7", a_path];
+    let first = String::from_utf8(main_binary().args(args).unwrap().stdout).unwrap();
+    let lines: Vec<u32> = first.lines().map(|line| line.parse().unwrap()).collect();
+    assert_eq!(lines.len(), 5);
+    assert!(lines.windows(2).all(|pair| pair[0] < pair[1]));
+    let again = String::from_utf8(main_binary().args(args).unwrap().stdout).unwrap();
+    assert_eq!(first, again);
+    let output = main_binary().args(["union", "--sample", "500", a_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), input);
+    main_binary().args(["union", "--seed", "7", a_path]).assert().failure();
+}
+
+#[test]
+fn shuffle_prints_the_lines_in_a_reproducible_random_order() {
+    let temp = TempDir::new().unwrap();
+    let input: String = (0..100).map(|n| format!("{n}\r\n")).collect();
+    let a_path = &path_with(&temp, "a.txt", &input, Encoding::Plain);
+    let args = ["union", "--shuffle", "--seed", "7", a_path];
+    let first = String::from_utf8(main_binary().args(args).unwrap().stdout).unwrap();
+    assert_ne!(first, input);
+    assert_eq!(first.matches("\r\n").count(), 100);
+    let mut lines: Vec<u32> = first.lines().map(|line| line.parse().unwrap()).collect();
+    lines.sort_unstable();
+    assert_eq!(lines, (0..100).collect::<Vec<_>>());
+    let again = String::from_utf8(main_binary().args(args).unwrap().stdout).unwrap();
+    assert_eq!(first, again);
+    main_binary().args(["union", "--shuffle", "--sort", a_path]).assert().failure();
+}
+
+#[test]
+fn count_lines_and_count_files_together_print_both_counts() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a\nb\na\na\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "a\nc\n", Encoding::Plain);
+    let args = ["union", "--count-lines", "--count-files", a_path, b_path];
+    let output = main_binary().args(args).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "4 2 a\n1 1 b\n1 1 c\n");
+    let args = ["diff", "--count-files", "--count-lines", "--format", "csv", "--header-row"];
+    let output = main_binary().args(args).args([a_path, b_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "count,files,line\n1,1,b\n");
+    let args = ["union", "--count-lines", "--count-files", "--count", a_path, b_path];
+    let output = main_binary().args(args).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "4 a\n1 b\n1 c\n");
+}
+
+#[test]
+fn count_per_file_prints_a_count_column_for_each_file() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a\nb\na\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "c\na\n", Encoding::Plain);
+    let c_path = &path_with(&temp, "c.txt", &"c\n".repeat(12), Encoding::Plain);
+    let args = ["union", "--count-per-file", a_path, b_path, c_path];
+    let output = main_binary().args(args).unwrap();
+    let expected = " 2  1  0 a\n 1  0  0 b\n 0  1 12 c\n";
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    let args = ["intersect", "--count-per-file", "--format", "tsv", "--header-row", a_path, b_path];
+    let output = main_binary().args(args).unwrap();
+    let expected = format!("{a_path}\t{b_path}\tline\n2\t1\ta\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    let args = ["union", "--count-per-file", "--show-files", a_path];
+    main_binary().args(args).assert().failure();
+}
+
+#[test]
+fn count_delimiter_separates_counts_from_lines() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "  7 up\n42\n  7 up\n", Encoding::Plain);
+    let args = ["union", "--count", "--count-delimiter", "\\t", a_path];
+    let output = main_binary().args(args).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "2\t  7 up\n1\t42\n");
+}
+
+#[test]
+fn count_after_prints_counts_after_the_line() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "x y\nz\nx y\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "z\n", Encoding::Plain);
+    let args = ["union", "--count", "--count-after", a_path];
+    let output = main_binary().args(args).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "x y\t2\nz\t1\n");
+    let args = ["union", "--count-lines", "--count-files", "--count-after", "--format", "csv"];
+    let output = main_binary().args(args).args(["--header-row", a_path, b_path]).unwrap();
+    let expected = "line,count,files\nx y,2,1\nz,2,2\n";
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    let output = main_binary().args(["top", "--count-after", a_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "x y\t2\nz\t1\n");
+    main_binary().args(["union", "--count-after", a_path]).assert().failure();
+}
+
+#[test]
+fn count_align_pads_counts_as_asked() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", &("x\n".repeat(10) + "y\n"), Encoding::Plain);
+    let run = |align| {
+        let output =
+            main_binary().args(["union", "--count", "--count-align", align, a_path]).unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(run("right"), "10 x\n 1 y\n");
+    assert_eq!(run("left"), "10 x\n1  y\n");
+    assert_eq!(run("none"), "10 x\n1 y\n");
+    main_binary().args(["union", "--count-align", "left", a_path]).assert().failure();
+}
+
+#[test]
+fn quiet_prints_nothing_and_exits_by_whether_there_are_lines() {
+    let temp = TempDir::new().unwrap();
+    let a_path = &path_with(&temp, "a.txt", "a\nb\n", Encoding::Plain);
+    let b_path = &path_with(&temp, "b.txt", "b\nc\n", Encoding::Plain);
+    let c_path = &path_with(&temp, "c.txt", "a\nd\n", Encoding::Plain);
+    let cases = [
+        (vec!["intersect", a_path, b_path], 0),
+        (vec!["intersect", a_path, b_path, c_path], 1),
+        (vec!["diff", a_path, b_path], 0),
+        (vec!["diff", a_path, b_path, c_path], 1),
+        (vec!["multiple", "--files", b_path, c_path], 1),
+        (vec!["single", "--files", a_path, a_path], 1),
+        (vec!["union", a_path], 0),
+        (vec!["diff", "--invert", a_path, b_path, c_path], 0),
+        (vec!["multiple", a_path], 1),
+    ];
+    for (args, status) in cases {
+        let output = main_binary().arg("-q").args(&args).output().unwrap();
+        assert!(output.stdout.is_empty(), "{args:?}");
+        assert_eq!(output.status.code(), Some(status), "{args:?}");
+    }
+
+    main_binary().args(["stats", "-q", a_path]).assert().failure().code(2);
+    main_binary().args(["union", "-q", "--summary", a_path]).assert().failure().code(2);
+}
+
+#[test]
+fn si_abbreviates_counts() {
+    let temp = TempDir::new().unwrap();
+    let input = "a\n".repeat(1234) + &"b\n".repeat(5);
+    let x_path = &path_with(&temp, "x.txt", &input, Encoding::Plain);
+    let output = main_binary().args(["union", "--count-lines", "--si", x_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1.2k a\n   5 b\n");
+}
+
+#[test]
+fn shell_quote_quotes_each_output_line() {
+    let temp = TempDir::new().unwrap();
+    let x_path = &path_with(&temp, "x.txt", "my file.txt\nit's\n*\n", Encoding::UTF8);
+    let output = main_binary().args(["union", "--shell-quote", x_path]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "'my file.txt'\n'it'\\''s'\n'*'\n");
+}
+
+#[test]
+fn intersect_in_at_least_accepts_counts_and_percentages() {
+    let temp = TempDir::new().unwrap();
+    let paths: Vec<String> = ["a\nb\n", "b\nc\n", "c\nd\n", "b\nc\ne\n", "b\n"]
+        .iter()
+        .enumerate()
+        .map(|(n, contents)| path_with(&temp, &format!("{n}.txt"), contents, Encoding::Plain))
+        .collect();
+    let stdout = |threshold: &str| {
+        let output =
+            main_binary().args(["intersect", "--in-at-least", threshold]).args(&paths).unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(stdout("80%"), "b\n");
+    assert_eq!(stdout("60%"), "b\nc\n");
+    assert_eq!(stdout("2"), "b\nc\n");
+    assert_eq!(stdout("100%"), "");
+    let output = main_binary().args(["intersect", "--at-least", "3"]).args(&paths).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "b\nc\n");
+
+    main_binary().args(["union", "--in-at-least", "2"]).args(&paths).assert().failure();
+    main_binary().args(["intersect", "--in-at-least", "120%"]).args(&paths).assert().failure();
+    // No line is in more files than there are
+    assert_eq!(stdout("6"), "");
+    let output =
+        main_binary().args(["intersect", "--at-least", "3", &paths[0], &paths[1]]).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+}
+
+#[test]
+fn format_csv_and_tsv_write_delimited_columns_with_optional_header() {
+    let temp = TempDir::new().unwrap();
+    let x_path = &path_with(&temp, "x.txt", "a,b\nc\nc\n", Encoding::Plain);
+    let y_path = &path_with(&temp, "y.txt", "c\nd\n", Encoding::Plain);
+    let stdout =
+        |args: &[&str]| String::from_utf8(main_binary().args(args).unwrap().stdout).unwrap();
+
+    assert_eq!(stdout(&["union", "--format", "csv", x_path, y_path]), "\"a,b\"\nc\nd\n");
+    let args = ["union", "--count", "--format", "csv", "--header-row", x_path, y_path];
+    assert_eq!(stdout(&args), "count,line\n1,\"a,b\"\n3,c\n1,d\n");
+    let args = ["union", "--count-split", "--format", "tsv", "--header-row", x_path, y_path];
+    assert_eq!(stdout(&args), "first\trest\tline\n1\t0\ta,b\n2\t1\tc\n0\t1\td\n");
+    let args = ["intersect", "--format", "tsv", "--header-row", x_path, y_path];
+    assert_eq!(stdout(&args), "line\nc\n");
+    let args = ["top", "--format", "csv", "--header-row", "--approx", "10", x_path, y_path];
+    assert_eq!(stdout(&args), "count,at_least,line\n3,3,c\n1,1,\"a,b\"\n1,1,d\n");
+
+    main_binary().args(["union", "--header-row", x_path]).assert().failure();
+    main_binary().args(["union", "--format", "csv", "--shell-quote", x_path]).assert().failure();
+}
@@ -0,0 +1,89 @@
+//! A `Baseline` is a set of lines loaded once and then queried many times, for
+//! `zet serve`: does it contain this line, and which lines of this new batch
+//! aren't in it? The baseline is the union of its operands.
+use crate::operations::{Bookkeeping, Options};
+use crate::set::{LaterOperand, ZetSet};
+use anyhow::Result;
+
+/// The lines of the baseline operands
+pub struct Baseline<'data> {
+    set: ZetSet<'data, Seen>,
+    options: Options,
+}
+
+impl<'data> Baseline<'data> {
+    /// The union of `first_operand` and `rest`, with lines compared (now and
+    /// in later queries) as `options` says. (The operation given by
+    /// `options.op` is ignored.)
+    pub fn new<O: LaterOperand>(
+        options: &Options,
+        first_operand: &'data [u8],
+        rest: impl Iterator<Item = Result<O>>,
+    ) -> Result<Self> {
+        let mut set = ZetSet::with_options(first_operand, Seen, options)?;
+        for operand in options.interrupt.cut_short(rest) {
+            set.insert_or_update(operand?, Seen)?;
+        }
+        Ok(Baseline { set, options: options.clone() })
+    }
+
+    /// The number of distinct lines in the baseline
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    /// Is the baseline empty?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    /// Is `line` in the baseline?
+    pub fn contains(&self, line: &[u8]) -> Result<bool> {
+        self.set.contains(line)
+    }
+
+    /// The distinct lines of `operand` that aren't in the baseline, in order of
+    /// first appearance — what `zet diff` prints for `operand` and the
+    /// baseline operands
+    pub fn missing(&self, operand: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let batch = ZetSet::with_options(operand, Seen, &self.options)?;
+        Ok(batch.lines_not_in(&self.set).map(<[u8]>::to_vec).collect())
+    }
+}
+
+/// Baseline lines need no bookkeeping: they're all in the set
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Seen;
+impl Bookkeeping for Seen {
+    fn new() -> Self {
+        Seen
+    }
+    fn next_file(&mut self) {}
+    fn update_with(&mut self, _other: Self) {}
+    fn retention_value(self) -> u32 {
+        1
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::key::Key;
+    use crate::operations::OpName;
+
+    #[test]
+    fn queries_compare_lines_as_the_options_say() {
+        let mut options = Options::new(OpName::Union);
+        options.key = Key::fields(&[1], None);
+        let rest = [Ok(&b"c 3\n"[..])].into_iter();
+        let baseline = Baseline::new(&options, b"a 1\nb 2\na 9\n", rest).unwrap();
+        assert_eq!(baseline.len(), 3);
+        assert!(baseline.contains(b"b 7").unwrap());
+        assert!(!baseline.contains(b"d 1").unwrap());
+        let missing = baseline.missing(b"d 1\nb 5\ne 2\nd 3\n").unwrap();
+        assert_eq!(missing, [b"d 1".to_vec(), b"e 2".to_vec()]);
+    }
+}
@@ -0,0 +1,404 @@
+//! The `equal`, `is-subset`, and `is-disjoint` commands don't print lines; they
+//! test a condition on their operands, and report the verdict in their exit
+//! code. The `Condition` enum names the condition, and its `holds` method
+//! decides it.
+//!
+//! A verdict is often settled long before the input runs out: two files aren't
+//! disjoint once we've seen a line in both, and aren't equal once we've seen a
+//! line in one but not the other. With `fail_fast` set, `holds` stops reading
+//! as soon as that happens.
+//!
+//! With `-v`, the conditions also report why they fail. For `equal`,
+//! `write_unequal` prints the lines that aren't in every operand; for
+//! `is-subset`, `write_unseen` prints the lines of the first operand that no
+//! other operand has — both of which mean reading all the input. For
+//! `is-disjoint`, `write_first_shared` prints the first line found in two
+//! operands, and stops reading there.
+//!
+//! The conditions also answer `-q`, which asks only whether a command would
+//! print any lines: `diff` prints none just when its first operand is a subset
+//! of the others, for instance. `Condition::of_no_lines` gives the condition,
+//! when there is one.
+use crate::operations::{Bookkeeping, OpName, Options};
+use crate::set::{LaterOperand, ZetSet};
+use anyhow::Result;
+
+/// A condition on the operands
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Condition {
+    /// Every operand contains exactly the same set of lines
+    Equal,
+    /// Every line of the first operand occurs in some other operand
+    Subset,
+    /// No line occurs in more than one operand
+    Disjoint,
+}
+
+impl Condition {
+    /// The name of the command that tests the condition
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Condition::Equal => "equal",
+            Condition::Subset => "is-subset",
+            Condition::Disjoint => "is-disjoint",
+        }
+    }
+
+    /// For `-q`: a condition that holds just when `options.op`, applied to
+    /// `operands` operands, would print no lines — if there is one, and
+    /// `options` asks for nothing that could leave out lines (like `--invert`
+    /// or `--head 0`) or print others (like `--header-row`)
+    #[must_use]
+    pub fn of_no_lines(options: &Options, operands: usize) -> Option<Condition> {
+        let filtered = options.top.is_some()
+            || options.approx.is_some()
+            || options.in_at_least.is_some()
+            || options.min_files.is_some()
+            || options.max_files.is_some()
+            || options.min_count.is_some()
+            || options.max_count.is_some()
+            || options.invert
+            || options.count_distinct.is_some()
+            || options.counted_input
+            || options.head == Some(0)
+            || options.sample.is_some_and(|sample| sample.size == 0);
+        let headed = options.header_row || options.grouped.is_some();
+        if filtered || headed {
+            return None;
+        }
+        match options.op {
+            OpName::Diff => Some(Condition::Subset),
+            OpName::MultipleByFile => Some(Condition::Disjoint),
+            OpName::Intersect if operands == 2 => Some(Condition::Disjoint),
+            OpName::SingleByFile if operands == 2 => Some(Condition::Equal),
+            _ => None,
+        }
+    }
+
+    /// Does the condition hold for `first_operand` and `rest`, comparing lines
+    /// as `options` says? (The operation given by `options.op` is ignored.)
+    /// With `fail_fast`, return as soon as the verdict is certain, without
+    /// reading the rest of the input.
+    pub fn holds<O: LaterOperand>(
+        self,
+        options: &Options,
+        first_operand: &[u8],
+        rest: impl Iterator<Item = Result<O>>,
+        fail_fast: bool,
+    ) -> Result<bool> {
+        let mut item = LastSeen::new();
+        let mut set = ZetSet::with_options(first_operand, item, options)?;
+        // Lines of the first operand not yet seen in another operand
+        let mut unseen = set.len();
+        let mut holds = match self {
+            Condition::Equal | Condition::Disjoint => true,
+            Condition::Subset => unseen == 0,
+        };
+        for operand in options.interrupt.cut_short(options.tally.counting(rest)) {
+            if fail_fast && self.is_settled(holds) {
+                break;
+            }
+            item.next_file();
+            let this_file = item.0;
+            // For `Equal`: distinct lines seen in this operand
+            let mut seen_here = 0;
+            let insert = self == Condition::Disjoint;
+            set.scan_while(operand?, item, insert, |previous| {
+                match (self, previous) {
+                    (Condition::Equal, None) => holds = false,
+                    (Condition::Equal, Some(LastSeen(file))) => {
+                        seen_here += usize::from(file != this_file);
+                    }
+                    (Condition::Subset, Some(LastSeen(0))) => {
+                        unseen -= 1;
+                        holds = unseen == 0;
+                    }
+                    (Condition::Disjoint, Some(LastSeen(file))) if file != this_file => {
+                        holds = false;
+                    }
+                    _ => {}
+                }
+                !(fail_fast && self.is_settled(holds))
+            })?;
+            if self == Condition::Equal && seen_here < set.len() {
+                holds = false;
+            }
+        }
+        Ok(holds)
+    }
+
+    /// For `Condition::Subset`: write the lines of `first_operand` that aren't
+    /// in any of `rest` to `out`, and return `true` if there are none (so the
+    /// condition holds). Lines are compared, and written, as `options` says.
+    pub fn write_unseen<O: LaterOperand>(
+        options: &Options,
+        first_operand: &[u8],
+        rest: impl Iterator<Item = Result<O>>,
+        mut out: impl std::io::Write,
+    ) -> Result<bool> {
+        let mut item = LastSeen::new();
+        let mut set = ZetSet::with_options(first_operand, item, options)?;
+        for operand in options.interrupt.cut_short(options.tally.counting(rest)) {
+            item.next_file();
+            set.update_if_present(operand?, item)?;
+        }
+        set.retain(|last_seen| last_seen == 0);
+        LastSeen::output_zet_set(&set, options, &mut out)?;
+        out.flush()?;
+        Ok(set.is_empty())
+    }
+
+    /// For `Condition::Disjoint`: write the first line found in more than one
+    /// of `first_operand` and `rest` to `out`, without reading any further,
+    /// and return `true` if there's no such line (so the condition holds).
+    /// Lines are compared, and written, as `options` says.
+    pub fn write_first_shared<O: LaterOperand>(
+        options: &Options,
+        first_operand: &[u8],
+        rest: impl Iterator<Item = Result<O>>,
+        mut out: impl std::io::Write,
+    ) -> Result<bool> {
+        let mut item = Spread::new();
+        let mut set = ZetSet::with_options(first_operand, item, options)?;
+        let mut disjoint = true;
+        for operand in options.interrupt.cut_short(options.tally.counting(rest)) {
+            item.next_file();
+            set.scan_while(operand?, item, true, |previous| {
+                disjoint = previous.map_or(true, |Spread { last, .. }| last == item.last);
+                disjoint
+            })?;
+            if !disjoint {
+                break;
+            }
+        }
+        set.retain(|files| files > 1);
+        Spread::output_zet_set(&set, options, &mut out)?;
+        out.flush()?;
+        Ok(disjoint)
+    }
+
+    /// For `Condition::Equal`: write the lines that aren't in every one of
+    /// `first_operand` and `rest` to `out`, and return `true` if there are
+    /// none (so the condition holds). Lines are compared, and written, as
+    /// `options` says.
+    pub fn write_unequal<O: LaterOperand>(
+        options: &Options,
+        first_operand: &[u8],
+        rest: impl Iterator<Item = Result<O>>,
+        mut out: impl std::io::Write,
+    ) -> Result<bool> {
+        let mut item = Spread::new();
+        let mut set = ZetSet::with_options(first_operand, item, options)?;
+        for operand in options.interrupt.cut_short(options.tally.counting(rest)) {
+            item.next_file();
+            set.insert_or_update(operand?, item)?;
+        }
+        let all_files = item.last + 1;
+        set.retain(|files| files != all_files);
+        Spread::output_zet_set(&set, options, &mut out)?;
+        out.flush()?;
+        Ok(set.is_empty())
+    }
+
+    /// Is `holds` the final verdict, whatever the rest of the input contains?
+    fn is_settled(self, holds: bool) -> bool {
+        match self {
+            Condition::Equal | Condition::Disjoint => !holds,
+            Condition::Subset => holds,
+        }
+    }
+}
+
+/// The bookkeeping value for each line is the number of the last operand it
+/// was seen in.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct LastSeen(u32);
+impl Bookkeeping for LastSeen {
+    fn new() -> Self {
+        LastSeen(0)
+    }
+    fn next_file(&mut self) {
+        self.0 = self.0.wrapping_add(1);
+    }
+    fn update_with(&mut self, other: Self) {
+        self.0 = other.0;
+    }
+    fn retention_value(self) -> u32 {
+        self.0
+    }
+}
+
+/// For `write_first_shared` and `write_unequal`, the bookkeeping value for
+/// each line is the number of the last operand it was seen in, and the number
+/// of operands it's been seen in.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Spread {
+    last: u32,
+    files: u32,
+}
+impl Bookkeeping for Spread {
+    fn new() -> Self {
+        Spread { last: 0, files: 1 }
+    }
+    fn next_file(&mut self) {
+        self.last = self.last.wrapping_add(1);
+    }
+    fn update_with(&mut self, other: Self) {
+        if self.last != other.last {
+            self.files += 1;
+            self.last = other.last;
+        }
+    }
+    fn retention_value(self) -> u32 {
+        self.files
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::operations::calculate;
+    use std::cell::Cell;
+
+    /// An operand that counts the lines read from it
+    struct Counted<'a>(&'a [u8], &'a Cell<usize>);
+    impl LaterOperand for Counted<'_> {
+        fn for_byte_line(self, mut for_each_line: impl FnMut(&[u8])) -> Result<()> {
+            self.for_byte_line_while(|line| {
+                for_each_line(line);
+                true
+            })
+        }
+        fn for_byte_line_while(self, mut for_each_line: impl FnMut(&[u8]) -> bool) -> Result<()> {
+            for line in self.0.split(|&b| b == b'\n').filter(|line| !line.is_empty()) {
+                self.1.set(self.1.get() + 1);
+                if !for_each_line(line) {
+                    break;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// The verdict, and the number of lines read from later operands
+    fn decide(condition: Condition, operands: &[&str], fail_fast: bool) -> (bool, usize) {
+        let read = Cell::new(0);
+        let rest = operands[1..].iter().map(|o| Ok(Counted(o.as_bytes(), &read)));
+        let options = Options::new(OpName::Union);
+        let holds = condition.holds(&options, operands[0].as_bytes(), rest, fail_fast).unwrap();
+        (holds, read.get())
+    }
+
+    #[test]
+    fn conditions_are_decided_correctly() {
+        use Condition::*;
+        let cases = [
+            (Equal, vec!["a\nb\n", "b\na\nb\n", "a\nb\n"], true),
+            (Equal, vec!["a\nb\n", "a\n"], false),
+            (Equal, vec!["a\n", "a\nb\n"], false),
+            (Subset, vec!["a\nb\n", "b\nc\n", "a\n"], true),
+            (Subset, vec!["a\nb\n", "b\nc\n"], false),
+            (Subset, vec!["", "b\n"], true),
+            (Disjoint, vec!["a\na\n", "b\nb\n", "c\n"], true),
+            (Disjoint, vec!["a\n", "b\n", "c\nb\n"], false),
+        ];
+        for (condition, operands, expected) in cases {
+            for fail_fast in [false, true] {
+                let (holds, _) = decide(condition, &operands, fail_fast);
+                assert_eq!(holds, expected, "{condition:?} {operands:?} fail_fast={fail_fast}");
+            }
+        }
+    }
+
+    #[test]
+    fn conditions_for_no_lines_agree_with_the_operations() {
+        let cases = [
+            vec!["a\nb\n", "b\na\n"],
+            vec!["a\nb\n", "b\nc\n"],
+            vec!["a\n", "b\n"],
+            vec!["", "b\n"],
+            vec!["a\n", "b\n", "c\nb\n"],
+            vec!["a\nb\n", "b\n", "a\nc\n"],
+        ];
+        let ops = [OpName::Diff, OpName::MultipleByFile, OpName::Intersect, OpName::SingleByFile];
+        let mut decided = 0;
+        for operands in cases {
+            for op in ops {
+                let options = Options::new(op);
+                let Some(condition) = Condition::of_no_lines(&options, operands.len()) else {
+                    continue;
+                };
+                decided += 1;
+                let mut out = Vec::new();
+                let read = Cell::new(0);
+                let rest = operands[1..].iter().map(|o| Ok(Counted(o.as_bytes(), &read)));
+                calculate(&options, operands[0].as_bytes(), rest, &mut out).unwrap();
+                let rest = operands[1..].iter().map(|o| Ok(Counted(o.as_bytes(), &read)));
+                let none = condition.holds(&options, operands[0].as_bytes(), rest, true).unwrap();
+                assert_eq!(none, out.is_empty(), "{op:?} {operands:?}");
+            }
+        }
+        assert_eq!(decided, 20);
+
+        let mut options = Options::new(OpName::Diff);
+        options.invert = true;
+        assert_eq!(Condition::of_no_lines(&options, 2), None);
+        assert_eq!(Condition::of_no_lines(&Options::new(OpName::Union), 2), None);
+        assert_eq!(Condition::of_no_lines(&Options::new(OpName::Intersect), 3), None);
+    }
+
+    #[test]
+    fn fail_fast_stops_reading_once_the_verdict_is_certain() {
+        let operands = ["a\nb\n", "x\ny\nz\n", "a\nb\nc\n"];
+        assert_eq!(decide(Condition::Equal, &operands, false), (false, 6));
+        assert_eq!(decide(Condition::Equal, &operands, true), (false, 1));
+        let operands = ["a\nb\n", "b\na\nc\n", "d\n"];
+        assert_eq!(decide(Condition::Subset, &operands, true), (true, 2));
+        let operands = ["a\n", "b\nc\n", "c\nd\ne\n"];
+        assert_eq!(decide(Condition::Disjoint, &operands, true), (false, 3));
+        assert_eq!(decide(Condition::Disjoint, &operands, false), (false, 5));
+    }
+
+    #[test]
+    fn unseen_lines_are_those_of_the_first_operand_in_no_other() {
+        let unseen = |operands: [&str; 3]| {
+            let rest = operands[1..].iter().map(|o| Ok(o.as_bytes()));
+            let mut out = Vec::new();
+            let options = Options::new(OpName::Union);
+            let holds = Condition::write_unseen(&options, operands[0].as_bytes(), rest, &mut out);
+            (holds.unwrap(), String::from_utf8(out).unwrap())
+        };
+        assert_eq!(unseen(["a\nb\nc\nd\n", "c\n", "a\nx\n"]), (false, "b\nd\n".to_string()));
+        assert_eq!(unseen(["a\nb\n", "b\n", "a\n"]), (true, String::new()));
+    }
+
+    #[test]
+    fn the_first_shared_line_is_the_first_found_in_a_second_operand() {
+        let shared = |operands: [&str; 3]| {
+            let read = Cell::new(0);
+            let rest = operands[1..].iter().map(|o| Ok(Counted(o.as_bytes(), &read)));
+            let mut out = Vec::new();
+            let options = Options::new(OpName::Union);
+            let holds =
+                Condition::write_first_shared(&options, operands[0].as_bytes(), rest, &mut out);
+            (holds.unwrap(), String::from_utf8(out).unwrap(), read.get())
+        };
+        assert_eq!(shared(["a\na\n", "b\nc\n", "d\nc\nb\n"]), (false, "c\n".to_string(), 4));
+        assert_eq!(shared(["a\n", "b\nb\n", "c\n"]), (true, String::new(), 3));
+    }
+
+    #[test]
+    fn unequal_lines_are_those_not_in_every_operand() {
+        let unequal = |operands: [&str; 3]| {
+            let rest = operands[1..].iter().map(|o| Ok(o.as_bytes()));
+            let mut out = Vec::new();
+            let options = Options::new(OpName::Union);
+            let holds = Condition::write_unequal(&options, operands[0].as_bytes(), rest, &mut out);
+            (holds.unwrap(), String::from_utf8(out).unwrap())
+        };
+        assert_eq!(unequal(["a\nb\nc\n", "c\nb\n", "b\nd\nc\n"]), (false, "a\nd\n".to_string()));
+        assert_eq!(unequal(["a\nb\n", "b\na\n", "a\nb\na\n"]), (true, String::new()));
+    }
+}
@@ -0,0 +1,128 @@
+//! `zet merge-counts` merges files that are themselves counted output — from
+//! `zet --count`, or `uniq -c` — summing the counts of each line, so counts
+//! made separately (say, per host) can be combined (say, for a whole fleet).
+//!
+//! Each input line is a count, then a single space or tab, then the counted
+//! line. Leading blanks are skipped, since counts are usually right-aligned.
+//! A count of `overflow` (which `zet` writes for counts of `u32::MAX` or more)
+//! is taken to be `u32::MAX`.
+//!
+//! With `--counted-input`, other commands read their input the same way, with
+//! each line standing for as many occurrences as its count says.
+//!
+//! Either way, a line with a count of zero stands for no occurrences, so it's
+//! skipped.
+use crate::operations::{self, CountColumns, Options};
+use crate::set::{self, LaterOperand};
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+
+/// Split a counted line into its count and the line counted, or return `None`
+/// if it doesn't start with a count.
+pub(crate) fn parse(line: &[u8]) -> Option<(u64, &[u8])> {
+    let line = line.trim_start_with(|c| c == ' ' || c == '\t');
+    let digits = line.iter().take_while(|b| b.is_ascii_digit()).count();
+    let (count, rest) = if digits > 0 {
+        let count = std::str::from_utf8(&line[..digits]).ok()?.parse().ok()?;
+        (count, &line[digits..])
+    } else {
+        (u64::from(u32::MAX), line.strip_prefix(b"overflow")?)
+    };
+    match rest {
+        [] => Some((count, rest)),
+        [b' ' | b'\t', counted @ ..] => Some((count, counted)),
+        _ => None,
+    }
+}
+
+/// For `--counted-input`: split a counted line into its count (at most
+/// `u32::MAX`, the largest count we keep) and the line counted, or return an
+/// error if it doesn't start with a count.
+pub(crate) fn weight_of(line: &[u8]) -> Result<(u32, &[u8])> {
+    let (count, counted) = parse(line).ok_or_else(|| not_counted(line))?;
+    Ok((u32::try_from(count).unwrap_or(u32::MAX), counted))
+}
+
+fn not_counted(line: &[u8]) -> anyhow::Error {
+    anyhow!("Line doesn't start with a count: {}", line.as_bstr())
+}
+
+/// Print each line of the input once, with the sum of its counts, in order of
+/// first appearance
+pub(crate) fn merge<O: LaterOperand>(
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl Iterator<Item = Result<O>>,
+    mut out: impl std::io::Write,
+) -> Result<()> {
+    let (bom, line_terminator) = set::output_info(first_operand, &options.framing);
+    let mut merged = Merged::default();
+    set::for_each_record(&first_operand[bom.len()..], options, |line| {
+        merged.add(options, line);
+    });
+    for operand in options.interrupt.cut_short(rest) {
+        operand?.for_byte_line(|line| merged.add(options, line))?;
+    }
+    if let Some(line) = merged.malformed {
+        return Err(not_counted(&line));
+    }
+
+    if merged.counts.is_empty() && !options.header_row {
+        return Ok(());
+    }
+    let columns = CountColumns::new(options, merged.counts.values().map(|(_, count)| *count));
+    out.write_all(options.line_format.bom(bom))?;
+    operations::write_header(&["count"], options, line_terminator, &mut out)?;
+    for (line, count) in merged.counts.values() {
+        let write_counts = |out: &mut _| columns.write(*count, out);
+        columns.write_row(line, options, line_terminator, &mut out, write_counts)?;
+    }
+    options.tally.wrote(merged.counts.len() as u64);
+    out.flush()?;
+    Ok(())
+}
+
+/// The merged counts: for each key, the first line seen with that key, and the
+/// sum of the counts. `malformed` is the first line without a count, if any.
+#[derive(Default)]
+struct Merged {
+    counts: IndexMap<Vec<u8>, (Vec<u8>, u64), FxBuildHasher>,
+    malformed: Option<Vec<u8>>,
+}
+
+impl Merged {
+    fn add(&mut self, options: &Options, line: &[u8]) {
+        if self.malformed.is_some() {
+            return;
+        }
+        let Some((count, counted)) = parse(line) else {
+            self.malformed = Some(line.to_vec());
+            return;
+        };
+        if count == 0 {
+            return;
+        }
+        let key = options.key.of(counted).into_owned();
+        let (_, total) = self.counts.entry(key).or_insert_with(|| (counted.to_vec(), 0));
+        *total = total.saturating_add(count);
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counts_are_split_from_the_lines_they_count() {
+        assert_eq!(parse(b"  12 a b"), Some((12, &b"a b"[..])));
+        assert_eq!(parse(b"3\t x"), Some((3, &b" x"[..])));
+        assert_eq!(parse(b"7"), Some((7, &b""[..])));
+        assert_eq!(parse(b" overflow big"), Some((u32::MAX as u64, &b"big"[..])));
+        assert_eq!(parse(b"12x"), None);
+        assert_eq!(parse(b"1.2k line"), None);
+        assert_eq!(parse(b"line"), None);
+    }
+}
@@ -0,0 +1,82 @@
+//! `zet union --key FIELDS --count-distinct FIELD` counts, for each key, the
+//! distinct values that another field takes in the lines with that key — the
+//! number of distinct user ids that visited each URL, say. It writes a line
+//! `KEY<TAB>COUNT` for each key, in order of first appearance.
+//!
+//! Counts are exact: we remember each distinct (key, value) pair, so memory
+//! grows with the number of pairs rather than the number of keys.
+use crate::key::Key;
+use crate::operations::Options;
+use crate::set::{self, LaterOperand};
+use anyhow::Result;
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+use std::collections::HashSet;
+
+/// Print each key of the input once, with the number of distinct values that
+/// `value` takes in the lines with that key
+pub(crate) fn count<O: LaterOperand>(
+    options: &Options,
+    value: &Key,
+    first_operand: &[u8],
+    rest: impl Iterator<Item = Result<O>>,
+    mut out: impl std::io::Write,
+) -> Result<()> {
+    let (bom, line_terminator) = set::output_info(first_operand, &options.framing);
+    let mut distinct = Distinct::default();
+    set::for_each_record(&first_operand[bom.len()..], options, |line| {
+        distinct.add(options, value, line);
+    });
+    for operand in options.interrupt.cut_short(rest) {
+        operand?.for_byte_line(|line| distinct.add(options, value, line))?;
+    }
+
+    out.write_all(options.line_format.bom(bom))?;
+    for (key, count) in &distinct.counts {
+        options.line_format.write(key, &mut out)?;
+        write!(out, "\t{count}")?;
+        out.write_all(line_terminator)?;
+    }
+    options.tally.wrote(distinct.counts.len() as u64);
+    out.flush()?;
+    Ok(())
+}
+
+/// For each key, the number of distinct values seen with it; and the (key,
+/// value) pairs seen, with each key given by its index in `counts`
+#[derive(Default)]
+struct Distinct {
+    counts: IndexMap<Vec<u8>, u64, FxBuildHasher>,
+    pairs: HashSet<(usize, Vec<u8>), FxBuildHasher>,
+}
+
+impl Distinct {
+    fn add(&mut self, options: &Options, value: &Key, line: &[u8]) {
+        let key = options.key.of(line);
+        let index = match self.counts.get_index_of(key.as_ref()) {
+            Some(index) => index,
+            None => self.counts.insert_full(key.into_owned(), 0).0,
+        };
+        if self.pairs.insert((index, value.of(line).into_owned())) {
+            self.counts[index] += 1;
+        }
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::operations::OpName;
+
+    #[test]
+    fn each_key_gets_the_number_of_distinct_values_seen_with_it() {
+        let mut options = Options::new(OpName::Union);
+        options.key = Key::fields(&[2], None);
+        let rest = [Ok(&b"u1 /a\nu3 /b\n"[..])].into_iter();
+        let mut out = Vec::new();
+        let value = Key::fields(&[1], None);
+        count(&options, &value, b"u1 /a\nu2 /a\nu1 /b\nu1 /a\n", rest, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "/a\t2\n/b\t2\n");
+    }
+}
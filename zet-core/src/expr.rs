@@ -0,0 +1,367 @@
+//! `zet expr` evaluates an expression over named operands, like
+//! `(a.txt & b.txt) - c.txt`, in one process — rather than in a pipeline of
+//! `zet` invocations. The operators are:
+//!
+//! * `-`, difference: the lines of the left operand not in the right one,
+//! * `&`, intersection: the lines in both operands,
+//! * `^`, symmetric difference: the lines in just one of the operands, and
+//! * `|`, union: the lines in either operand,
+//!
+//! from the most tightly binding to the least (so `a | b & c` means
+//! `a | (b & c)`), with each grouping to the left (so `a - b - c` means
+//! `(a - b) - c`). Parentheses group as usual.
+//!
+//! An operand name is a run of characters other than whitespace, parentheses,
+//! and the operators `&`, `^`, and `|`. A `-` is the difference operator only
+//! when it's a word on its own, so `a-now.txt` is a name. Names with other
+//! characters in them can be quoted with `'` or `"`.
+//!
+//! `zet eval` takes the same kind of expression as a list of signed operands:
+//! `+a.txt +b.txt -c.txt` adds the lines of `a.txt` and `b.txt`, then takes
+//! away those of `c.txt`, meaning `(a.txt | b.txt) - c.txt`. The signs apply
+//! from left to right, so `+a -b +c` means `(a - b) | c`.
+//!
+//! Operand groups, as in `zet diff a.txt b.txt -- c.txt d.txt`, are another
+//! way to write an expression: each group stands for the union of its files,
+//! and the groups are combined by the operation, so this one means
+//! `(a.txt | b.txt) - (c.txt | d.txt)`.
+//!
+//! We don't evaluate the expression one operator at a time. Instead, each line
+//! of every operand goes into a single `ZetSet`, whose bookkeeping values (as
+//! for `venn`) are bit masks of the operands a line occurs in, and we keep the
+//! lines for whose masks the expression holds. So output is in order of first
+//! appearance, as for the other operations, and the operands are read just
+//! once each, even if named more than once.
+use crate::operations::{arrange, Bookkeeping, OpName, Options};
+use crate::set::{LaterOperand, ZetSet};
+use crate::venn::{Membership, MAX_OPERANDS};
+use anyhow::{bail, Result};
+
+/// A parsed set expression
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expression {
+    root: Node,
+    /// The names of the distinct operands, in order of first appearance
+    operands: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    /// The `n`th operand
+    Operand(usize),
+    Diff(Box<Node>, Box<Node>),
+    Intersect(Box<Node>, Box<Node>),
+    Xor(Box<Node>, Box<Node>),
+    Union(Box<Node>, Box<Node>),
+}
+
+impl Node {
+    /// Does the expression hold for a line in just the operands whose bits are
+    /// set in `members`?
+    fn holds(&self, members: u64) -> bool {
+        match self {
+            Node::Operand(n) => members & (1 << n) != 0,
+            Node::Diff(a, b) => a.holds(members) && !b.holds(members),
+            Node::Intersect(a, b) => a.holds(members) && b.holds(members),
+            Node::Xor(a, b) => a.holds(members) != b.holds(members),
+            Node::Union(a, b) => a.holds(members) || b.holds(members),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Operator(char),
+    Name(String),
+}
+
+/// The operators, from the least tightly binding to the most
+const OPERATORS: [char; 4] = ['|', '^', '&', '-'];
+
+impl Expression {
+    /// Parse `text`, returning an error saying what's wrong if it isn't a
+    /// well-formed expression
+    pub fn parse(text: &str) -> Result<Self> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens: &tokens, next: 0, operands: Vec::new() };
+        let root = parser.expression(0)?;
+        match parser.tokens.get(parser.next) {
+            None => {}
+            Some(Token::Close) => bail!("Unmatched `)` in the expression"),
+            Some(token) => bail!("Expected an operator before {}", describe(token)),
+        }
+        Expression::checked(root, parser.operands)
+    }
+
+    /// The expression for `signed`, a list of operand names each prefixed by
+    /// `+` (to add its lines) or `-` (to take them away), applied from left to
+    /// right. The first must be added.
+    pub fn signed<'a>(signed: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let mut operands = Vec::new();
+        let mut root = None;
+        for word in signed {
+            let mut chars = word.chars();
+            let (sign, name) = (chars.next(), chars.as_str());
+            if name.is_empty() || !matches!(sign, Some('+' | '-')) {
+                bail!("`{word}` should be a file name prefixed by `+` or `-`");
+            }
+            let node = Box::new(Node::Operand(operand_number(&mut operands, name)));
+            root = Some(match (root, sign) {
+                (None, Some('+')) => *node,
+                (None, _) => bail!("The first file, `{name}`, must be added with `+`"),
+                (Some(root), Some('+')) => Node::Union(Box::new(root), node),
+                (Some(root), _) => Node::Diff(Box::new(root), node),
+            });
+        }
+        let Some(root) = root else { bail!("Expected at least one file prefixed by `+`") };
+        Expression::checked(root, operands)
+    }
+
+    /// The expression combining `groups` of operand names with `op` (which
+    /// must be `Union`, `Intersect`, or `Diff`), where each group stands for
+    /// the union of its operands. `Diff` takes the lines of the first group
+    /// less those of all the others.
+    pub fn grouped<'a, G: IntoIterator<Item = &'a str>>(
+        op: OpName,
+        groups: impl IntoIterator<Item = G>,
+    ) -> Result<Self> {
+        let combine = match op {
+            OpName::Union => Node::Union,
+            OpName::Intersect => Node::Intersect,
+            OpName::Diff => Node::Diff,
+            op => bail!("Only union, intersect, and diff can combine groups, not {}", op.name()),
+        };
+        let mut operands = Vec::new();
+        let mut root = None;
+        for group in groups {
+            let mut names = group.into_iter();
+            let Some(first) = names.next() else { bail!("An operand group is empty") };
+            let mut node = Node::Operand(operand_number(&mut operands, first));
+            for name in names {
+                let operand = Node::Operand(operand_number(&mut operands, name));
+                node = Node::Union(Box::new(node), Box::new(operand));
+            }
+            root = Some(match root {
+                None => node,
+                Some(root) => combine(Box::new(root), Box::new(node)),
+            });
+        }
+        let Some(root) = root else { bail!("Expected at least one operand group") };
+        Expression::checked(root, operands)
+    }
+
+    /// The expression with `root` and `operands`, if there aren't too many
+    /// operands
+    fn checked(root: Node, operands: Vec<String>) -> Result<Self> {
+        if operands.len() > MAX_OPERANDS {
+            bail!("An expression can name at most {MAX_OPERANDS} different files");
+        }
+        Ok(Expression { root, operands })
+    }
+
+    /// The names of the operands the expression uses, each once, in order of
+    /// first appearance. `evaluate` expects the operands in this order.
+    #[must_use]
+    pub fn operands(&self) -> &[String] {
+        &self.operands
+    }
+
+    /// Write the lines of the set the expression describes, where
+    /// `first_operand` and `rest` are the contents of the operands named by
+    /// `operands()`, and lines are compared as `options` says. (The operation
+    /// given by `options.op`, and any counts, are ignored.)
+    pub fn evaluate<O: LaterOperand>(
+        &self,
+        options: &Options,
+        first_operand: &[u8],
+        rest: impl Iterator<Item = Result<O>>,
+        out: impl std::io::Write,
+    ) -> Result<()> {
+        let mut item = Membership::new();
+        let mut set = ZetSet::with_options(first_operand, item, options)?;
+        for operand in options.interrupt.cut_short(options.tally.counting(rest)) {
+            item.next_file();
+            set.insert_or_update(operand?, item)?;
+        }
+        set.retain_if(|_line, &Membership(members)| self.root.holds(members));
+        arrange(&mut set, options);
+        Membership::output_zet_set(&set, options, out)
+    }
+}
+
+/// Split `text` into tokens
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '&' | '^' | '|' => tokens.push(Token::Operator(c)),
+            '\'' | '"' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some(d) if d == c => break,
+                        Some(d) => name.push(d),
+                        None => bail!("A quoted file name in the expression has no closing {c}"),
+                    }
+                }
+                if name.is_empty() {
+                    bail!("A quoted file name in the expression is empty");
+                }
+                tokens.push(Token::Name(name));
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(&d) = chars.peek() {
+                    if d.is_whitespace() || "()&^|".contains(d) {
+                        break;
+                    }
+                    word.push(d);
+                    chars.next();
+                }
+                tokens.push(if word == "-" { Token::Operator('-') } else { Token::Name(word) });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// A precedence-climbing parser
+struct Parser<'a> {
+    tokens: &'a [Token],
+    next: usize,
+    operands: Vec<String>,
+}
+
+impl Parser<'_> {
+    /// Parse an expression whose operators bind at least as tightly as
+    /// `OPERATORS[level]`
+    fn expression(&mut self, level: usize) -> Result<Node> {
+        let Some(&operator) = OPERATORS.get(level) else { return self.term() };
+        let mut node = self.expression(level + 1)?;
+        while self.tokens.get(self.next) == Some(&Token::Operator(operator)) {
+            self.next += 1;
+            let (a, b) = (Box::new(node), Box::new(self.expression(level + 1)?));
+            node = match operator {
+                '-' => Node::Diff(a, b),
+                '&' => Node::Intersect(a, b),
+                '^' => Node::Xor(a, b),
+                _ => Node::Union(a, b),
+            };
+        }
+        Ok(node)
+    }
+
+    /// Parse a file name or a parenthesized expression
+    fn term(&mut self) -> Result<Node> {
+        let token = self.tokens.get(self.next);
+        self.next += 1;
+        match token {
+            Some(Token::Name(name)) => Ok(Node::Operand(operand_number(&mut self.operands, name))),
+            Some(Token::Open) => {
+                let node = self.expression(0)?;
+                if self.tokens.get(self.next) != Some(&Token::Close) {
+                    bail!("Unmatched `(` in the expression");
+                }
+                self.next += 1;
+                Ok(node)
+            }
+            Some(token) => bail!("Expected a file name or `(`, not {}", describe(token)),
+            None => bail!("Expected a file name or `(` at the end of the expression"),
+        }
+    }
+}
+
+/// The number of the operand called `name` in `operands`, which we add it to if
+/// it isn't there yet
+fn operand_number(operands: &mut Vec<String>, name: &str) -> usize {
+    operands.iter().position(|operand| operand == name).unwrap_or_else(|| {
+        operands.push(name.to_string());
+        operands.len() - 1
+    })
+}
+
+/// How to refer to `token` in an error message
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Open => "`(`".to_string(),
+        Token::Close => "`)`".to_string(),
+        Token::Operator(c) => format!("`{c}`"),
+        Token::Name(name) => format!("the file name `{name}`"),
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn evaluated(text: &str, operands: &[&str]) -> String {
+        evaluate(&Expression::parse(text).unwrap(), operands)
+    }
+
+    fn evaluate(expression: &Expression, operands: &[&str]) -> String {
+        let contents: Vec<_> = expression
+            .operands()
+            .iter()
+            .map(|name| operands[name.parse::<usize>().unwrap()].as_bytes())
+            .collect();
+        let rest = contents[1..].iter().map(|operand| Ok(*operand));
+        let mut out = Vec::new();
+        expression.evaluate(&Options::new(OpName::Union), contents[0], rest, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn operators_bind_as_documented() {
+        let operands = ["a\nb\nc\nd\n", "b\nc\ne\n", "c\nd\ne\nf\n"];
+        assert_eq!(evaluated("(0 & 1) - 2", &operands), "b\n");
+        assert_eq!(evaluated("0 & 1 - 2", &operands), "b\n");
+        assert_eq!(evaluated("0 | 1 & 2", &operands), "a\nb\nc\nd\ne\n");
+        assert_eq!(evaluated("(0 | 1) & 2", &operands), "c\nd\ne\n");
+        assert_eq!(evaluated("0 ^ 1", &operands), "a\nd\ne\n");
+        assert_eq!(evaluated("0 - 1 - 2", &operands), "a\n");
+        assert_eq!(evaluated("2 - (0 - 1)", &operands), "c\ne\nf\n");
+        assert_eq!(evaluated("1 & 1", &operands), "b\nc\ne\n");
+    }
+
+    #[test]
+    fn names_may_contain_dashes_or_be_quoted() {
+        let expression = Expression::parse("a-now.txt - 'my file.txt'|\"x&y\"").unwrap();
+        assert_eq!(expression.operands(), ["a-now.txt", "my file.txt", "x&y"]);
+        for bad in ["", "a &", "(a | b", "a | b)", "a b", "& a", "'a"] {
+            assert!(Expression::parse(bad).is_err(), "{bad:?} should be an error");
+        }
+    }
+
+    #[test]
+    fn groups_are_unions_combined_by_the_operation() {
+        let operands = ["a\nb\nc\nd\n", "b\nc\ne\n", "c\nd\ne\nf\n", "b\n"];
+        let grouped = |op, groups: &[&[&str]]| {
+            Expression::grouped(op, groups.iter().map(|group| group.iter().copied()))
+        };
+        let diff = grouped(OpName::Diff, &[&["0", "1"], &["2"], &["3"]]).unwrap();
+        assert_eq!(evaluate(&diff, &operands), "a\n");
+        let intersect = grouped(OpName::Intersect, &[&["0", "3"], &["1", "2"]]).unwrap();
+        assert_eq!(evaluate(&intersect, &operands), "b\nc\nd\n");
+        assert!(grouped(OpName::Diff, &[&["0"], &[]]).is_err());
+        assert!(grouped(OpName::Single, &[&["0"], &["1"]]).is_err());
+    }
+
+    #[test]
+    fn signs_apply_from_left_to_right() {
+        let operands = ["a\nb\nc\nd\n", "b\nc\ne\n", "c\nd\ne\nf\n"];
+        let signed = |words: &[&str]| Expression::signed(words.iter().copied());
+        assert_eq!(evaluate(&signed(&["+0", "+1", "-2"]).unwrap(), &operands), "a\nb\n");
+        assert_eq!(evaluate(&signed(&["+0", "-2", "+1"]).unwrap(), &operands), "a\nb\nc\ne\n");
+        assert_eq!(signed(&["+a-b", "-c"]).unwrap().operands(), ["a-b", "c"]);
+        for bad in [&[][..], &["-0", "+1"], &["0"], &["+0", "-"]] {
+            assert!(signed(bad).is_err(), "{bad:?} should be an error");
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! `zet union --hashed`, `zet intersect --hashed`, and `zet diff --hashed`
+//! keep just a 128-bit hash of each line's key, rather than the line, so that
+//! their memory use doesn't depend on the length of the lines — and unlike the
+//! other operations, they don't read the whole first operand into memory.
+//!
+//! `Union` prints each line as soon as its hash turns up for the first time, as
+//! the [huniq](https://crates.io/crates/huniq) command does. `Intersect` and
+//! `Diff` hash the lines of every operand, then read the first operand again,
+//! printing the lines whose hashes survive. Two different keys with the same
+//! 128-bit hash would be taken for the same line, but that's vanishingly
+//! unlikely.
+//!
+//! As with `--stream`, we don't see the start of the input before writing
+//! output: there's no Byte Order Mark, and lines end with `\n`.
+use crate::operations::{write_header, LogType, OpName, Options};
+use crate::set::{output_info, LaterOperand};
+use anyhow::{bail, Result};
+use fxhash::FxBuildHasher;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use xxhash_rust::xxh3::xxh3_128;
+
+/// Print the result of `options.op`, which must be `OpName::Union`,
+/// `OpName::Intersect`, or `OpName::Diff`, on `operands`, keeping just the hash
+/// of each line. For `Intersect` and `Diff`, `first_again` must read the first
+/// operand again.
+pub fn calculate<O: LaterOperand>(
+    options: &Options,
+    operands: impl Iterator<Item = Result<O>>,
+    first_again: impl FnOnce() -> Result<O>,
+    out: impl Write,
+) -> Result<()> {
+    if !matches!(options.op, OpName::Union | OpName::Intersect | OpName::Diff) {
+        bail!(
+            "Only union, intersect, and diff can keep just hashes of lines, not {}",
+            options.op.name()
+        );
+    }
+    if !matches!(options.log_type, LogType::None) {
+        bail!("Can't count lines or files while keeping just hashes of lines");
+    }
+    if options.key.is_command() {
+        bail!("Can't hash keys when a command computes the keys of all of an operand at once");
+    }
+    let (_, line_terminator) = output_info(b"", &options.framing);
+    let mut out = Output { out, line_terminator, written: 0 };
+    write_header(&[], options, line_terminator, &mut out.out)?;
+    let hash = |line: &[u8]| xxh3_128(&options.key.of(line));
+    let mut operands = options.interrupt.cut_short(options.tally.counting(operands));
+    if options.op == OpName::Union {
+        let mut seen: HashSet<u128, FxBuildHasher> = HashSet::default();
+        for next in operands {
+            let mut result = Ok(());
+            next?.for_byte_line_while(|line| {
+                if seen.insert(hash(line)) {
+                    result = out.write(options, line);
+                }
+                result.is_ok()
+            })?;
+            result?;
+        }
+        return out.finish(options);
+    }
+    // The number of operands each line of the first is in, so far
+    let mut files: HashMap<u128, u32, FxBuildHasher> = HashMap::default();
+    if let Some(first) = operands.next() {
+        first?.for_byte_line(|line| {
+            files.insert(hash(line), 1);
+        })?;
+    }
+    let mut all_files = 1_u32;
+    for next in operands {
+        all_files += 1;
+        next?.for_byte_line(|line| match options.op {
+            OpName::Diff => {
+                files.remove(&hash(line));
+            }
+            _ => {
+                if let Some(count) = files.get_mut(&hash(line)) {
+                    if *count == all_files - 1 {
+                        *count = all_files;
+                    }
+                }
+            }
+        })?;
+    }
+    let wanted = if options.op == OpName::Diff { 1 } else { all_files };
+    let mut result = Ok(());
+    first_again()?.for_byte_line_while(|line| {
+        if files.remove(&hash(line)) == Some(wanted) {
+            result = out.write(options, line);
+        }
+        result.is_ok()
+    })?;
+    result?;
+    out.finish(options)
+}
+
+/// Where the lines go, and how many have gone
+struct Output<'t, W: Write> {
+    out: W,
+    line_terminator: &'t [u8],
+    written: u64,
+}
+
+impl<W: Write> Output<'_, W> {
+    fn write(&mut self, options: &Options, line: &[u8]) -> Result<()> {
+        options.write_line(line, &mut self.out)?;
+        self.out.write_all(self.line_terminator)?;
+        self.written += 1;
+        Ok(())
+    }
+
+    fn finish(mut self, options: &Options) -> Result<()> {
+        options.tally.wrote(self.written);
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hashed(op: OpName, operands: &[&str]) -> String {
+        let first = operands[0].as_bytes();
+        let operands = operands.iter().map(|operand| Ok(operand.as_bytes()));
+        let mut out = Vec::new();
+        calculate(&Options::new(op), operands, || Ok(first), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn hashed_operations_match_the_usual_ones() {
+        let operands = ["a\nb\nc\nb\nd\n", "c\na\ne\n", "a\nc\nf\n"];
+        assert_eq!(hashed(OpName::Union, &operands), "a\nb\nc\nd\ne\nf\n");
+        assert_eq!(hashed(OpName::Intersect, &operands), "a\nc\n");
+        assert_eq!(hashed(OpName::Diff, &operands), "b\nd\n");
+        assert_eq!(hashed(OpName::Diff, &operands[..1]), "a\nb\nc\nd\n");
+        let single = calculate(
+            &Options::new(OpName::Single),
+            [Ok(&b"a"[..])].into_iter(),
+            || Ok(&b"a"[..]),
+            Vec::new(),
+        );
+        assert!(single.is_err());
+    }
+}
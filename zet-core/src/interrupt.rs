@@ -0,0 +1,42 @@
+//! Support for `--partial-on-interrupt`. Normally Ctrl-C kills `zet` at once,
+//! and everything read so far is lost. With `--partial-on-interrupt` we
+//! instead note that an interrupt was requested, stop reading input, and print
+//! the result calculated from the input we've read.
+//!
+//! Noticing the interrupt is the embedding program's business (the `zet`
+//! command installs a Ctrl-C handler with the `ctrlc` crate). It hands the
+//! engine an `Interrupt` in `Options::interrupt`, keeps a clone for itself,
+//! and calls `request` on its clone; the engine checks `requested` between
+//! operands and between the records of the first operand.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// The conventional exit code for a process killed by `SIGINT` (128 + 2)
+pub const EXIT_CODE: i32 = 130;
+
+/// A request to stop reading input, shared by all its clones. The default is
+/// one that nothing has requested yet.
+#[derive(Clone, Debug, Default)]
+pub struct Interrupt(Arc<AtomicBool>);
+
+impl Interrupt {
+    /// Ask the engine to stop reading input. Returns `true` if an interrupt
+    /// had already been requested.
+    #[must_use = "a second request may mean the user wants out right away"]
+    pub fn request(&self) -> bool {
+        self.0.swap(true, Ordering::Relaxed)
+    }
+
+    /// Has anyone asked us to stop reading input?
+    #[must_use]
+    pub fn requested(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Wrap an iterator over operands so that it stops as soon as we're
+    /// interrupted — before opening the next operand.
+    pub(crate) fn cut_short<I: Iterator>(&self, mut operands: I) -> impl Iterator<Item = I::Item> {
+        let interrupt = self.clone();
+        std::iter::from_fn(move || if interrupt.requested() { None } else { operands.next() })
+    }
+}
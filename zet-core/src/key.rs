@@ -0,0 +1,742 @@
+//! Provides the `Key` structure, which maps each input line to the key used to
+//! compare it with other lines. By default the key is the whole line. With
+//! `--key`, the key is made up of selected fields of the line, split either on
+//! runs of whitespace or on the (possibly multi-byte) `--field-delimiter`, or
+//! with `--csv`, made up of CSV fields, which may be quoted, and may be named
+//! by the header. Each operand has its own header (its first record), so its
+//! columns may come in any order; the header itself isn't part of the set.
+//!
+//! With `--key-cmd`, an external command computes the keys. The engine doesn't
+//! run commands itself: it hands all the lines of each operand at once to a
+//! `KeyCommand` supplied by the caller, which returns their keys. (The `zet`
+//! command's `KeyCommand` runs a shell command, once per operand rather than
+//! once per line, which keeps that tolerable.) A `KeyExtractor`, like the
+//! `zet` command's `--key-regex`, instead computes the key of each line on its
+//! own. `LinePart` is a `KeyExtractor` for `uniq`'s way of choosing the part of
+//! a line to compare, which the `zet` command's `--skip-fields`,
+//! `--skip-chars`, and `--check-chars` follow.
+//!
+//! The key (whether the whole line, selected fields, or a command's output) may
+//! then be edited by further `KeyExtractor`s, like the `zet` command's
+//! `--ignore-pattern`, which removes the matches of a regular expression. And
+//! then it may be normalized — trimmed, with whitespace collapsed, lowercased — by the
+//! `Normalizer`s given. The `--normalize` flag applies all three, `--trim` just
+//! trims, and `--ignore-case` just lowercases — or with `--ignore-case=unicode`, applies
+//! Unicode's full case folding, under which `STRASSE` and `straße` are equal.
+//! With `--numeric`, keys that are decimal numbers are written in a canonical
+//! form, so that `1`, `01`, and `1.0` are equal.
+//!
+//! When keys aren't whole lines, two different lines can have the same key.
+//! `zet` treats such lines as the same element of the set, and prints the first
+//! line seen with that key.
+use anyhow::{bail, Result};
+use bstr::ByteSlice;
+use std::borrow::Cow;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// How to derive a comparison key from a line
+#[derive(Clone, Debug, Default)]
+pub struct Key {
+    fields: Option<Fields>,
+    command: Option<Arc<dyn KeyCommand>>,
+    extractor: Option<Arc<dyn KeyExtractor>>,
+    edits: Vec<Arc<dyn KeyExtractor>>,
+    normalizers: Vec<Normalizer>,
+}
+
+/// Something that computes the keys of many lines at once, like the external
+/// command given by `--key-cmd`.
+pub trait KeyCommand: Debug + Send + Sync {
+    /// Returns the key of each of `lines`, in order.
+    fn keys_of(&self, lines: &[&[u8]]) -> Result<Vec<Vec<u8>>>;
+}
+
+/// Something that computes the key of each line on its own, like the regular
+/// expression given by `--key-regex`.
+pub trait KeyExtractor: Debug + Send + Sync {
+    /// Returns the key of `line`, borrowing from `line` when possible.
+    fn key_of<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]>;
+}
+
+/// A transformation applied to a key, so that lines differing only in ways
+/// we don't care about compare equal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Normalizer {
+    /// Remove leading and trailing whitespace
+    Trim,
+    /// Replace each run of whitespace by a single space
+    CollapseWhitespace,
+    /// Change ASCII uppercase letters to lowercase
+    Lowercase,
+    /// Apply Unicode's full case folding if the key is valid UTF-8 (which may
+    /// change its length, as from `ß` to `ss`), and otherwise act like
+    /// `Lowercase`
+    FoldCase,
+    /// Write a key that's a decimal number (ignoring surrounding whitespace)
+    /// in a canonical form: without a `+` sign, leading zeros, trailing zeros
+    /// after the decimal point, or a decimal point with nothing after it. So
+    /// `+01.50` becomes `1.5`, and `-0.0` becomes `0`. Other keys are left as
+    /// they are.
+    Numeric,
+}
+
+impl Normalizer {
+    /// The normalizers applied by `--normalize`
+    pub const ALL: [Normalizer; 3] =
+        [Normalizer::Trim, Normalizer::CollapseWhitespace, Normalizer::Lowercase];
+
+    /// Apply the normalization to `key`, allocating only if `key` changes
+    /// (other than by being trimmed).
+    fn apply(self, key: Cow<'_, [u8]>) -> Cow<'_, [u8]> {
+        match self {
+            Normalizer::Trim => match key {
+                Cow::Borrowed(k) => Cow::Borrowed(trim_whitespace(k)),
+                Cow::Owned(k) => Cow::Owned(trim_whitespace(&k).to_vec()),
+            },
+            Normalizer::CollapseWhitespace => {
+                let needs_collapsing = key.windows(2).any(|pair| pair == b"  ")
+                    || key.iter().any(|&b| b != b' ' && b.is_ascii_whitespace());
+                if !needs_collapsing {
+                    return key;
+                }
+                let mut collapsed = Vec::with_capacity(key.len());
+                for &b in key.iter() {
+                    if !b.is_ascii_whitespace() {
+                        collapsed.push(b);
+                    } else if collapsed.last() != Some(&b' ') {
+                        collapsed.push(b' ');
+                    }
+                }
+                Cow::Owned(collapsed)
+            }
+            Normalizer::Lowercase => {
+                if key.iter().any(u8::is_ascii_uppercase) {
+                    Cow::Owned(key.to_ascii_lowercase())
+                } else {
+                    key
+                }
+            }
+            Normalizer::FoldCase => match std::str::from_utf8(&key) {
+                Ok(text) if !text.is_ascii() => {
+                    let folded = caseless::default_case_fold_str(text);
+                    if folded == text {
+                        key
+                    } else {
+                        Cow::Owned(folded.into_bytes())
+                    }
+                }
+                _ => Normalizer::Lowercase.apply(key),
+            },
+            Normalizer::Numeric => match canonical_number(trim_whitespace(&key)) {
+                Some(number) if number != *key => Cow::Owned(number),
+                _ => key,
+            },
+        }
+    }
+}
+
+/// The canonical form of `text`, if it's a decimal number (as for
+/// `Normalizer::Numeric`). We work on the digits rather than parsing a float, so
+/// that long numbers, like IDs, stay exact.
+fn canonical_number(text: &[u8]) -> Option<Vec<u8>> {
+    let (negative, unsigned) = match text.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        Some((b'+', rest)) => (false, rest),
+        _ => (false, text),
+    };
+    let (whole, fraction) = match unsigned.iter().position(|&b| b == b'.') {
+        Some(n) => (&unsigned[..n], &unsigned[n + 1..]),
+        None => (unsigned, &b""[..]),
+    };
+    let digits = |part: &[u8]| part.iter().all(u8::is_ascii_digit);
+    if (whole.is_empty() && fraction.is_empty()) || !digits(whole) || !digits(fraction) {
+        return None;
+    }
+    let whole = &whole[whole.iter().position(|&b| b != b'0').unwrap_or(whole.len())..];
+    let fraction = &fraction[..fraction.iter().rposition(|&b| b != b'0').map_or(0, |n| n + 1)];
+    let mut number = Vec::with_capacity(text.len());
+    if negative && !(whole.is_empty() && fraction.is_empty()) {
+        number.push(b'-');
+    }
+    number.extend_from_slice(if whole.is_empty() { b"0" } else { whole });
+    if !fraction.is_empty() {
+        number.push(b'.');
+        number.extend_from_slice(fraction);
+    }
+    Some(number)
+}
+
+/// `key` without leading or trailing ASCII whitespace
+fn trim_whitespace(key: &[u8]) -> &[u8] {
+    let start = key.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(key.len());
+    let end = key.iter().rposition(|b| !b.is_ascii_whitespace()).map_or(start, |n| n + 1);
+    &key[start..end]
+}
+
+/// The fields to compare: `numbers` holds zero-based field numbers, in the
+/// order they should appear in the key. If `delimiter` is `None`, fields are
+/// separated by runs of ASCII whitespace (ignoring leading and trailing
+/// whitespace); otherwise by each occurrence of `delimiter`. With `csv` set,
+/// they're the fields of a CSV record instead; see `csv_fields`. If some of
+/// the CSV columns are named, `named` holds them all, to be looked up in each
+/// operand's header, and `numbers` are those of the operand being read.
+#[derive(Clone, Debug)]
+struct Fields {
+    numbers: Vec<usize>,
+    delimiter: Option<Vec<u8>>,
+    csv: bool,
+    named: Option<Vec<Column>>,
+}
+
+/// A column of CSV records, given by its number (from 1) or by its name in the
+/// header
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Column {
+    Number(usize),
+    Name(String),
+}
+
+/// The part of a line `uniq -f N -s M -w W` compares: what's left after
+/// skipping `skip_fields` fields (each a run of blanks and the non-blanks after
+/// it) and then `skip_bytes` bytes, cut to at most `check_bytes` bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LinePart {
+    pub skip_fields: usize,
+    pub skip_bytes: usize,
+    pub check_bytes: Option<usize>,
+}
+
+impl KeyExtractor for LinePart {
+    fn key_of<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        let is_blank = |b: &u8| *b == b' ' || *b == b'\t';
+        let mut rest = line;
+        for _ in 0..self.skip_fields {
+            let start = rest.iter().position(|b| !is_blank(b)).unwrap_or(rest.len());
+            let end = rest[start..].iter().position(is_blank).map_or(rest.len(), |n| start + n);
+            rest = &rest[end..];
+        }
+        rest = &rest[self.skip_bytes.min(rest.len())..];
+        if let Some(width) = self.check_bytes {
+            rest = &rest[..width.min(rest.len())];
+        }
+        Cow::Borrowed(rest)
+    }
+}
+
+impl Key {
+    /// Compare whole lines — the default
+    #[must_use]
+    pub fn whole_line() -> Self {
+        Key::default()
+    }
+
+    /// Compare the fields numbered (from 1) in `numbers`, separated by
+    /// `delimiter`, or by runs of whitespace if `delimiter` is `None`.
+    #[must_use]
+    pub fn fields(numbers: &[usize], delimiter: Option<Vec<u8>>) -> Self {
+        let numbers = numbers.iter().map(|n| n.saturating_sub(1)).collect();
+        let fields = Fields { numbers, delimiter, csv: false, named: None };
+        Key { fields: Some(fields), ..Key::default() }
+    }
+
+    /// Compare the CSV `columns` of each record, keeping any normalizers. If
+    /// any of them are named, the first record of each operand is its header,
+    /// which says which column each name refers to in that operand; the
+    /// `ZetSet` looks them up with `for_header`, and leaves the header out.
+    #[must_use]
+    pub fn csv_columns(mut self, columns: &[Column]) -> Self {
+        let named = columns.iter().any(|column| matches!(column, Column::Name(_)));
+        let numbers = columns
+            .iter()
+            .filter_map(|column| match column {
+                Column::Number(n) => Some(n.saturating_sub(1)),
+                Column::Name(_) => None,
+            })
+            .collect();
+        let named = named.then(|| columns.to_vec());
+        self.fields = Some(Fields { numbers, delimiter: None, csv: true, named });
+        self
+    }
+
+    /// Does the key name CSV columns, so that each operand starts with a
+    /// header to look them up in?
+    #[must_use]
+    pub(crate) fn names_columns(&self) -> bool {
+        self.fields.as_ref().is_some_and(|fields| fields.named.is_some())
+    }
+
+    /// The key for the records after `header`, an operand's first record:
+    /// with our named columns looked up in it
+    pub(crate) fn for_header(&self, header: &[u8]) -> Result<Key> {
+        let Some(Fields { named: Some(columns), .. }) = &self.fields else {
+            return Ok(self.clone());
+        };
+        let header = header.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(header);
+        let names = csv_fields(header.strip_suffix(b"\r").unwrap_or(header));
+        let mut numbers = Vec::with_capacity(columns.len());
+        for column in columns {
+            numbers.push(match column {
+                Column::Number(n) => n.saturating_sub(1),
+                Column::Name(name) => match names.iter().position(|f| f[..] == *name.as_bytes()) {
+                    Some(n) => n,
+                    None => bail!("The CSV header has no column named `{name}`"),
+                },
+            });
+        }
+        let mut key = self.clone();
+        if let Some(fields) = &mut key.fields {
+            fields.numbers = numbers;
+        }
+        Ok(key)
+    }
+
+    /// Use `command` to compute keys
+    #[must_use]
+    pub fn command(command: impl KeyCommand + 'static) -> Self {
+        Key { command: Some(Arc::new(command)), ..Key::default() }
+    }
+
+    /// Use `extractor` to compute keys
+    #[must_use]
+    pub fn extracted_by(extractor: impl KeyExtractor + 'static) -> Self {
+        Key { extractor: Some(Arc::new(extractor)), ..Key::default() }
+    }
+
+    /// Apply `edit` to each key, after any edits given before, and before
+    /// normalizing it
+    #[must_use]
+    pub fn edited_by(mut self, edit: impl KeyExtractor + 'static) -> Self {
+        self.edits.push(Arc::new(edit));
+        self
+    }
+
+    /// Apply `normalizers`, in order, to each key
+    #[must_use]
+    pub fn normalized_by(mut self, normalizers: &[Normalizer]) -> Self {
+        for &normalizer in normalizers {
+            if !self.normalizers.contains(&normalizer) {
+                self.normalizers.push(normalizer);
+            }
+        }
+        self
+    }
+
+    /// Is the key of a line the line itself?
+    #[must_use]
+    pub(crate) fn is_whole_line(&self) -> bool {
+        self.fields.is_none()
+            && self.command.is_none()
+            && self.extractor.is_none()
+            && self.edits.is_empty()
+            && self.normalizers.is_empty()
+    }
+
+    /// Do keys come from an external command? If so, use `of_each` rather than
+    /// `of`.
+    #[must_use]
+    pub(crate) fn is_command(&self) -> bool {
+        self.command.is_some()
+    }
+
+    /// Returns the keys of each of `lines`, as computed by our `KeyCommand`.
+    pub(crate) fn of_each<L: AsRef<[u8]>>(&self, lines: &[L]) -> Result<Vec<Vec<u8>>> {
+        match &self.command {
+            None => Ok(lines.iter().map(|line| self.of(line.as_ref()).into_owned()).collect()),
+            Some(_) if lines.is_empty() => Ok(Vec::new()),
+            Some(command) => {
+                let lines: Vec<&[u8]> = lines.iter().map(AsRef::as_ref).collect();
+                let keys = command.keys_of(&lines)?;
+                if keys.len() != lines.len() {
+                    bail!("Got {} keys for {} lines from {command:?}", keys.len(), lines.len());
+                }
+                Ok(keys
+                    .into_iter()
+                    .map(|key| self.normalize(Cow::Owned(key)).into_owned())
+                    .collect())
+            }
+        }
+    }
+
+    /// Returns the key of `line`, borrowing from `line` when possible.
+    pub(crate) fn of<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        let key = match (&self.fields, &self.extractor) {
+            (Some(fields), _) => fields.select(line, fields.joiner()),
+            (None, Some(extractor)) => extractor.key_of(line),
+            (None, None) => Cow::Borrowed(line),
+        };
+        self.normalize(key)
+    }
+
+    /// Returns the key's fields of `line`, joined by `joiner` and not
+    /// normalized, for `--output-fields`. If the key isn't made of fields,
+    /// that's the whole line.
+    #[must_use]
+    pub fn fields_of<'a>(&self, line: &'a [u8], joiner: &[u8]) -> Cow<'a, [u8]> {
+        match &self.fields {
+            None => Cow::Borrowed(line),
+            Some(fields) => fields.select(line, joiner),
+        }
+    }
+
+    /// Apply our edits, then our normalizers, to `key`
+    fn normalize<'a>(&self, key: Cow<'a, [u8]>) -> Cow<'a, [u8]> {
+        let key = self.edits.iter().fold(key, |key, edit| match key {
+            Cow::Borrowed(key) => edit.key_of(key),
+            Cow::Owned(key) => Cow::Owned(edit.key_of(&key).into_owned()),
+        });
+        self.normalizers.iter().fold(key, |key, normalizer| normalizer.apply(key))
+    }
+}
+
+impl Fields {
+    /// The delimiter (or a space, when splitting on whitespace), which joins
+    /// several fields in a key, so that keys built from different field values
+    /// stay different. Unquoted CSV fields may hold any character, so we join
+    /// them with the ASCII unit separator, which they're unlikely to.
+    fn joiner(&self) -> &[u8] {
+        match &self.delimiter {
+            _ if self.csv => b"\x1f",
+            Some(delimiter) => delimiter,
+            None => b" ",
+        }
+    }
+
+    /// A single field is borrowed from `line` (unless it's a quoted CSV field).
+    /// Several fields are joined with `joiner`.
+    fn select<'a>(&self, line: &'a [u8], joiner: &[u8]) -> Cow<'a, [u8]> {
+        let mut all: Vec<Cow<'a, [u8]>> = match &self.delimiter {
+            _ if self.csv => csv_fields(line),
+            None => line
+                .split(u8::is_ascii_whitespace)
+                .filter(|f| !f.is_empty())
+                .map(Cow::Borrowed)
+                .collect(),
+            Some(delimiter) => line.split_str(delimiter).map(Cow::Borrowed).collect(),
+        };
+        if let [n] = self.numbers[..] {
+            return if n < all.len() { all.swap_remove(n) } else { Cow::Borrowed(b"") };
+        }
+        let mut key = Vec::with_capacity(line.len());
+        for (i, &n) in self.numbers.iter().enumerate() {
+            if i > 0 {
+                key.extend_from_slice(joiner);
+            }
+            key.extend_from_slice(all.get(n).map_or(b"", |field| field));
+        }
+        Cow::Owned(key)
+    }
+}
+
+/// The fields of the CSV `record`, split on commas outside double quotes. A
+/// field starting with a quote loses its quotes, and the doubled quotes inside
+/// it become single ones; other fields are borrowed as they are.
+fn csv_fields(record: &[u8]) -> Vec<Cow<'_, [u8]>> {
+    let mut fields = Vec::new();
+    let mut rest = record;
+    loop {
+        let end = if rest.first() == Some(&b'"') {
+            let mut field = Vec::new();
+            let mut n = 1;
+            while n < rest.len() {
+                match rest[n] {
+                    b'"' if rest.get(n + 1) == Some(&b'"') => {
+                        field.push(b'"');
+                        n += 2;
+                    }
+                    b'"' => break,
+                    b => {
+                        field.push(b);
+                        n += 1;
+                    }
+                }
+            }
+            // Anything between the closing quote and the comma is kept too
+            let end = rest[n..].find_byte(b',').map_or(rest.len(), |comma| n + comma);
+            field.extend_from_slice(rest.get(n + 1..end).unwrap_or_default());
+            fields.push(Cow::Owned(field));
+            end
+        } else {
+            let end = rest.find_byte(b',').unwrap_or(rest.len());
+            fields.push(Cow::Borrowed(&rest[..end]));
+            end
+        };
+        if end == rest.len() {
+            return fields;
+        }
+        rest = &rest[end + 1..];
+    }
+}
+
+/// Parse a delimiter given on the command line. Besides ordinary characters,
+/// we recognize the escapes `\t`, `\n`, `\r`, `\0`, `\\`, and `\xHH` (a byte in
+/// hexadecimal), so that `'\t'` and `'\x1f'` can be given without shell
+/// gymnastics.
+pub fn parse_delimiter(text: &str) -> Result<Vec<u8>, String> {
+    let mut result = Vec::new();
+    let mut bytes = text.bytes();
+    while let Some(b) = bytes.next() {
+        if b != b'\\' {
+            result.push(b);
+            continue;
+        }
+        let escaped = match bytes.next() {
+            Some(b't') => b'\t',
+            Some(b'n') => b'\n',
+            Some(b'r') => b'\r',
+            Some(b'0') => b'\0',
+            Some(b'\\') => b'\\',
+            Some(b'x') => {
+                let hex = [bytes.next(), bytes.next()];
+                let hex = match hex {
+                    [Some(hi), Some(lo)] => std::str::from_utf8(&[hi, lo])
+                        .ok()
+                        .and_then(|h| u8::from_str_radix(h, 16).ok()),
+                    _ => None,
+                };
+                hex.ok_or_else(|| format!("'\\x' must be followed by two hex digits in '{text}'"))?
+            }
+            Some(other) => {
+                return Err(format!("unknown escape '\\{}' in '{text}'", char::from(other)))
+            }
+            None => return Err(format!("'{text}' ends with a lone backslash")),
+        };
+        result.push(escaped);
+    }
+    if result.is_empty() {
+        return Err("the delimiter can't be empty".to_string());
+    }
+    Ok(result)
+}
+
+/// Parse a comma-separated list of field numbers and ranges of them, like
+/// `2,4` or `2-4,6` (which means `2,3,4,6`), as `cut -f` takes. Field numbers
+/// start at 1.
+pub fn parse_field_list(text: &str) -> Result<Vec<usize>, String> {
+    let number = |n: &str| match n.trim().parse::<usize>() {
+        Ok(0) => Err("field numbers start at 1".to_string()),
+        Ok(n) => Ok(n),
+        Err(_) => Err(format!("'{n}' is not a field number")),
+    };
+    let mut fields = Vec::new();
+    for item in text.split(',') {
+        match item.split_once('-') {
+            None => fields.push(number(item)?),
+            Some((first, last)) => {
+                let (first, last) = (number(first)?, number(last)?);
+                if first > last {
+                    return Err(format!("the range '{item}' runs backwards"));
+                }
+                fields.extend(first..=last);
+            }
+        }
+    }
+    Ok(fields)
+}
+
+/// Parse a comma-separated list of CSV columns: field numbers, ranges of them
+/// (as for `parse_field_list`), and names of columns in the header. A name is
+/// anything that isn't made of just digits and `-`.
+pub fn parse_column_list(text: &str) -> Result<Vec<Column>, String> {
+    let mut columns = Vec::new();
+    for item in text.split(',') {
+        let item = item.trim();
+        if item.is_empty() {
+            return Err("a column can't be empty".to_string());
+        } else if item.bytes().all(|b| b.is_ascii_digit() || b == b'-') {
+            columns.extend(parse_field_list(item)?.into_iter().map(Column::Number));
+        } else {
+            columns.push(Column::Name(item.to_string()));
+        }
+    }
+    Ok(columns)
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key_of(key: &Key, line: &str) -> String {
+        String::from_utf8(key.of(line.as_bytes()).into_owned()).unwrap()
+    }
+
+    #[test]
+    fn whitespace_separated_fields_ignore_leading_and_repeated_blanks() {
+        let key = Key::fields(&[2], None);
+        assert_eq!(key_of(&key, "  a \t b  c"), "b");
+        assert_eq!(key_of(&key, "a"), "");
+    }
+
+    #[test]
+    fn several_fields_are_joined_in_the_order_given() {
+        assert_eq!(key_of(&Key::fields(&[3, 1], None), "a b c"), "c a");
+        assert_eq!(key_of(&Key::fields(&[3, 1], Some(b",".to_vec())), "a,b,c"), "c,a");
+    }
+
+    #[test]
+    fn output_fields_are_rejoined_but_not_normalized() {
+        let key = Key::fields(&[3, 1], Some(b",".to_vec())).normalized_by(&Normalizer::ALL);
+        assert_eq!(key.fields_of(b"A,b,C", b"\t"), &b"C\tA"[..]);
+        assert_eq!(Key::whole_line().fields_of(b"a,b", b"\t"), &b"a,b"[..]);
+    }
+
+    #[test]
+    fn multi_byte_delimiters_split_on_the_whole_delimiter() {
+        let key = Key::fields(&[2], Some(b"||".to_vec()));
+        assert_eq!(key_of(&key, "a|b||c|d||e"), "c|d");
+        assert_eq!(key_of(&key, "||x"), "x");
+    }
+
+    /// A `KeyCommand` that uppercases each line, and gets the number of keys
+    /// wrong for more than two lines
+    #[derive(Debug)]
+    struct Upper;
+    impl KeyCommand for Upper {
+        fn keys_of(&self, lines: &[&[u8]]) -> Result<Vec<Vec<u8>>> {
+            Ok(lines.iter().take(2).map(|line| line.to_ascii_uppercase()).collect())
+        }
+    }
+
+    #[test]
+    fn a_key_command_gets_all_the_lines_at_once() {
+        let key = Key::command(Upper).normalized_by(&[Normalizer::Trim]);
+        let keys = key.of_each(&[&b"abc "[..], b"Def"]).unwrap();
+        assert_eq!(keys, vec![b"ABC".to_vec(), b"DEF".to_vec()]);
+        assert!(key.of_each(&[b"a", b"b", b"c"]).is_err());
+    }
+
+    #[derive(Debug)]
+    struct BeforeColon;
+
+    impl KeyExtractor for BeforeColon {
+        fn key_of<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+            Cow::Borrowed(line.split_str(":").next().unwrap_or(line))
+        }
+    }
+
+    #[test]
+    fn a_key_extractor_gets_one_line_at_a_time() {
+        let key = Key::extracted_by(BeforeColon).normalized_by(&[Normalizer::Lowercase]);
+        assert!(!key.is_whole_line() && !key.is_command());
+        assert_eq!(key_of(&key, "Host:example"), "host");
+        assert_eq!(key_of(&key, "plain"), "plain");
+    }
+
+    #[test]
+    fn edits_come_after_extraction_and_before_normalizing() {
+        let key = Key::fields(&[2], None).edited_by(BeforeColon).normalized_by(&Normalizer::ALL);
+        assert_eq!(key_of(&key, "a B:c d"), "b");
+        let key = Key::command(Upper).edited_by(BeforeColon).edited_by(BeforeColon);
+        assert_eq!(key.of_each(&["x:y"]).unwrap(), vec![b"X".to_vec()]);
+    }
+
+    #[test]
+    fn line_parts_skip_fields_then_bytes_like_uniq() {
+        let part = |skip_fields, skip_bytes, check_bytes| {
+            Key::extracted_by(LinePart { skip_fields, skip_bytes, check_bytes })
+        };
+        assert_eq!(key_of(&part(1, 0, None), "  one \ttwo three"), " \ttwo three");
+        assert_eq!(key_of(&part(2, 2, None), "a b c"), "");
+        assert_eq!(key_of(&part(1, 2, Some(3)), "a bcdefg"), "cde");
+        assert_eq!(key_of(&part(0, 0, Some(0)), "abc"), "");
+        assert_eq!(key_of(&part(5, 0, None), "a b"), "");
+    }
+
+    #[test]
+    fn numeric_keys_are_written_canonically() {
+        let key = Key::whole_line().normalized_by(&[Normalizer::Numeric]);
+        for (line, expected) in [
+            ("1", "1"),
+            ("01", "1"),
+            ("1.0", "1"),
+            (" +001.500 ", "1.5"),
+            (".5", "0.5"),
+            ("5.", "5"),
+            ("-0.00", "0"),
+            ("-007", "-7"),
+            ("123456789012345678901234567890", "123456789012345678901234567890"),
+            ("1e3", "1e3"),
+            ("-", "-"),
+            (".", "."),
+            ("1.2.3", "1.2.3"),
+        ] {
+            assert_eq!(key_of(&key, line), expected);
+        }
+        assert!(matches!(key.of(b"42"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn normalizers_are_applied_in_order() {
+        let key = Key::whole_line().normalized_by(&Normalizer::ALL);
+        assert_eq!(key_of(&key, " \t Hello,   \tWORLD \r"), "hello, world");
+        assert!(matches!(key.of(b"already normal"), Cow::Borrowed(_)));
+        let key = Key::fields(&[2], Some(b",".to_vec())).normalized_by(&[Normalizer::Trim]);
+        assert_eq!(key_of(&key, "a,  B c ,d"), "B c");
+    }
+
+    #[test]
+    fn case_folding_covers_non_ascii_letters() {
+        let key = Key::whole_line().normalized_by(&[Normalizer::FoldCase]);
+        assert_eq!(key_of(&key, "Ärger ÜBER Straße"), "ärger über strasse");
+        assert_eq!(key_of(&key, "STRASSE"), key_of(&key, "straße"));
+        assert_eq!(key_of(&key, "MiXeD Case"), "mixed case");
+        assert!(matches!(key.of("schön".as_bytes()), Cow::Borrowed(_)));
+        assert_eq!(key.of(b"\xffAB").as_ref(), b"\xffab");
+    }
+
+    #[test]
+    fn csv_fields_may_be_quoted() {
+        let fields = csv_fields(
+            br#"a,"b,""c""
+d","",e"#,
+        );
+        assert_eq!(fields, [&b"a"[..], b"b,\"c\"\nd", b"", b"e"]);
+        assert_eq!(csv_fields(b""), [&b""[..]]);
+        assert_eq!(csv_fields(b"\"open"), [&b"open"[..]]);
+    }
+
+    #[test]
+    fn csv_columns_may_be_named_by_the_header() {
+        let header = b"\xEF\xBB\xBFid,\"full name\",email\r";
+        let columns = parse_column_list("email,id").unwrap();
+        let key = Key::whole_line().csv_columns(&columns);
+        assert!(key.names_columns());
+        let key = key.for_header(header).unwrap();
+        assert_eq!(key.of(b"2,\"Roe, R\",r@x").as_ref(), b"r@x\x1f2");
+        let key = key.for_header(b"email,name,id").unwrap();
+        assert_eq!(key.of(b"r@x,\"Roe, R\",2").as_ref(), b"r@x\x1f2");
+        let columns = parse_column_list("full name").unwrap();
+        let key = Key::whole_line().csv_columns(&columns).for_header(header).unwrap();
+        assert_eq!(key_of(&key, "1,\"Doe, J\",j@x"), "Doe, J");
+        let columns = parse_column_list("phone").unwrap();
+        assert!(Key::whole_line().csv_columns(&columns).for_header(header).is_err());
+        let columns = parse_column_list("2").unwrap();
+        assert!(!Key::whole_line().csv_columns(&columns).names_columns());
+        assert_eq!(parse_column_list("2-3,id").unwrap().len(), 3);
+        assert!(parse_column_list("0").is_err());
+        assert!(parse_column_list("a,,b").is_err());
+    }
+
+    #[test]
+    fn delimiters_may_be_escaped() {
+        assert_eq!(parse_delimiter("||").unwrap(), b"||");
+        assert_eq!(parse_delimiter("\\t").unwrap(), b"\t");
+        assert_eq!(parse_delimiter("\\x1f").unwrap(), b"\x1f");
+        assert_eq!(parse_delimiter("a\\\\b").unwrap(), b"a\\b");
+        assert!(parse_delimiter("").is_err());
+        assert!(parse_delimiter("\\x1").is_err());
+        assert!(parse_delimiter("\\q").is_err());
+    }
+
+    #[test]
+    fn field_lists_are_one_based() {
+        assert_eq!(parse_field_list("2,4").unwrap(), vec![2, 4]);
+        assert_eq!(parse_field_list("2-4,1").unwrap(), vec![2, 3, 4, 1]);
+        assert!(parse_field_list("4-2").is_err());
+        assert!(parse_field_list("0-2").is_err());
+        assert!(parse_field_list("2-").is_err());
+        assert!(parse_field_list("0").is_err());
+        assert!(parse_field_list("a").is_err());
+    }
+}
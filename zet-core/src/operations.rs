@@ -0,0 +1,2345 @@
+//! Houses the `calculate` function
+//!
+use anyhow::{bail, Result};
+use std::cmp::Reverse;
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+
+use crate::interrupt::Interrupt;
+use crate::key::Key;
+use crate::sample::Sample;
+use crate::set::{Framing, Keep, LaterOperand, Order, ZetSet};
+use crate::summary::{self, Tally};
+use OpName::{
+    Diff, Intersect, Majority, MergeCounts, Multiple, MultipleByFile, Single, SingleByFile, Stats,
+    Top, Union,
+};
+
+/// Set operation to perform
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum OpName {
+    /// Print the lines present in every file
+    Intersect,
+    /// Print the lines present in any file
+    Union,
+    /// Print the lines present in the first file but no other
+    Diff,
+    /// Print the lines present exactly once in the entire input
+    Single,
+    /// Print the lines present in exactly one file
+    SingleByFile,
+    /// Print the lines present more than once in the entire input
+    Multiple,
+    /// Print the lines present in two or more files
+    MultipleByFile,
+    /// Print the lines present in more than half of the files
+    Majority,
+    /// Print lines with the number of times they occur, most frequent first
+    Top,
+    /// Print statistics of how often the input's lines occur
+    Stats,
+    /// Merge counted input (as from `--count`), printing each line with the
+    /// sum of its counts
+    MergeCounts,
+}
+
+impl OpName {
+    /// Every operation, in the order the help message lists them
+    pub const ALL: [OpName; 11] = [
+        Intersect,
+        Union,
+        Diff,
+        Single,
+        SingleByFile,
+        Multiple,
+        MultipleByFile,
+        Majority,
+        Top,
+        Stats,
+        MergeCounts,
+    ];
+
+    /// The operation's name, as accepted by `FromStr`. `SingleByFile` and
+    /// `MultipleByFile` (`single --files` and `multiple --files` on the command
+    /// line) are `single-by-file` and `multiple-by-file`.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Intersect => "intersect",
+            Union => "union",
+            Diff => "diff",
+            Single => "single",
+            SingleByFile => "single-by-file",
+            Multiple => "multiple",
+            MultipleByFile => "multiple-by-file",
+            Majority => "majority",
+            Top => "top",
+            Stats => "stats",
+            MergeCounts => "merge-counts",
+        }
+    }
+}
+
+impl fmt::Display for OpName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for OpName {
+    type Err = UnknownOpName;
+    /// Parse an operation name (ignoring ASCII case)
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        OpName::ALL
+            .into_iter()
+            .find(|op| op.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| UnknownOpName(name.to_string()))
+    }
+}
+
+/// The error returned when parsing a string that doesn't name an operation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownOpName(pub String);
+
+impl fmt::Display for UnknownOpName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<_> = OpName::ALL.iter().map(|op| op.name()).collect();
+        write!(f, "Unknown operation \"{}\" (expected one of: {})", self.0, names.join(", "))
+    }
+}
+
+impl std::error::Error for UnknownOpName {}
+
+#[derive(Clone, Copy, Debug)]
+pub enum LogType {
+    Lines,
+    Files,
+    Split,
+    LinesAndFiles,
+    None,
+}
+
+/// The options that control `calculate`: which operation to perform, and how.
+/// It's `#[non_exhaustive]`, so new options won't break library users: create
+/// one with `Options::new(op)` and set the fields that need changing.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Options {
+    /// The set operation to perform
+    pub op: OpName,
+    /// Whether to print a count of lines or files with each line
+    pub log_type: LogType,
+    /// How to derive the comparison key of each line
+    pub key: Key,
+    /// How to divide the input into records
+    pub framing: Framing,
+    /// If memory runs out as the set grows, should we return an error (an
+    /// `InputTooLarge`) rather than aborting? For long-running services.
+    pub fallible_allocation: bool,
+    /// For `OpName::Top`, the number of lines to print (by default, all)
+    pub top: Option<usize>,
+    /// For `OpName::Top`, estimate the most frequent lines using this many
+    /// counters, rather than counting every line exactly
+    pub approx: Option<NonZeroUsize>,
+    /// How to write counts, when we write them
+    pub count_format: CountFormat,
+    /// How to write each output line
+    pub line_format: LineFormat,
+    /// How to lay out the output: as plain text, or as comma- or tab-separated
+    /// values
+    pub output_format: OutputFormat,
+    /// For comma- or tab-separated output, should we start with a row of
+    /// column names?
+    pub header_row: bool,
+    /// For `OpName::Intersect`, print the lines present in at least this many
+    /// files (or this percentage of them), rather than in every file
+    pub in_at_least: Option<Threshold>,
+    /// Print only the lines present in at least this many files
+    pub min_files: Option<u32>,
+    /// Print only the lines present in at most this many files
+    pub max_files: Option<u32>,
+    /// Print only the lines occurring at least this many times in all
+    pub min_count: Option<u32>,
+    /// Print only the lines occurring at most this many times in all
+    pub max_count: Option<u32>,
+    /// Print the lines of the input that the operation would leave out, rather
+    /// than those it selects
+    pub invert: bool,
+    /// With `--where`, list the places each output line occurs, rather than
+    /// just the line
+    pub locations: Option<Locations>,
+    /// With `--grouped`, `SingleByFile` prints the lines of each operand under
+    /// a heading naming it (one of these names of the operands, in order)
+    pub grouped: Option<Vec<String>>,
+    /// With `--count-per-file`, each output line is prefixed by a column for
+    /// each operand (these names of the operands, in order) counting the times
+    /// it occurs there
+    pub count_per_file: Option<Vec<String>>,
+    /// With `--show-files`, each output line is prefixed by a comma-separated
+    /// list of the operands it occurs in (these names of the operands, in order)
+    pub show_files: Option<Vec<String>>,
+    /// With `show_files`, name just the first operand each line occurs in,
+    /// followed by `:`, like `grep -H`
+    pub with_filename: bool,
+    /// Does each input line start with a count, as in `uniq -c` output, saying
+    /// how many occurrences of the rest of the line it stands for?
+    pub counted_input: bool,
+    /// With `--output-fields`, write just the fields of each line that make up
+    /// its key, joined by this delimiter, rather than the whole line
+    pub output_fields: Option<Vec<u8>>,
+    /// With `--count-distinct`, the union prints each key with the number of
+    /// distinct values this key takes in the lines with that key, rather than
+    /// printing lines
+    pub count_distinct: Option<Key>,
+    /// When keys aren't whole lines, which of the lines with the same key to
+    /// print: the first seen, or the last
+    pub keep: Keep,
+    /// Print lines in bytewise order, rather than in order of first appearance
+    pub sort: bool,
+    /// Print lines in order of their first appearance, or of their last
+    pub order: Order,
+    /// With `--sort-by-count`, print lines in order of their count (lines
+    /// with the same count staying in their usual order)
+    pub count_order: Option<CountOrder>,
+    /// With `--head N`, print at most `N` lines — the first `N` in the order
+    /// they're printed
+    pub head: Option<usize>,
+    /// With `--sample N`, print a random sample of `N` of the lines selected
+    pub sample: Option<Sample>,
+    /// With `--count-after`, print counts after each line rather than before
+    pub count_after: bool,
+    /// With `--count-align`, how plain output pads its counts
+    pub count_align: CountAlign,
+    /// With `--shuffle`, print lines in a random order, shuffled with this
+    /// seed
+    pub shuffle: Option<u64>,
+    /// With `--partial-on-interrupt`, stop reading input once this is
+    /// requested, and calculate the result from what we've read
+    pub interrupt: Interrupt,
+    /// Where the run tallies the lines it reads and writes. `calculate` keeps
+    /// a fresh tally for each call, and returns its `Stats`; after the other
+    /// entry points (`Venn::new`, `stream::multiple`, and so on), read them
+    /// from here — and give each run a tally of its own, as `Options::new`
+    /// does.
+    pub tally: Tally,
+}
+
+/// How `--where` lists the places each output line occurs
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Locations {
+    /// List at most this many places for each line (by default, all of them)
+    pub limit: Option<NonZeroUsize>,
+    /// The names of the operands, in order, for the listing
+    pub names: Vec<String>,
+}
+
+impl Locations {
+    /// The most places to list for each line
+    #[must_use]
+    pub fn limit(&self) -> usize {
+        self.limit.map_or(usize::MAX, NonZeroUsize::get)
+    }
+}
+
+impl Options {
+    /// Options for operation `op`, with no counts, comparing whole lines
+    #[must_use]
+    pub fn new(op: OpName) -> Self {
+        Options {
+            op,
+            log_type: LogType::None,
+            key: Key::whole_line(),
+            framing: Framing::Lines,
+            fallible_allocation: false,
+            top: None,
+            approx: None,
+            count_format: CountFormat::Full,
+            line_format: LineFormat::Raw,
+            output_format: OutputFormat::Plain,
+            header_row: false,
+            in_at_least: None,
+            min_files: None,
+            max_files: None,
+            min_count: None,
+            max_count: None,
+            invert: false,
+            locations: None,
+            grouped: None,
+            show_files: None,
+            with_filename: false,
+            counted_input: false,
+            output_fields: None,
+            count_distinct: None,
+            keep: Keep::First,
+            sort: false,
+            order: Order::FirstSeen,
+            count_order: None,
+            head: None,
+            sample: None,
+            count_after: false,
+            count_align: CountAlign::Right,
+            count_per_file: None,
+            shuffle: None,
+            interrupt: Interrupt::default(),
+            tally: Tally::default(),
+        }
+    }
+
+    /// Write `line` (without its terminator) as `line_format` says — or just
+    /// its key fields, with `output_fields`.
+    pub(crate) fn write_line(&self, line: &[u8], out: &mut impl std::io::Write) -> Result<()> {
+        match &self.output_fields {
+            None => self.line_format.write(line, out),
+            Some(joiner) => self.line_format.write(&self.key.fields_of(line, joiner), out),
+        }
+    }
+
+    /// Is `count` within the bounds set by `min_files` and `max_files`, and
+    /// by `min_count` and `max_count`? (`calculate` sees to it that the bounds
+    /// set are on what `count` counts: files or occurrences.)
+    #[must_use]
+    pub fn in_bounds(&self, count: u32) -> bool {
+        let min = [self.min_files, self.min_count].into_iter().flatten().max();
+        let max = [self.max_files, self.max_count].into_iter().flatten().min();
+        !min.is_some_and(|min| count < min) && !max.is_some_and(|max| count > max)
+    }
+
+    /// Is either of `min_files` and `max_files` set?
+    fn bounds_files(&self) -> bool {
+        self.min_files.is_some() || self.max_files.is_some()
+    }
+
+    /// Is either of `min_count` and `max_count` set?
+    fn bounds_counts(&self) -> bool {
+        self.min_count.is_some() || self.max_count.is_some()
+    }
+
+    /// Bail if `self.op` can't do what the other options ask: if bounds are
+    /// set on something it doesn't count, if it can't be inverted and
+    /// `self.invert` is set, if it can't group its output by operand and
+    /// `self.grouped` is set, or if it isn't `Union` and `self.count_distinct`
+    /// is set
+    fn check_options(&self) -> Result<()> {
+        if self.count_distinct.is_some() && self.op != Union {
+            bail!("Only the union operation can count distinct values, not {}", self.op.name());
+        }
+        if self.invert && matches!(self.op, Top | Stats | MergeCounts) {
+            bail!("The {} operation doesn't select lines, so can't be inverted", self.op.name());
+        }
+        let grouped = self.grouped.is_some();
+        if grouped
+            && (self.op != SingleByFile || !matches!(self.log_type, LogType::None) || self.invert)
+        {
+            bail!("Only single-by-file, without counts or inversion, can group lines by file");
+        }
+        if self.bounds_files()
+            && !matches!(
+                self.op,
+                Union | Intersect | Diff | SingleByFile | MultipleByFile | Majority
+            )
+        {
+            bail!("The {} operation doesn't count files, so can't bound them", self.op.name());
+        }
+        if self.bounds_counts() && !matches!(self.op, Union | Single | Multiple) {
+            bail!("The {} operation doesn't count lines, so can't bound them", self.op.name());
+        }
+        if self.bounds_counts() && self.bounds_files() {
+            bail!("Can't bound both the number of files and the number of times a line occurs");
+        }
+        Ok(())
+    }
+}
+
+/// A minimum number of files, given as a count (`3`) or as a percentage of the
+/// number of operands (`80%`)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Threshold {
+    Files(u32),
+    Percent(f64),
+}
+
+impl Threshold {
+    /// The minimum number of files, out of `operands`. A percentage is rounded
+    /// up, so that `80%` of 9 files is 8. Every line is in at least one file,
+    /// so a threshold of less than 1 is the same as 1.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+    pub fn files(self, operands: usize) -> u32 {
+        let files = match self {
+            Threshold::Files(files) => files,
+            Threshold::Percent(percent) => (percent / 100.0 * operands as f64).ceil() as u32,
+        };
+        files.max(1)
+    }
+}
+
+impl FromStr for Threshold {
+    type Err = String;
+    /// Parse a count of files (`3`) or a percentage (`80%`, `12.5%`)
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if let Some(percent) = text.strip_suffix('%') {
+            match percent.parse::<f64>() {
+                Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(Threshold::Percent(percent)),
+                _ => Err(format!("'{text}' isn't a percentage from 0% to 100%")),
+            }
+        } else {
+            text.parse()
+                .map(Threshold::Files)
+                .map_err(|_| format!("'{text}' isn't a number of files"))
+        }
+    }
+}
+
+/// How counts are written: in full, or (with `--si`) abbreviated with SI
+/// suffixes, like `1.2k` or `3.4M`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CountFormat {
+    #[default]
+    Full,
+    Si,
+}
+
+/// The order of lines sorted by their counts: most frequent first (the
+/// default), or with `--asc`, least frequent first
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CountOrder {
+    #[default]
+    Descending,
+    Ascending,
+}
+
+/// How plain output pads its counts: right-aligned in a column as wide as the
+/// largest count (the default), left-aligned in that column, or (with
+/// `CountAlign::None`) not padded at all
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CountAlign {
+    #[default]
+    Right,
+    Left,
+    None,
+}
+
+impl CountFormat {
+    /// The width of the count column when the largest count is `max_count`
+    fn width(self, max_count: u64) -> usize {
+        match self {
+            // Abbreviations of counts of 10,000 or more can be as wide as
+            // `99.9k`, even when `max_count`'s abbreviation is `1.2M`.
+            CountFormat::Si if max_count >= 10_000 => 5,
+            CountFormat::Si => si(max_count).len(),
+            CountFormat::Full => max_count.checked_ilog10().unwrap_or(0) as usize + 1,
+        }
+    }
+
+    /// Write `count`, aligned as `align` says in a column `width` wide
+    fn write(
+        self,
+        count: u64,
+        width: usize,
+        align: CountAlign,
+        out: &mut impl std::io::Write,
+    ) -> Result<()> {
+        let count = match self {
+            CountFormat::Full => count.to_string(),
+            CountFormat::Si => si(count),
+        };
+        match align {
+            CountAlign::Left => write!(out, "{count:<width$}")?,
+            CountAlign::Right | CountAlign::None => write!(out, "{count:>width$}")?,
+        }
+        Ok(())
+    }
+}
+
+/// How output is laid out: as plain text, with counts right-aligned in columns
+/// separated by spaces, or (with `--format csv` or `--format tsv`) as comma- or
+/// tab-separated values, with counts unpadded — or with `--column-delimiter`,
+/// unpadded columns separated by any byte. CSV output should also use
+/// `LineFormat::CsvQuoted`, to quote lines containing commas or quotes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Counts right-aligned in columns, separated by spaces
+    #[default]
+    Plain,
+    /// Comma-separated values
+    Csv,
+    /// Tab-separated values
+    Tsv,
+    /// Values separated by the given byte
+    Delimited(u8),
+}
+
+impl OutputFormat {
+    /// The byte separating columns
+    pub(crate) fn delimiter(self) -> u8 {
+        match self {
+            OutputFormat::Plain => b' ',
+            OutputFormat::Csv => b',',
+            OutputFormat::Tsv => b'\t',
+            OutputFormat::Delimited(delimiter) => delimiter,
+        }
+    }
+}
+
+/// How to write the count columns that precede each line of annotated output
+/// (or with `after`, follow it): in the given `format`, aligned as `align` says
+/// in columns `width` wide (or unpadded, for delimited output), each followed
+/// (or with `after`, preceded) by `delimiter`. This is our policy for laying
+/// out counts, which every writer of counted lines follows.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CountColumns {
+    format: CountFormat,
+    width: usize,
+    align: CountAlign,
+    delimiter: u8,
+    after: bool,
+}
+
+impl CountColumns {
+    /// The columns for `counts`, as `options` says to write them. Counts after
+    /// the line can't line up, so for plain output they're unpadded, after a
+    /// tab.
+    pub(crate) fn new(options: &Options, counts: impl Iterator<Item = u64>) -> Self {
+        let (format, align, after) =
+            (options.count_format, options.count_align, options.count_after);
+        let (width, delimiter) = match options.output_format {
+            OutputFormat::Plain if after => (0, b'\t'),
+            OutputFormat::Plain if align == CountAlign::None => (0, b' '),
+            OutputFormat::Plain => {
+                (counts.max().map_or(1, |max_count| format.width(max_count)), b' ')
+            }
+            OutputFormat::Csv | OutputFormat::Tsv | OutputFormat::Delimited(_) => {
+                (0, options.output_format.delimiter())
+            }
+        };
+        CountColumns { format, width, align, delimiter, after }
+    }
+
+    /// Write `count`, followed (or with `after`, preceded) by our delimiter
+    pub(crate) fn write(&self, count: u64, out: &mut impl std::io::Write) -> Result<()> {
+        if self.after {
+            out.write_all(&[self.delimiter])?;
+        }
+        self.format.write(count, self.width, self.align, out)?;
+        if !self.after {
+            out.write_all(&[self.delimiter])?;
+        }
+        Ok(())
+    }
+
+    /// Write `overflow` in place of a count too large to be sure of
+    fn write_overflow(&self, out: &mut impl std::io::Write) -> Result<()> {
+        let overflow: &[u8] = if self.width > 0 { b" overflow " } else { b"overflow" };
+        if self.after {
+            out.write_all(&[self.delimiter])?;
+        }
+        out.write_all(overflow)?;
+        if !self.after {
+            out.write_all(&[self.delimiter])?;
+        }
+        Ok(())
+    }
+
+    /// Write `line` with the counts `write_counts` writes: before it, or with
+    /// `after`, after it — and then `terminator`
+    pub(crate) fn write_row<W: std::io::Write>(
+        &self,
+        line: &[u8],
+        options: &Options,
+        terminator: &[u8],
+        out: &mut W,
+        write_counts: impl FnOnce(&mut W) -> Result<()>,
+    ) -> Result<()> {
+        if self.after {
+            options.write_line(line, out)?;
+            write_counts(out)?;
+        } else {
+            write_counts(out)?;
+            options.write_line(line, out)?;
+        }
+        out.write_all(terminator)?;
+        Ok(())
+    }
+}
+
+/// With `options.header_row`, write a row naming the `count_columns` and then
+/// the `line` column (or with `options.count_after`, the `line` column first),
+/// ending with `terminator`
+pub(crate) fn write_header(
+    count_columns: &[&str],
+    options: &Options,
+    terminator: &[u8],
+    out: &mut impl std::io::Write,
+) -> Result<()> {
+    if options.header_row {
+        let delimiter = [options.output_format.delimiter()];
+        if options.count_after {
+            out.write_all(b"line")?;
+        }
+        for name in count_columns {
+            if options.count_after {
+                out.write_all(&delimiter)?;
+            }
+            out.write_all(name.as_bytes())?;
+            if !options.count_after {
+                out.write_all(&delimiter)?;
+            }
+        }
+        if !options.count_after {
+            out.write_all(b"line")?;
+        }
+        out.write_all(terminator)?;
+    }
+    Ok(())
+}
+
+/// How output lines are written: as is, or (with `--shell-quote`) quoted for a
+/// POSIX shell, so they can be safely substituted into `xargs` or `for` loops,
+/// or (with `--format csv`) quoted if need be as a CSV field.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineFormat {
+    #[default]
+    Raw,
+    ShellQuoted,
+    CsvQuoted,
+}
+
+impl LineFormat {
+    /// The Byte Order Mark to start output with, given the input's `bom`. A
+    /// shell wouldn't understand a Byte Order Mark, so we drop it when quoting.
+    pub(crate) fn bom(self, bom: &'static [u8]) -> &'static [u8] {
+        match self {
+            LineFormat::Raw | LineFormat::CsvQuoted => bom,
+            LineFormat::ShellQuoted => b"",
+        }
+    }
+
+    /// Write `line` (without its terminator). To shell-quote it, we surround
+    /// it with single quotes, writing each single quote within it as `'\''`.
+    /// A CSV field needs quoting only if it contains a comma, a double quote,
+    /// or a line break; then we surround it with double quotes, doubling each
+    /// double quote within it.
+    pub(crate) fn write(self, line: &[u8], out: &mut impl std::io::Write) -> Result<()> {
+        match self {
+            LineFormat::Raw => out.write_all(line)?,
+            LineFormat::ShellQuoted => {
+                out.write_all(b"'")?;
+                for (n, part) in line.split(|&b| b == b'\'').enumerate() {
+                    if n > 0 {
+                        out.write_all(br"'\''")?;
+                    }
+                    out.write_all(part)?;
+                }
+                out.write_all(b"'")?;
+            }
+            LineFormat::CsvQuoted => {
+                if !line.iter().any(|b| b",\"\r\n".contains(b)) {
+                    out.write_all(line)?;
+                    return Ok(());
+                }
+                out.write_all(b"\"")?;
+                for (n, part) in line.split(|&b| b == b'"').enumerate() {
+                    if n > 0 {
+                        out.write_all(b"\"\"")?;
+                    }
+                    out.write_all(part)?;
+                }
+                out.write_all(b"\"")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `count` abbreviated with an SI suffix, to three significant figures (or
+/// two, for `1.0k` through `9.9k` and the like): `999`, `1.2k`, `34.5k`,
+/// `678k`, `9.0M`.
+#[allow(clippy::cast_precision_loss)]
+fn si(count: u64) -> String {
+    if count < 1000 {
+        return count.to_string();
+    }
+    let mut value = count as f64;
+    for suffix in ["k", "M", "G", "T", "P"] {
+        value /= 1000.0;
+        if value < 99.95 {
+            return format!("{value:.1}{suffix}");
+        } else if value < 999.5 {
+            return format!("{value:.0}{suffix}");
+        }
+    }
+    format!("{:.1}E", value / 1000.0)
+}
+/// Calculates and prints the set operation named by `options.op`. Each operand
+/// is treated as a set of lines:
+///
+/// * `OpName::Union` prints the lines that occur in any file,
+/// * `OpName::Intersect` prints the lines that occur in all files (or, with
+///   `options.in_at_least`, in at least the given number or percentage of
+///   files),
+/// * `OpName::Diff` prints the lines that occur in the first file and no other,
+/// * `OpName::Single` prints the lines that occur once in exactly in the input,
+/// * `OpName::Multiple` prints the lines that occur more than once in the input,
+/// * `OpName::SingleByFile` prints the lines that occur in exactly one file,
+/// * `OpName::MultipleByFile` prints the lines that occur in more than one file,
+///   and
+/// * `OpName::Majority` prints the lines that occur in more than half of the
+///   files.
+///
+/// With `options.min_files` or `options.max_files`, only those lines present
+/// in a number of files within the bounds are printed. Since the bounds are
+/// numbers of files, they can be used with `Union`, `Intersect`, `Diff`,
+/// `SingleByFile`, `MultipleByFile`, and `Majority`, but not with those that
+/// count lines. Likewise `options.min_count` and `options.max_count` bound the
+/// number of times a line occurs in all, for `Union`, `Single`, and `Multiple`.
+///
+/// With `options.grouped`, `SingleByFile` prints the lines of each operand
+/// under a heading naming it.
+///
+/// With `options.invert`, the lines printed are those of the input that the
+/// operation (with any bounds) would leave out — so `Intersect` prints the lines
+/// that aren't in every file, and `Single` the same lines as `Multiple`. Only
+/// the operations that select lines can be inverted, not `Top`, `Stats`, or
+/// `MergeCounts`.
+///
+/// The `options.log_type` field specifies whether `calculate` should print the number
+/// of times each line appears in the input (`LogType::Lines`), the number of
+/// files in which each line appears (`LogType::Files`), the number of times
+/// each line appears in the first operand and in the rest of the input
+/// (`LogType::Split`), both the number of times and the number of files
+/// (`LogType::LinesAndFiles`), or none of these (`LogType::None`).
+///
+/// The `options.key` field says how to compare lines: normally by the whole
+/// line, but possibly by selected fields. And `options.framing` says how the
+/// input is divided into records: normally lines, but possibly fixed-width
+/// binary records.
+///
+/// Returns the statistics of the run: the lines read and written by this call
+/// alone, whatever `options.tally` holds.
+///
+pub fn calculate<O: LaterOperand>(
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl ExactSizeIterator<Item = Result<O>>,
+    out: impl std::io::Write,
+) -> Result<summary::Stats> {
+    let options = &Options { tally: Tally::default(), ..options.clone() };
+    calculate_tallied(options, first_operand, options.tally.counting(rest), out)?;
+    Ok(options.tally.stats())
+}
+
+/// `calculate`, tallying its statistics in `options.tally`
+fn calculate_tallied<O: LaterOperand>(
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl ExactSizeIterator<Item = Result<O>>,
+    out: impl std::io::Write,
+) -> Result<()> {
+    let Options { op: operation, log_type, .. } = *options;
+    let number_of_operands = rest.len() + 1; // + 1 because first_operand is an operand
+    if number_of_operands > u32::MAX as usize {
+        bail!("Zet can't handle more than {} input files", u32::MAX)
+        // Since we have <= u32::MAX operands, the `next_file` method can't overflow and we can use
+        // wrapping_add
+    }
+    options.check_options()?;
+    if let Some(value) = &options.count_distinct {
+        return crate::distinct::count(options, value, first_operand, rest, out);
+    }
+    match log_type {
+        LogType::None => match operation {
+            Union if options.bounds_files() => {
+                within_bounds::<Files, O>(options, first_operand, rest, out)
+            }
+            Union if options.bounds_counts() => {
+                within_bounds::<Lines, O>(options, first_operand, rest, out)
+            }
+            Union => union::<Unsifted, O>(options, first_operand, rest, out),
+            Diff => diff::<Files, O>(options, first_operand, rest, out),
+            Intersect => intersect::<Files, O>(options, first_operand, rest, out),
+            Single => keep_single::<Lines, O>(options, first_operand, rest, out),
+            Multiple => keep_multiple::<Lines, O>(options, first_operand, rest, out),
+            SingleByFile if options.grouped.is_some() => {
+                single_grouped(options, first_operand, rest, out)
+            }
+            SingleByFile => keep_single::<Files, O>(options, first_operand, rest, out),
+            MultipleByFile => keep_multiple::<Files, O>(options, first_operand, rest, out),
+            Majority => majority::<Files, O>(options, first_operand, rest, out),
+            // `Top`, `Stats`, and `MergeCounts` always count lines, whatever
+            // the `log_type`
+            Top => top(options, first_operand, rest, out),
+            Stats => stats(options, first_operand, rest, out),
+            MergeCounts => crate::counted::merge(options, first_operand, rest, out),
+        },
+
+        // When `log_type` is `LogType::Lines` and `operation` is `Single` or
+        // `Multiple`, both logging and selection use `Lines`. Since
+        // `SiftLog<Lines, Lines>` would do duplicate bookkeeping, we just
+        // use `Lines` by itself.
+        LogType::Lines => match operation {
+            Union if options.bounds_files() => {
+                within_bounds::<SiftLog<Files, Lines>, O>(options, first_operand, rest, out)
+            }
+            Union if options.bounds_counts() => {
+                within_bounds::<Log<Lines>, O>(options, first_operand, rest, out)
+            }
+            Union => union::<Log<Lines>, O>(options, first_operand, rest, out),
+            Diff => diff::<SiftLog<Files, Lines>, O>(options, first_operand, rest, out),
+            Intersect => intersect::<SiftLog<Files, Lines>, O>(options, first_operand, rest, out),
+            Single => keep_single::<Log<Lines>, O>(options, first_operand, rest, out),
+            Multiple => keep_multiple::<Log<Lines>, O>(options, first_operand, rest, out),
+            SingleByFile => {
+                keep_single::<SiftLog<Files, Lines>, O>(options, first_operand, rest, out)
+            }
+            MultipleByFile => {
+                keep_multiple::<SiftLog<Files, Lines>, O>(options, first_operand, rest, out)
+            }
+            Majority => majority::<SiftLog<Files, Lines>, O>(options, first_operand, rest, out),
+            Top => top(options, first_operand, rest, out),
+            Stats => stats(options, first_operand, rest, out),
+            MergeCounts => crate::counted::merge(options, first_operand, rest, out),
+        },
+
+        // Similarly, we don't want to use `SiftLog<Files, Files>` bookkeeping
+        // values, so we use `Log<Files>` by itself when `log_type` is
+        // LogType::Files` and `operation` is `SingleByFile` or
+        // `MultipleByFile`.
+        //
+        // And we use `Log<Lines>` for `Single`, rather than `SiftLog<Lines,
+        // Files>`, since the number reported for `Single` will always be 1 — a
+        // line appearing only once can appear in only one file.
+        LogType::Files => match operation {
+            Union if options.bounds_files() => {
+                within_bounds::<Log<Files>, O>(options, first_operand, rest, out)
+            }
+            Union if options.bounds_counts() => {
+                within_bounds::<SiftLog<Lines, Files>, O>(options, first_operand, rest, out)
+            }
+            Union => union::<Log<Files>, O>(options, first_operand, rest, out),
+            Diff => diff::<Log<Files>, O>(options, first_operand, rest, out),
+            Intersect => intersect::<Log<Files>, O>(options, first_operand, rest, out),
+            Single => keep_single::<Log<Lines>, O>(options, first_operand, rest, out),
+            Multiple => {
+                keep_multiple::<SiftLog<Lines, Files>, O>(options, first_operand, rest, out)
+            }
+            SingleByFile => keep_single::<Log<Files>, O>(options, first_operand, rest, out),
+            MultipleByFile => keep_multiple::<Log<Files>, O>(options, first_operand, rest, out),
+            Majority => majority::<Log<Files>, O>(options, first_operand, rest, out),
+            Top => top(options, first_operand, rest, out),
+            Stats => stats(options, first_operand, rest, out),
+            MergeCounts => crate::counted::merge(options, first_operand, rest, out),
+        },
+
+        LogType::Split => count_split(options, first_operand, rest, out),
+        LogType::LinesAndFiles => count_lines_and_files(options, first_operand, rest, out),
+    }
+}
+
+/// `calculate` for `LogType::Split`. `Split` counts lines, so `Single` and
+/// `Multiple` can sift by its total count and need no separate `Lines` item.
+fn count_split<O: LaterOperand>(
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl ExactSizeIterator<Item = Result<O>>,
+    out: impl std::io::Write,
+) -> Result<()> {
+    match options.op {
+        Union if options.bounds_files() => {
+            within_bounds::<SiftLog<Files, Split>, O>(options, first_operand, rest, out)
+        }
+        Union if options.bounds_counts() => {
+            within_bounds::<Log<Split>, O>(options, first_operand, rest, out)
+        }
+        Union => union::<Log<Split>, O>(options, first_operand, rest, out),
+        Diff => diff::<SiftLog<Files, Split>, O>(options, first_operand, rest, out),
+        Intersect => intersect::<SiftLog<Files, Split>, O>(options, first_operand, rest, out),
+        Single => keep_single::<Log<Split>, O>(options, first_operand, rest, out),
+        Multiple => keep_multiple::<Log<Split>, O>(options, first_operand, rest, out),
+        SingleByFile => keep_single::<SiftLog<Files, Split>, O>(options, first_operand, rest, out),
+        MultipleByFile => {
+            keep_multiple::<SiftLog<Files, Split>, O>(options, first_operand, rest, out)
+        }
+        Majority => majority::<SiftLog<Files, Split>, O>(options, first_operand, rest, out),
+        Top => top(options, first_operand, rest, out),
+        Stats => stats(options, first_operand, rest, out),
+        MergeCounts => crate::counted::merge(options, first_operand, rest, out),
+    }
+}
+
+/// `calculate` for `LogType::LinesAndFiles`. `LinesAndFiles` sifts by its
+/// count of lines, so only the operations that sift by files need a separate
+/// `Files` item.
+fn count_lines_and_files<O: LaterOperand>(
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl ExactSizeIterator<Item = Result<O>>,
+    out: impl std::io::Write,
+) -> Result<()> {
+    match options.op {
+        Union if options.bounds_files() => {
+            within_bounds::<SiftLog<Files, LinesAndFiles>, O>(options, first_operand, rest, out)
+        }
+        Union if options.bounds_counts() => {
+            within_bounds::<Log<LinesAndFiles>, O>(options, first_operand, rest, out)
+        }
+        Union => union::<Log<LinesAndFiles>, O>(options, first_operand, rest, out),
+        Diff => diff::<SiftLog<Files, LinesAndFiles>, O>(options, first_operand, rest, out),
+        Intersect => {
+            intersect::<SiftLog<Files, LinesAndFiles>, O>(options, first_operand, rest, out)
+        }
+        Single => keep_single::<Log<LinesAndFiles>, O>(options, first_operand, rest, out),
+        Multiple => keep_multiple::<Log<LinesAndFiles>, O>(options, first_operand, rest, out),
+        SingleByFile => {
+            keep_single::<SiftLog<Files, LinesAndFiles>, O>(options, first_operand, rest, out)
+        }
+        MultipleByFile => {
+            keep_multiple::<SiftLog<Files, LinesAndFiles>, O>(options, first_operand, rest, out)
+        }
+        Majority => majority::<SiftLog<Files, LinesAndFiles>, O>(options, first_operand, rest, out),
+        Top => top(options, first_operand, rest, out),
+        Stats => stats(options, first_operand, rest, out),
+        MergeCounts => crate::counted::merge(options, first_operand, rest, out),
+    }
+}
+
+/// Like `calculate`, but with the operation given by name — `"intersect"`,
+/// `"single-by-file"`, and so on (see `OpName::name`) — rather than by
+/// `options.op`, for callers that don't want to depend on our argument-parsing
+/// types.
+pub fn calculate_named<O: LaterOperand>(
+    operation: &str,
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl ExactSizeIterator<Item = Result<O>>,
+    out: impl std::io::Write,
+) -> Result<summary::Stats> {
+    let options = Options { op: operation.parse()?, ..options.clone() };
+    calculate(&options, first_operand, rest, out)
+}
+
+/// Like `calculate`, but for records of any hashable type `K` that the caller
+/// has parsed from its input, rather than for lines: returns the records of the
+/// result, in order of first appearance. Only the sifting operations —
+/// `Union`, `Intersect`, `Diff`, `Single`, `SingleByFile`, `Multiple`,
+/// `MultipleByFile`, and `Majority` — make sense for records; the others bail.
+pub fn calculate_records<K: Hash + Eq, R: IntoIterator<Item = K>>(
+    operation: OpName,
+    first_operand: impl IntoIterator<Item = K>,
+    rest: impl IntoIterator<Item = R>,
+) -> Result<Vec<K>> {
+    let records = match operation {
+        Union => every_record::<Unsifted, K, R>(first_operand, rest).into_records().collect(),
+        Diff => kept(first_operand_records::<Files, K, R>(first_operand, rest), |files| files == 1),
+        Intersect => {
+            let mut all_operands = 1_u32;
+            let rest = rest.into_iter().inspect(|_| all_operands += 1);
+            let set = first_operand_records::<Files, K, R>(first_operand, rest);
+            kept(set, |files| files == all_operands)
+        }
+        Single => kept(every_record::<Lines, K, R>(first_operand, rest), |lines| lines == 1),
+        Multiple => kept(every_record::<Lines, K, R>(first_operand, rest), |lines| lines > 1),
+        SingleByFile => kept(every_record::<Files, K, R>(first_operand, rest), |files| files == 1),
+        MultipleByFile => kept(every_record::<Files, K, R>(first_operand, rest), |files| files > 1),
+        Majority => {
+            let mut all_operands = 1_u32;
+            let rest = rest.into_iter().inspect(|_| all_operands += 1);
+            let set = every_record::<Files, K, R>(first_operand, rest);
+            kept(set, |files| files.saturating_mul(2) > all_operands)
+        }
+        Top | Stats | MergeCounts => {
+            bail!("The {} operation works only on lines, not on records", operation.name())
+        }
+    };
+    Ok(records)
+}
+
+/// The records version of `every_line`: a `ZetSet` of the records of every
+/// operand
+fn every_record<B: Bookkeeping, K: Hash + Eq, R: IntoIterator<Item = K>>(
+    first_operand: impl IntoIterator<Item = K>,
+    rest: impl IntoIterator<Item = R>,
+) -> ZetSet<'static, B, K> {
+    let mut item = B::new();
+    let mut set = ZetSet::from_records(first_operand, item);
+    for operand in rest {
+        item.next_file();
+        set.insert_or_update_records(operand, item);
+    }
+    set
+}
+
+/// The records version of `first_file_lines`: a `ZetSet` of the records of
+/// the first operand, updated by those of the rest
+fn first_operand_records<B: Bookkeeping, K: Hash + Eq, R: IntoIterator<Item = K>>(
+    first_operand: impl IntoIterator<Item = K>,
+    rest: impl IntoIterator<Item = R>,
+) -> ZetSet<'static, B, K> {
+    let mut item = B::new();
+    let mut set = ZetSet::from_records(first_operand, item);
+    for operand in rest {
+        item.next_file();
+        set.update_records_if_present(operand, item);
+    }
+    set
+}
+
+/// The records of `set` whose bookkeeping values pass `keep`, in order
+fn kept<B: Bookkeeping, K: Hash + Eq>(mut set: ZetSet<B, K>, keep: impl Fn(u32) -> bool) -> Vec<K> {
+    set.retain(keep);
+    set.into_records().collect()
+}
+
+/// A `ZetSet` is an ordered set of lines where each line from the input file(s)
+/// occurs once in the `ZetSet`, and each line has an associated `Bookkeeping`
+/// value that we use to determine whether to retain the line in the output, and
+/// optionally to output a count along with each line (counting either the
+/// number of times the line occurs in the input, or the number of files in
+/// which the line occurs).
+///
+/// The `Bookkeeping` trait specifies the kind of types that can serve as the
+/// bookkeeping values for a `ZetSet`, and defines a default `output_zet_set`
+/// method to print the lines without a count.
+///
+/// There are seven basic `Bookkeeping` types. The `Unsifted`, `Lines`, and `Files`
+/// types are used for "sifting" — after all files have been processed, we look
+/// at the bookkeeping values to sift out unwanted lines before printing.  The
+/// `Union` operation outputs every line, so uses an `Unsifted` bookkeeping type
+/// with a zero-size value and no-op methods.  The `Single` and `Multiple`
+/// operations use the `Lines` type to sift by the number of times a line has
+/// been seen, while the `Diff`, `Intersect`, `SingleByFile`, and
+/// `MultipleByFile` operations use the `Files` type to sift by the number of
+/// files in which a line has been seen.
+///
+/// The `Log<Lines>` and `Log<Files>` types act like `Lines` and `Files`
+/// respectively, except that their `output_zet_set` methods output the
+/// appropriate count along with each line. They can also be used for sifting,
+/// so if we want to output only those lines which occur more than once in the
+/// input, and want to know how many times each line has been seen, we can use
+/// `Log<Lines>` both retain lines seen more than once and to print the exact
+/// number.
+///
+/// Sometimes, though we want to sift by one value but print another. We might,
+/// for instance, want to output lines that occur in only one file, but also
+/// want to print how many time each line occurred in the file. For that we'd
+/// use `SiftLog<Files, Lines>` bookkeeping values to sift by the number of
+/// files seen and log the number of lines seen.  And we could use
+/// `SiftLog<Lines, Files>` to print only lines occuring multiple times, while
+/// printing the number of files each line occurs in.
+///
+/// The trait is public so that library users can drive a `ZetSet` with their
+/// own bookkeeping types — say, one that sums a numeric field of each line —
+/// without changing this module. Such types will usually override `for_line`,
+/// and may filter the set with `ZetSet::retain_if` rather than `retain`.
+///
+/// Finally, for `--count-split`, the `Split` type counts lines like `Lines`,
+/// but keeps separate counts for the first operand and the rest of the input.
+/// It's used as `Log<Split>`, or as `SiftLog<Files, Split>`. And for
+/// `--count-lines --count-files`, the `LinesAndFiles` type keeps both a
+/// `Lines` and a `Files` count, and logs them in two columns.
+pub trait Bookkeeping: Copy + PartialEq + Debug {
+    /// The initial bookkeeping value for each line in the first operand.
+    /// Usually keeps track of lines and/or files seen.
+    fn new() -> Self;
+
+    /// Increment the bookkeeping item's `n`th file field (if it has one)
+    fn next_file(&mut self);
+
+    /// Here `other` is the value that would have been inserted for a
+    /// newly-encountered line. Used to update the bookkeeping values of lines
+    /// already present in the `ZetSet`.
+    fn update_with(&mut self, other: Self);
+
+    /// Adjust `self`, the item passed to the `ZetSet` for the current operand,
+    /// to account for the particular `line` about to be inserted or updated.
+    /// The provided implementation returns `self` unchanged, as the counts we
+    /// keep don't depend on what a line contains.
+    #[must_use]
+    fn for_line(self, _line: &[u8]) -> Self {
+        self
+    }
+
+    /// With `--counted-input`, adjust `self` to stand for `weight` occurrences
+    /// of a line rather than one. The provided implementation returns `self`
+    /// unchanged, as is right for counts (of files, say) that don't depend on
+    /// how often a line occurs.
+    #[must_use]
+    fn weighted(self, _weight: u32) -> Self {
+        self
+    }
+
+    /// The value to be used in closure passed to the `ZetSet`'s `retain`
+    /// method.
+    fn retention_value(self) -> u32;
+
+    /// Output the `ZetSet`. The provided implementation doesn't log a count of
+    /// lines or files, so must be overridden by types that do loggging (which
+    /// should consult `options.count_format` as well as `options.line_format`).
+    fn output_zet_set(
+        set: &ZetSet<Self>,
+        options: &Options,
+        mut out: impl std::io::Write,
+    ) -> Result<()> {
+        if let Some(locations) = &options.locations {
+            return write_locations(set, locations, options, out);
+        }
+        if let Some(names) = &options.show_files {
+            return write_with_files(set, names, options, out);
+        }
+        if let Some(names) = &options.count_per_file {
+            return write_counts_per_file(set, names, options, out);
+        }
+        out.write_all(options.line_format.bom(set.bom))?;
+        write_header(&[], options, &set.line_terminator, &mut out)?;
+        for line in set.lines() {
+            options.write_line(line, &mut out)?;
+            out.write_all(&set.line_terminator)?;
+        }
+        options.tally.wrote(set.len() as u64);
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// The `Loggable` trait specifies two additional methods used to log a count
+/// with each output line, and the names of the count columns for a header row.
+trait Loggable: Bookkeeping {
+    /// The names of the columns `write_log` writes
+    const COUNT_COLUMNS: &'static [&'static str];
+
+    /// The line/file count to be used for logging purposes
+    fn log_value(self) -> u32;
+
+    /// Write the count to the output, as `columns` says. Called before
+    /// outputting the line itself.
+    fn write_log(&self, columns: &CountColumns, out: &mut impl std::io::Write) -> Result<()>;
+}
+
+/// For the "additive" operations (all but `Diff` and `Intersect`), we insert
+/// every line in the input into the `ZetSet`. Both `ZetSet::new` and
+/// `set.insert_or_update` will call `b.update_with(item)` on the line's
+/// bookkeeping item `b` if the line is already present in the `ZetSet`.
+///
+/// `every_line`'s caller can then use `set.retain()` to examine the each line's
+/// bookkeeping item to decide whether or not it belongs in the set.
+fn every_line<'data, B: Bookkeeping, O: LaterOperand>(
+    options: &Options,
+    first_operand: &'data [u8],
+    rest: impl Iterator<Item = Result<O>>,
+) -> Result<ZetSet<'data, B>> {
+    let item = B::new();
+    let set = ZetSet::with_options(first_operand, item, options)?;
+    every_later_line(options, set, item, rest)
+}
+
+/// Insert or update every line of `rest` in `set`, whose lines so far are
+/// those of the first operand, with bookkeeping item `item`
+fn every_later_line<'data, B: Bookkeeping, O: LaterOperand>(
+    options: &Options,
+    mut set: ZetSet<'data, B>,
+    mut item: B,
+    rest: impl Iterator<Item = Result<O>>,
+) -> Result<ZetSet<'data, B>> {
+    for operand in options.interrupt.cut_short(rest) {
+        item.next_file();
+        set.insert_or_update(operand?, item)?;
+    }
+    options.tally.distinct(set.len() as u64);
+    Ok(set)
+}
+
+/// `Union` collects every line, so we don't need to call `retain` — unless
+/// `options.invert` is set, when it keeps none of them.
+fn union<B: Bookkeeping, O: LaterOperand>(
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl Iterator<Item = Result<O>>,
+    out: impl std::io::Write,
+) -> Result<()> {
+    let mut set = every_line::<B, O>(options, first_operand, rest)?;
+    if options.invert {
+        set.retain(|_| false);
+    }
+    output_and_discard(set, options, out)
+}
+
+/// `Union` with `options.min_files` and `options.max_files`, or
+/// `options.min_count` and `options.max_count`, retains the lines whose file
+/// count or line count is within the bounds.
+fn within_bounds<B: Bookkeeping, O: LaterOperand>(
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl Iterator<Item = Result<O>>,
+    out: impl std::io::Write,
+) -> Result<()> {
+    let mut set = every_line::<B, O>(options, first_operand, rest)?;
+    set.retain(|files_containing_line| options.invert != options.in_bounds(files_containing_line));
+    output_and_discard(set, options, out)
+}
+
+/// `Single` and `SingleByFile` retain those lines where the relevant count is
+/// `1`.
+fn keep_single<B: Bookkeeping, O: LaterOperand>(
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl Iterator<Item = Result<O>>,
+    out: impl std::io::Write,
+) -> Result<()> {
+    let mut set = every_line::<B, O>(options, first_operand, rest)?;
+    set.retain(|occurences| options.invert != (occurences == 1 && options.in_bounds(occurences)));
+    output_and_discard(set, options, out)
+}
+
+/// With `options.grouped`, `SingleByFile` writes the lines that occur in just
+/// one file grouped by that file: for each operand, a heading like `== only
+/// x.txt ==`, then the operand's lines (in order of first appearance). The
+/// `file_number` of a line's `Files` item is the operand it was last seen in —
+/// for a line in just one operand, that operand.
+fn single_grouped<O: LaterOperand>(
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl Iterator<Item = Result<O>>,
+    mut out: impl std::io::Write,
+) -> Result<()> {
+    let names = options.grouped.as_deref().unwrap_or_default();
+    let mut set = every_line::<Files, O>(options, first_operand, rest)?;
+    set.retain(|files| files == 1 && options.in_bounds(files));
+    arrange(&mut set, options);
+    let mut groups = vec![Vec::new(); names.len()];
+    for (line, files) in set.iter() {
+        let operand = files.file_number as usize;
+        if operand >= groups.len() {
+            groups.resize_with(operand + 1, Vec::new);
+        }
+        groups[operand].push(line);
+    }
+    out.write_all(options.line_format.bom(set.bom))?;
+    for (operand, lines) in groups.iter().enumerate() {
+        let name = names.get(operand).map_or("-", String::as_str);
+        write!(out, "== only {name} ==")?;
+        out.write_all(&set.line_terminator)?;
+        for line in lines {
+            options.write_line(line, &mut out)?;
+            out.write_all(&set.line_terminator)?;
+        }
+    }
+    options.tally.wrote(set.len() as u64);
+    out.flush()?;
+    Ok(())
+}
+
+/// `Multiple` and `MultipleByFile` retain those lines where the relevant count is
+/// greater than `1` and within the bounds set in `options`.
+fn keep_multiple<B: Bookkeeping, O: LaterOperand>(
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl Iterator<Item = Result<O>>,
+    out: impl std::io::Write,
+) -> Result<()> {
+    let mut set = every_line::<B, O>(options, first_operand, rest)?;
+    set.retain(|occurences| options.invert != (occurences > 1 && options.in_bounds(occurences)));
+    output_and_discard(set, options, out)
+}
+
+/// `Majority` retains those lines present in more than half of the files. (As
+/// for `Intersect`, we count the files as we read them, so that if reading is
+/// interrupted, the majority is of the files read so far.)
+fn majority<B: Bookkeeping, O: LaterOperand>(
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl Iterator<Item = Result<O>>,
+    out: impl std::io::Write,
+) -> Result<()> {
+    let mut all_files = 1_u32;
+    let rest = rest.inspect(|_| all_files += 1);
+    let mut set = every_line::<B, O>(options, first_operand, rest)?;
+    set.retain(|files_containing_line| {
+        options.invert
+            != (files_containing_line.saturating_mul(2) > all_files
+                && options.in_bounds(files_containing_line))
+    });
+    output_and_discard(set, options, out)
+}
+
+/// For the "subtractive" operations `Diff` and `Intersect`, we insert only
+/// those lines in the first input file into the `ZetSet`. `ZetSet::new` will
+/// call `b.update_with(item)` on the line's bookkeeping item `b` if the line is
+/// already present in the `ZetSet`.
+///
+/// Lines in the remaining files are only used to reduce the output, so we call
+/// `set.update_if_present` to call `b.update_with(item)` when the line is
+/// present in the `ZetSet` will bookkeeping value `b`.
+///
+/// Then the caller of `first_file_lines` can then use `set.retain()` to examine
+/// the each line's bookkeeping item to decide whether or not it belongs in the
+/// set.
+fn first_file_lines<'data, B: Bookkeeping, O: LaterOperand>(
+    options: &Options,
+    first_operand: &'data [u8],
+    rest: impl Iterator<Item = Result<O>>,
+) -> Result<ZetSet<'data, B>> {
+    let mut item = B::new();
+    let mut set = ZetSet::with_options(first_operand, item, options)?;
+    for operand in options.interrupt.cut_short(rest) {
+        item.next_file();
+        set.update_if_present(operand?, item)?;
+    }
+    options.tally.distinct(set.len() as u64);
+    Ok(set)
+}
+
+/// `Diff` retains only those lines seen only in the first file. Since
+/// `first_file_lines` only includes lines from the first file, we can
+/// equivalently retain those lines whose file count is `1`.
+///
+/// With `options.invert`, we want the lines of every file, less those. The
+/// first file's lines come first in the set, so a line's index tells us
+/// whether it's in the first file.
+fn diff<B: Bookkeeping, O: LaterOperand>(
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl Iterator<Item = Result<O>>,
+    out: impl std::io::Write,
+) -> Result<()> {
+    let first_file_only = 1;
+    if options.invert {
+        let item = B::new();
+        let set = ZetSet::with_options(first_operand, item, options)?;
+        let first_file_lines = set.len();
+        let mut set = every_later_line(options, set, item, rest)?;
+        set.retain_indexed(|index, files_containing_line| {
+            let in_first_file = index < first_file_lines;
+            !(in_first_file
+                && files_containing_line == first_file_only
+                && options.in_bounds(files_containing_line))
+        });
+        return output_and_discard(set, options, out);
+    }
+    let mut set = first_file_lines::<B, O>(options, first_operand, rest)?;
+    set.retain(|files_containing_line| {
+        files_containing_line == first_file_only && options.in_bounds(files_containing_line)
+    });
+    output_and_discard(set, options, out)
+}
+
+/// `Intersect` retains only those lines whose file count is the same as the
+/// number of input files. (We count the files as `first_file_lines` reads
+/// them, so that if reading is interrupted we retain the lines present in
+/// every file read so far.)
+///
+/// With `options.in_at_least` other than the number of files, a line needn't
+/// be in the first file, so we collect `every_line` and retain those in enough
+/// files — none, if the threshold is more files than there are. (A threshold
+/// of every file is just the usual intersection.) So too
+/// with `options.invert`, since the lines that aren't in every file needn't be
+/// in the first one.
+fn intersect<B: Bookkeeping, O: LaterOperand>(
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl ExactSizeIterator<Item = Result<O>>,
+    out: impl std::io::Write,
+) -> Result<()> {
+    let operands = rest.len() + 1;
+    let enough_files = options.in_at_least.map(|threshold| threshold.files(operands));
+    if let Some(enough_files) = enough_files.filter(|&files| files as usize != operands) {
+        let mut set = every_line::<B, O>(options, first_operand, rest)?;
+        set.retain(|files_containing_line| {
+            options.invert
+                != (files_containing_line >= enough_files
+                    && options.in_bounds(files_containing_line))
+        });
+        return output_and_discard(set, options, out);
+    }
+    let mut all_files = 1_u32;
+    let rest = rest.inspect(|_| all_files += 1);
+    if options.invert {
+        let mut set = every_line::<B, O>(options, first_operand, rest)?;
+        set.retain(|files_containing_line| {
+            files_containing_line != all_files || !options.in_bounds(files_containing_line)
+        });
+        return output_and_discard(set, options, out);
+    }
+    let mut set = first_file_lines::<B, O>(options, first_operand, rest)?;
+    set.retain(|files_containing_line| {
+        files_containing_line == all_files && options.in_bounds(files_containing_line)
+    });
+    output_and_discard(set, options, out)
+}
+
+/// `Top` prints lines with the number of times each occurs, most frequent
+/// first (and in order of first appearance for equal counts), stopping after
+/// `options.top` lines if that's set. With `options.approx` set, the `top`
+/// module estimates the most frequent lines with a bounded number of counters,
+/// instead of our counting every distinct line exactly.
+fn top<O: LaterOperand>(
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl Iterator<Item = Result<O>>,
+    out: impl std::io::Write,
+) -> Result<()> {
+    if let Some(counters) = options.approx {
+        return crate::top::approximate(options, counters, first_operand, rest, out);
+    }
+    let set = every_line::<Log<Lines>, O>(options, first_operand, rest)?;
+    let mut lines: Vec<_> = set.iter().collect();
+    lines.sort_by_key(|(_, item)| Reverse(item.log_value()));
+    lines.truncate(options.top.unwrap_or(usize::MAX));
+    write_annotated(&set, lines.into_iter(), options, out)
+}
+
+/// `Stats` prints statistics of the distribution of line counts — entropy,
+/// Gini coefficient, and percentiles, calculated by the `stats` module — rather
+/// than the lines themselves.
+fn stats<O: LaterOperand>(
+    options: &Options,
+    first_operand: &[u8],
+    rest: impl Iterator<Item = Result<O>>,
+    out: impl std::io::Write,
+) -> Result<()> {
+    let set = every_line::<Log<Lines>, O>(options, first_operand, rest)?;
+    let counts = set.iter().map(|(_, item)| u64::from(item.log_value())).collect();
+    crate::stats::Frequencies::of(counts).write(options, &set.line_terminator, out)
+}
+
+/// Put the lines of `set` in the order `options` asks for: bytewise with
+/// `options.sort`, at random with `options.shuffle`, or by last appearance
+/// with `Order::LastSeen`. Otherwise they stay in order of first appearance.
+/// With `options.sample`, first keep just a random sample of the lines; with
+/// `options.head`, keep just the first.
+pub(crate) fn arrange<B: Bookkeeping>(set: &mut ZetSet<B>, options: &Options) {
+    if let Some(sample) = options.sample {
+        let kept = sample.chosen(set.len());
+        set.retain_indexed(|index, _| kept[index]);
+    }
+    if options.sort {
+        set.sort_lines();
+    } else if let Some(seed) = options.shuffle {
+        set.shuffle(seed);
+    } else if options.order == Order::LastSeen {
+        set.sort_by_last_seen();
+    }
+    // With `options.count_order`, the first lines are those with the highest
+    // (or lowest) counts, which `output_zet_set_annotated` keeps
+    if let (Some(head), None) = (options.head, options.count_order) {
+        set.retain_indexed(|index, _| index < head);
+    }
+}
+
+/// When we've finished constructing the `ZetSet`, we write its lines to our
+/// output and drop it. (Leaking it would be slightly faster for a program about
+/// to exit, but a long-running caller may call `calculate` again and again.)
+fn output_and_discard<B: Bookkeeping>(
+    mut set: ZetSet<B>,
+    options: &Options,
+    out: impl std::io::Write,
+) -> Result<()> {
+    arrange(&mut set, options);
+    B::output_zet_set(&set, options, out)
+}
+
+/// We use the `Unsifted` struct for the `Union` operation when logging isn't needed.
+/// `Union` includes every line seen and doesn't need bookkeeping for anything
+/// but such logging.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Unsifted();
+impl Bookkeeping for Unsifted {
+    fn new() -> Self {
+        Unsifted()
+    }
+    fn next_file(&mut self) {}
+    fn update_with(&mut self, _other: Self) {}
+    fn retention_value(self) -> u32 {
+        0
+    }
+}
+
+/// For `Single` and `Multiple` each line's `Lines` item will keep track of
+/// how many times it has appeared in the entire input. `Lines` can also be
+/// used for reporting the number of times each line appears in the input.
+///
+/// `Lines` is a thin wrapper around `u32`. It ignores `next_file`, and uses
+/// `update_with` only to add to its `u32` element. We use a saturating
+/// addition, because `Single` and `Multiple` care only whether the `u32` is
+/// `1` or greater than `1`, and for logging purposes it seems better to report
+/// overflow for lines that appear `u32::MAX` times or more than to stop `zet`
+/// completely.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Lines(u32);
+impl Bookkeeping for Lines {
+    /// Returns `Lines(1)` because when we insert a fresh line into the `ZetSet`
+    /// we've seen it once.
+    fn new() -> Self {
+        Lines(1)
+    }
+
+    /// `next_file` does nothing because `Lines` isn't affected by the number of
+    /// files we've seen.
+    fn next_file(&mut self) {}
+
+    /// When `update_with` is called, it means we've seen the line an additional
+    /// time — or, with `--counted-input`, an additional `other.0` times. We add
+    /// `other`'s count to ours (with `saturating_add` so we don't wrap around).
+    fn update_with(&mut self, other: Self) {
+        self.0 = self.0.saturating_add(other.0);
+    }
+
+    /// A line with count `weight` stands for `weight` occurrences.
+    fn weighted(self, weight: u32) -> Self {
+        Lines(weight)
+    }
+
+    /// Our `retention_value` is just the `u32` element.
+    fn retention_value(self) -> u32 {
+        self.0
+    }
+}
+impl Loggable for Lines {
+    const COUNT_COLUMNS: &'static [&'static str] = &["count"];
+
+    /// Our `log_value` is the same as our `retention_value`: the underlying
+    /// `u32` element.
+    fn log_value(self) -> u32 {
+        self.retention_value()
+    }
+
+    /// Write our `log_value`. But if that is `u32::MAX`, write `overflow`
+    /// instead, since we might actually have seen more than `u32::MAX` lines.
+    fn write_log(&self, columns: &CountColumns, out: &mut impl std::io::Write) -> Result<()> {
+        if self.0 == u32::MAX {
+            columns.write_overflow(out)
+        } else {
+            columns.write(self.0.into(), out)
+        }
+    }
+}
+/// For `Diff`, `Intersect`, `SingleByFile`, and `MultipleByFile`, each line's
+/// `Files` item will keep track of how many files the line has appeared in.
+/// `Files` can also be used to report the file count information for operatons
+/// whose selection criteria are different from number of files.
+///
+/// The `Files` struct has `file_number` and `files_seen` fields.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Files {
+    file_number: u32,
+    files_seen: u32,
+}
+impl Bookkeeping for Files {
+    /// Returns `Files { file_number: 0, files_seen: 1 }` — `file_number` acts
+    /// as an ID number, different for each operand, while `files_seen` counts
+    /// the number of files this line has been seen to occur in.
+    fn new() -> Self {
+        Files { file_number: 0, files_seen: 1 }
+    }
+
+    /// Increment the `file_number` field — with `wrapping_add(1)` because we
+    /// trust `calculate` to have bailed if there are more than `u32::MAX` file
+    /// operands.
+    fn next_file(&mut self) {
+        self.file_number = self.file_number.wrapping_add(1);
+    }
+
+    /// If a line is already present in the `ZetSet`, with bookkeeping value
+    /// `b`, and `other.file_number` is different from `b.file_number`, we
+    /// update `b.file_number` and increment `b.files_seen`.
+    fn update_with(&mut self, other: Self) {
+        if other.file_number != self.file_number {
+            self.files_seen += 1;
+            self.file_number = other.file_number;
+        }
+    }
+
+    /// Our `retention_value` is the `files_seen` field.
+    fn retention_value(self) -> u32 {
+        self.files_seen
+    }
+}
+impl Loggable for Files {
+    const COUNT_COLUMNS: &'static [&'static str] = &["files"];
+
+    /// Our `log_value` is the same as our `retention_value` — `files_seen`.
+    fn log_value(self) -> u32 {
+        self.retention_value()
+    }
+
+    /// We write `files_seen`.
+    fn write_log(&self, columns: &CountColumns, out: &mut impl std::io::Write) -> Result<()> {
+        columns.write(self.files_seen.into(), out)
+    }
+}
+
+/// For `--count-split`, the `Split` type counts the times a line appears in the
+/// first operand and in all later operands separately, so we can tell whether a
+/// difference between them is 1-vs-0 or 500-vs-499.
+///
+/// The bookkeeping item for a freshly inserted line counts that one occurrence,
+/// in `first` for a line of the first operand and in `rest` otherwise. Both
+/// counts use saturating arithmetic, like `Lines`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Split {
+    first: u32,
+    rest: u32,
+}
+impl Bookkeeping for Split {
+    /// Returns `Split { first: 1, rest: 0 }`, for a line seen once in the first
+    /// operand.
+    fn new() -> Self {
+        Split { first: 1, rest: 0 }
+    }
+
+    /// Lines of every operand after the first count toward `rest`.
+    fn next_file(&mut self) {
+        *self = Split { first: 0, rest: 1 };
+    }
+
+    /// Add `other`'s occurrences to the appropriate count.
+    fn update_with(&mut self, other: Self) {
+        self.first = self.first.saturating_add(other.first);
+        self.rest = self.rest.saturating_add(other.rest);
+    }
+
+    /// A line with count `weight` stands for `weight` occurrences, in whichever
+    /// count it belongs to.
+    fn weighted(self, weight: u32) -> Self {
+        Split { first: self.first.saturating_mul(weight), rest: self.rest.saturating_mul(weight) }
+    }
+
+    /// Our `retention_value` is the total number of times we've seen the line.
+    fn retention_value(self) -> u32 {
+        self.first.saturating_add(self.rest)
+    }
+}
+impl Loggable for Split {
+    const COUNT_COLUMNS: &'static [&'static str] = &["first", "rest"];
+
+    /// Our `log_value` is the larger of the two counts, since it's only used
+    /// to find the width of the count columns.
+    fn log_value(self) -> u32 {
+        self.first.max(self.rest)
+    }
+
+    /// We write two columns, `first` and then `rest`, writing `overflow` for a
+    /// count of `u32::MAX`, as `Lines` does.
+    fn write_log(&self, columns: &CountColumns, out: &mut impl std::io::Write) -> Result<()> {
+        for count in [self.first, self.rest] {
+            if count == u32::MAX {
+                columns.write_overflow(out)?
+            } else {
+                columns.write(count.into(), out)?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// For `--count-lines --count-files` together, `LinesAndFiles` counts both the
+/// times a line appears and the files it appears in, and logs both counts.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct LinesAndFiles {
+    lines: Lines,
+    files: Files,
+}
+impl Bookkeeping for LinesAndFiles {
+    /// Returns a line seen once, in one file.
+    fn new() -> Self {
+        LinesAndFiles { lines: Lines::new(), files: Files::new() }
+    }
+
+    /// Our `next_file` method calls `next_file` for both counts.
+    fn next_file(&mut self) {
+        self.lines.next_file();
+        self.files.next_file();
+    }
+
+    /// Our `update_with` method updates each count with `other`'s.
+    fn update_with(&mut self, other: Self) {
+        self.lines.update_with(other.lines);
+        self.files.update_with(other.files);
+    }
+
+    /// Weighting changes the count of lines, but not of files.
+    fn weighted(self, weight: u32) -> Self {
+        LinesAndFiles { lines: self.lines.weighted(weight), ..self }
+    }
+
+    /// Our `retention_value` is the number of times we've seen the line.
+    fn retention_value(self) -> u32 {
+        self.lines.retention_value()
+    }
+}
+impl Loggable for LinesAndFiles {
+    const COUNT_COLUMNS: &'static [&'static str] = &["count", "files"];
+
+    /// Our `log_value` is the count of lines, which is never less than the
+    /// count of files, so serves for the width of both columns.
+    fn log_value(self) -> u32 {
+        self.lines.log_value()
+    }
+
+    /// We write two columns: the count of lines, and then of files.
+    fn write_log(&self, columns: &CountColumns, out: &mut impl std::io::Write) -> Result<()> {
+        self.lines.write_log(columns, out)?;
+        self.files.write_log(columns, out)
+    }
+}
+
+/// The `Log` newtype delegates everything except `output_zet_set` to its
+/// sole element, and overrides `output_zet_set` to call
+/// `output_zet_set_annotated`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Log<B: Loggable>(B);
+impl<B: Loggable> Bookkeeping for Log<B> {
+    fn new() -> Self {
+        Self(B::new())
+    }
+    fn next_file(&mut self) {
+        self.0.next_file()
+    }
+    fn update_with(&mut self, other: Self) {
+        self.0.update_with(other.0)
+    }
+    fn weighted(self, weight: u32) -> Self {
+        Self(self.0.weighted(weight))
+    }
+    fn retention_value(self) -> u32 {
+        self.0.retention_value()
+    }
+    fn output_zet_set(
+        set: &ZetSet<Self>,
+        options: &Options,
+        out: impl std::io::Write,
+    ) -> Result<()> {
+        output_zet_set_annotated(set, options, out)
+    }
+}
+impl<B: Loggable> Loggable for Log<B> {
+    const COUNT_COLUMNS: &'static [&'static str] = B::COUNT_COLUMNS;
+    fn log_value(self) -> u32 {
+        self.0.log_value()
+    }
+    fn write_log(&self, columns: &CountColumns, out: &mut impl std::io::Write) -> Result<()> {
+        self.0.write_log(columns, out)
+    }
+}
+
+/// The two `Loggable` methods are used in `output_zet_set_annotated`, and the
+/// `Log<X>` and `SiftLog<X,Y>` types override `output_zet_set` to call
+/// `output_zet_set_annotated` for the actual logging. With
+/// `options.count_order`, the lines are sorted by their counts first (and with
+/// `options.head`, only the first of them kept).
+fn output_zet_set_annotated<B: Loggable>(
+    set: &ZetSet<B>,
+    options: &Options,
+    out: impl std::io::Write,
+) -> Result<()> {
+    let Some(order) = options.count_order else {
+        return write_annotated(set, set.iter(), options, out);
+    };
+    let mut lines: Vec<_> = set.iter().collect();
+    match order {
+        CountOrder::Descending => lines.sort_by_key(|(_, item)| Reverse(item.log_value())),
+        CountOrder::Ascending => lines.sort_by_key(|(_, item)| item.log_value()),
+    }
+    lines.truncate(options.head.unwrap_or(usize::MAX));
+    write_annotated(set, lines.into_iter(), options, out)
+}
+
+/// Write `lines` (from `set`, but not necessarily all of them, or in order)
+/// with their counts.
+fn write_annotated<'a, B: Loggable + 'a>(
+    set: &ZetSet<B>,
+    lines: impl Iterator<Item = (&'a [u8], &'a B)> + Clone,
+    options: &Options,
+    mut out: impl std::io::Write,
+) -> Result<()> {
+    if lines.clone().next().is_none() && !options.header_row {
+        return Ok(());
+    }
+    let columns = CountColumns::new(options, lines.clone().map(|(_, v)| v.log_value().into()));
+    out.write_all(options.line_format.bom(set.bom))?;
+    write_header(B::COUNT_COLUMNS, options, &set.line_terminator, &mut out)?;
+    let mut written = 0;
+    for (line, item) in lines {
+        let write_counts = |out: &mut _| item.write_log(&columns, out);
+        columns.write_row(line, options, &set.line_terminator, &mut out, write_counts)?;
+        written += 1;
+    }
+    options.tally.wrote(written);
+    out.flush()?;
+    Ok(())
+}
+
+/// For `--where`, write each line of `set` once for each place it occurs, as
+/// `name:number:line`, like `grep -n` — where `name` is the name of the
+/// operand it occurs in, and `number` its line number there.
+fn write_locations<B: Bookkeeping>(
+    set: &ZetSet<B>,
+    locations: &Locations,
+    options: &Options,
+    mut out: impl std::io::Write,
+) -> Result<()> {
+    out.write_all(options.line_format.bom(set.bom))?;
+    for (index, line) in set.lines().enumerate() {
+        for position in set.positions_of(index) {
+            let operand = usize::try_from(position.operand).unwrap_or(usize::MAX);
+            let name = locations.names.get(operand).map_or("-", String::as_str);
+            write!(out, "{name}:{}:", position.line)?;
+            options.write_line(line, &mut out)?;
+            out.write_all(&set.line_terminator)?;
+        }
+    }
+    options.tally.wrote(set.len() as u64);
+    out.flush()?;
+    Ok(())
+}
+
+/// For `--show-files`, write each line of `set` after a comma-separated list of
+/// the `names` of the operands it occurs in (and the output's column delimiter)
+/// — or with `options.with_filename`, after the name of the first and `:`
+fn write_with_files<B: Bookkeeping>(
+    set: &ZetSet<B>,
+    names: &[String],
+    options: &Options,
+    mut out: impl std::io::Write,
+) -> Result<()> {
+    out.write_all(options.line_format.bom(set.bom))?;
+    write_header(&["files"], options, &set.line_terminator, &mut out)?;
+    for (index, line) in set.lines().enumerate() {
+        let operands = set.operands_containing(index);
+        let mut containing = operands.map(|n| names.get(n as usize).map_or("-", String::as_str));
+        if options.with_filename {
+            write!(out, "{}:", containing.next().unwrap_or("-"))?;
+        } else {
+            out.write_all(containing.collect::<Vec<_>>().join(",").as_bytes())?;
+            out.write_all(&[options.output_format.delimiter()])?;
+        }
+        options.write_line(line, &mut out)?;
+        out.write_all(&set.line_terminator)?;
+    }
+    options.tally.wrote(set.len() as u64);
+    out.flush()?;
+    Ok(())
+}
+
+/// For `--count-per-file`, write each line of `set` after a column for each of
+/// the `names` of the operands, giving the times it occurs in that operand
+fn write_counts_per_file<B: Bookkeeping>(
+    set: &ZetSet<B>,
+    names: &[String],
+    options: &Options,
+    mut out: impl std::io::Write,
+) -> Result<()> {
+    let counts = (0..set.len()).flat_map(|index| set.counts_in_operands(index));
+    let columns = CountColumns::new(options, counts.map(|&(_, count)| count.into()));
+    out.write_all(options.line_format.bom(set.bom))?;
+    let header: Vec<_> = names.iter().map(String::as_str).collect();
+    write_header(&header, options, &set.line_terminator, &mut out)?;
+    let mut row = vec![0; names.len()];
+    for (index, line) in set.lines().enumerate() {
+        row.fill(0);
+        for &(operand, count) in set.counts_in_operands(index) {
+            if let Some(cell) = row.get_mut(operand as usize) {
+                *cell = count;
+            }
+        }
+        let write_counts =
+            |out: &mut _| row.iter().try_for_each(|&count| columns.write(count.into(), out));
+        columns.write_row(line, options, &set.line_terminator, &mut out, write_counts)?;
+    }
+    options.tally.wrote(set.len() as u64);
+    out.flush()?;
+    Ok(())
+}
+
+/// A `SiftLog<Sifted, Logged>` struct tracks a `Bookkeeping` item of type
+/// `Sifted` and a `Loggable` item of type `Logged`. The latter will be used to
+/// print a count for each line, either the number of times the line appeared in
+/// the input, or the number of files it appeared in. We use the
+/// `retention_value` of `Sifted` and the `log_value` and `write_log` methods of
+/// `Logged`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct SiftLog<Sifted: Bookkeeping, Logged: Loggable> {
+    sift: Sifted,
+    log: Logged,
+}
+impl<Sifted: Bookkeeping, Logged: Loggable> Bookkeeping for SiftLog<Sifted, Logged> {
+    /// Returns `SiftLog { sift: Sifted::new(), log: Logged::new() }` —
+    /// freshly inserted lines will have a bookkeeping item suitable for both
+    /// sifting and logging.
+    fn new() -> Self {
+        SiftLog { sift: Sifted::new(), log: Logged::new() }
+    }
+
+    /// Our `next_file` method calls `next_file` for both its fields.
+    fn next_file(&mut self) {
+        self.sift.next_file();
+        self.log.next_file()
+    }
+
+    /// Our `update_with` method calls `update_with` for both its fields,
+    /// sending `other.sift` to our `sift` field and `other.log` to our `log`
+    /// field.
+    fn update_with(&mut self, other: Self) {
+        self.sift.update_with(other.sift);
+        self.log.update_with(other.log);
+    }
+
+    /// Our `weighted` method weights both fields.
+    fn weighted(self, weight: u32) -> Self {
+        SiftLog { sift: self.sift.weighted(weight), log: self.log.weighted(weight) }
+    }
+
+    /// Our `retention_value` is our **`sift` field's** retention value.
+    fn retention_value(self) -> u32 {
+        self.sift.retention_value()
+    }
+
+    /// We override `output_zet_set` to use `output_zet_set_annotated`.
+    fn output_zet_set(
+        set: &ZetSet<Self>,
+        options: &Options,
+        out: impl std::io::Write,
+    ) -> Result<()> {
+        output_zet_set_annotated(set, options, out)
+    }
+}
+impl<Sifted: Bookkeeping, Logged: Loggable> Loggable for SiftLog<Sifted, Logged> {
+    /// Our count columns are our **`log` field's** count columns.
+    const COUNT_COLUMNS: &'static [&'static str] = Logged::COUNT_COLUMNS;
+
+    /// Our `log_value` is our **`log` field's** log value.
+    fn log_value(self) -> u32 {
+        self.log.log_value()
+    }
+
+    /// For `write_log` we output our `log` field's log value.
+    fn write_log(&self, columns: &CountColumns, out: &mut impl std::io::Write) -> Result<()> {
+        self.log.write_log(columns, out)
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bstr::ByteSlice;
+    use indexmap::IndexMap;
+
+    impl LaterOperand for &[u8] {
+        fn for_byte_line(self, for_each_line: impl FnMut(&[u8])) -> Result<()> {
+            self.lines().for_each(for_each_line);
+            Ok(())
+        }
+    }
+
+    type V8<'a> = [&'a [u8]];
+    fn calc(operation: OpName, operands: &V8) -> String {
+        let first = operands[0];
+        let rest = operands[1..].iter().map(|o| Ok(*o));
+        let mut answer = Vec::new();
+        calculate(&Options::new(operation), first, rest, &mut answer).unwrap();
+        String::from_utf8(answer).unwrap()
+    }
+
+    #[test]
+    fn given_a_single_argument_all_most_ops_return_input_lines_in_order_without_dups() {
+        let arg: Vec<&[u8]> = vec![b"xxx\nabc\nxxx\nyyy\nxxx\nabc\n"];
+        let uniq = "xxx\nabc\nyyy\n";
+        let solo = "yyy\n";
+        let multi = "xxx\nabc\n";
+        let empty = "";
+        for &op in &[Intersect, Union, Diff, Single, SingleByFile, Multiple, MultipleByFile] {
+            let result = calc(op, &arg);
+            let expected = if op == Single {
+                solo
+            } else if op == Multiple {
+                multi
+            } else if op == MultipleByFile {
+                empty
+            } else {
+                uniq
+            };
+            assert_eq!(result, *expected, "for {op:?}");
+        }
+    }
+    #[test]
+    fn results_for_each_operation() {
+        let args: Vec<&[u8]> = vec![
+            b"xyz\nabc\nxy\nxz\nx\n",    // Strings containing "x" (and "abc")
+            b"xyz\nabc\nxy\nyz\ny\ny\n", // Strings containing "y" (and "abc")
+            b"xyz\nabc\nxz\nyz\nz\n",    // Strings containing "z" (and "abc")
+        ];
+        assert_eq!(calc(Union, &args), "xyz\nabc\nxy\nxz\nx\nyz\ny\nz\n", "for {Union:?}");
+        assert_eq!(calc(Intersect, &args), "xyz\nabc\n", "for {Intersect:?}");
+        assert_eq!(calc(Diff, &args), "x\n", "for {Diff:?}");
+        assert_eq!(calc(Single, &args), "x\nz\n", "for {Single:?}");
+        assert_eq!(calc(SingleByFile, &args), "x\ny\nz\n", "for {SingleByFile:?}");
+        assert_eq!(calc(Multiple, &args), "xyz\nabc\nxy\nxz\nyz\ny\n", "for {Multiple:?}");
+        assert_eq!(calc(MultipleByFile, &args), "xyz\nabc\nxy\nxz\nyz\n", "for {MultipleByFile:?}");
+    }
+
+    #[test]
+    fn an_interrupt_stops_only_the_run_it_was_given_to() {
+        let interrupted = Options::new(Union);
+        let _ = interrupted.interrupt.request();
+        let rest = [&b"b\n"[..]].into_iter().map(Ok);
+        let mut answer = Vec::new();
+        calculate(&interrupted, b"a\n", rest, &mut answer).unwrap();
+        assert_eq!(answer.to_str().unwrap(), "");
+        assert_eq!(calc(Union, &[b"a\n", b"b\n"]), "a\nb\n");
+    }
+
+    #[test]
+    fn each_call_of_calculate_returns_its_own_statistics() {
+        let options = Options::new(Union);
+        let run = |rest: &V8| {
+            let rest = rest.iter().map(|o| Ok(*o));
+            calculate(&options, b"a\nb\na\n", rest, std::io::sink()).unwrap()
+        };
+        let expected = summary::Stats {
+            read_by_operand: vec![3, 2],
+            lines_written: 3,
+            distinct_lines: Some(3),
+        };
+        assert_eq!(run(&[b"c\nb\n"]), expected);
+        assert_eq!(run(&[b"c\nb\n"]), expected);
+        let alone = run(&[]);
+        assert_eq!((alone.lines_read(), alone.lines_written), (3, 2));
+        assert_eq!(options.tally.stats(), summary::Stats::default());
+    }
+
+    #[test]
+    fn operations_can_be_named() {
+        // Counted lines, so `MergeCounts` can merge them
+        let args: Vec<&[u8]> = vec![b"1 a\n2 b\n1 c\n", b"1 b\n1 c\n3 d\n", b"1 c\n"];
+        for op in OpName::ALL {
+            assert_eq!(op.to_string().parse::<OpName>(), Ok(op));
+            let mut answer = Vec::new();
+            let rest = args[1..].iter().map(|o| Ok(*o));
+            calculate_named(op.name(), &Options::new(Union), args[0], rest, &mut answer).unwrap();
+            assert_eq!(String::from_utf8(answer).unwrap(), calc(op, &args), "for {op}");
+        }
+        assert_eq!("Single-By-File".parse::<OpName>(), Ok(SingleByFile));
+        let err = "xor".parse::<OpName>().unwrap_err().to_string();
+        assert!(err.starts_with("Unknown operation \"xor\""), "{err}");
+    }
+
+    #[test]
+    fn merge_counts_sums_the_counts_of_each_line() {
+        let args: Vec<&[u8]> = vec![b"   3 a\n  10 b\n", b"2 b\n1 c\n7 a\n"];
+        assert_eq!(calc(MergeCounts, &args), "10 a\n12 b\n 1 c\n");
+        let rest = [Ok(&b"not counted\n"[..])].into_iter();
+        let err = calculate(&Options::new(MergeCounts), args[0], rest, std::io::sink());
+        assert!(err.unwrap_err().to_string().contains("not counted"));
+    }
+
+    #[test]
+    fn fallible_allocation_gives_the_same_results() {
+        let args: Vec<&[u8]> =
+            vec![b"1 xyz\n1 abc\n2 xy\n1 xz\n1 x\n", b"1 xyz\n3 abc\n2 xy\n1 y\n1 y\n", b"1 z\n"];
+        for op in OpName::ALL {
+            let options = Options { fallible_allocation: true, ..Options::new(op) };
+            let rest = args[1..].iter().map(|o| Ok(*o));
+            let mut answer = Vec::new();
+            calculate(&options, args[0], rest, &mut answer).unwrap();
+            assert_eq!(String::from_utf8(answer).unwrap(), calc(op, &args), "for {op}");
+        }
+    }
+
+    // Test `LogType::Lines` and `LogType::Files' output
+    type CountMap = IndexMap<String, u32>;
+    fn counted(operation: OpName, count: LogType, operands: &V8) -> CountMap {
+        let first = operands[0];
+        let rest = operands[1..].iter().map(|o| Ok(*o));
+        let mut answer = Vec::new();
+        let options = Options { log_type: count, ..Options::new(operation) };
+        calculate(&options, first, rest, &mut answer).unwrap();
+
+        let mut result = CountMap::new();
+        for line in String::from_utf8(answer).unwrap().lines() {
+            let line = line.trim_start();
+            let v: Vec<_> = line.splitn(2, ' ').collect();
+            let count: u32 = v[0].parse().unwrap();
+            result.insert(v[1].to_string(), count);
+        }
+        result
+    }
+    fn lines(operands: &V8) -> CountMap {
+        let mut result = CountMap::new();
+        for &operand in operands {
+            let operand = String::from_utf8(operand.to_vec()).unwrap();
+            for line in operand.lines() {
+                result.entry(line.to_string()).and_modify(|c| *c += 1).or_insert(1);
+            }
+        }
+        result
+    }
+    fn files(operands: &V8) -> CountMap {
+        let mut result = CountMap::new();
+        for &operand in operands {
+            let operand = String::from_utf8(operand.to_vec()).unwrap();
+            let mut seen = CountMap::new();
+            for line in operand.lines() {
+                seen.insert(line.to_string(), 1);
+            }
+            for line in seen.into_keys() {
+                result.entry(line).and_modify(|c| *c += 1).or_insert(1);
+            }
+        }
+        result
+    }
+    #[test]
+    fn check_line_count() {
+        let args: Vec<&[u8]> = vec![
+            b"xyz\nabc\nxy\nxz\nx\n",    // Strings containing "x" (and "abc")
+            b"xyz\nabc\nxy\nyz\ny\ny\n", // Strings containing "y" (and "abc")
+            b"xyz\nabc\nxz\nyz\nz\n",    // Strings containing "z" (and "abc")
+        ];
+        let line_count = lines(&args);
+        for &op in &[Intersect, Union, Diff, Single, SingleByFile, Multiple, MultipleByFile] {
+            let result = counted(op, LogType::Lines, &args);
+            for line in result.keys() {
+                assert_eq!(result.get(line), line_count.get(line));
+            }
+        }
+    }
+    #[test]
+    fn check_file_count() {
+        let args: Vec<&[u8]> = vec![
+            b"xyz\nabc\nxy\nxz\nx\n",    // Strings containing "x" (and "abc")
+            b"xyz\nabc\nxy\nyz\ny\ny\n", // Strings containing "y" (and "abc")
+            b"xyz\nabc\nxz\nyz\nz\n",    // Strings containing "z" (and "abc")
+        ];
+        let file_count = files(&args);
+        for &op in &[Intersect, Union, Diff, Single, SingleByFile, Multiple, MultipleByFile] {
+            let result = counted(op, LogType::Files, &args);
+            for line in result.keys() {
+                assert_eq!(result.get(line), file_count.get(line));
+            }
+        }
+    }
+
+    #[test]
+    fn check_line_and_file_counts() {
+        let args: Vec<&[u8]> = vec![b"a\nb\na\n", b"a\nc\n", b"b\n"];
+        let options = Options { log_type: LogType::LinesAndFiles, ..Options::new(Union) };
+        let mut answer = Vec::new();
+        calculate(&options, args[0], args[1..].iter().map(|o| Ok(*o)), &mut answer).unwrap();
+        assert_eq!(String::from_utf8(answer).unwrap(), "3 2 a\n2 2 b\n1 1 c\n");
+    }
+
+    #[test]
+    fn records_of_any_hashable_type_can_be_combined() {
+        let evens = [2, 4, 6, 8, 2];
+        let small = vec![vec![1, 2, 3, 4], vec![4, 5]];
+        let result = |op| calculate_records(op, evens, small.clone()).unwrap();
+        assert_eq!(result(Union), [2, 4, 6, 8, 1, 3, 5]);
+        assert_eq!(result(Intersect), [4]);
+        assert_eq!(result(Diff), [6, 8]);
+        assert_eq!(result(Single), [6, 8, 1, 3, 5]);
+        assert_eq!(result(Multiple), [2, 4]);
+        assert_eq!(result(SingleByFile), [6, 8, 1, 3, 5]);
+        assert_eq!(result(MultipleByFile), [2, 4]);
+        assert_eq!(result(Majority), [2, 4]);
+        assert!(calculate_records(Top, evens, small).is_err());
+
+        #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+        struct User {
+            id: u32,
+            name: &'static str,
+        }
+        let staff = [User { id: 1, name: "ann" }, User { id: 2, name: "bo" }];
+        let left = [[User { id: 2, name: "bo" }]];
+        assert_eq!(calculate_records(Diff, staff, left).unwrap(), [User { id: 1, name: "ann" }]);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::pedantic)]
+mod test_bookkeeping {
+    use super::*;
+
+    #[test]
+    fn si_abbreviations_have_at_most_three_significant_figures() {
+        let abbreviated: Vec<_> =
+            [0, 999, 1000, 1234, 9_949, 9_999, 34_567, 99_950, 678_499, 999_999]
+                .into_iter()
+                .map(si)
+                .collect();
+        let expected =
+            ["0", "999", "1.0k", "1.2k", "9.9k", "10.0k", "34.6k", "100k", "678k", "1.0M"];
+        assert_eq!(abbreviated, expected);
+        assert_eq!(si(u64::MAX), "18.4E");
+        for count in [0, 999, 1000, 9_999, 99_949, 999_499, 123_456_789, u64::MAX] {
+            assert!(si(count).len() <= CountFormat::Si.width(count.max(10_000)));
+        }
+    }
+
+    #[test]
+    fn shell_quoting_escapes_single_quotes() {
+        let mut quoted = Vec::new();
+        for line in [&b"it's a *.txt"[..], b"", b"'"] {
+            LineFormat::ShellQuoted.write(line, &mut quoted).unwrap();
+            quoted.push(b' ');
+        }
+        assert_eq!(String::from_utf8(quoted).unwrap(), r"'it'\''s a *.txt' '' ''\''' ");
+    }
+
+    #[test]
+    fn intersect_in_at_least_keeps_lines_in_enough_files() {
+        let args: Vec<&[u8]> = vec![b"a\nb\n", b"b\nc\n", b"c\nd\n", b"b\nc\ne\n"];
+        let calc_with = |threshold: &str| {
+            let mut options = Options::new(Intersect);
+            options.in_at_least = Some(threshold.parse().unwrap());
+            let rest = args[1..].iter().map(|o| Ok(*o));
+            let mut answer = Vec::new();
+            calculate(&options, args[0], rest, &mut answer).unwrap();
+            String::from_utf8(answer).unwrap()
+        };
+        assert_eq!(calc_with("3"), "b\nc\n");
+        assert_eq!(calc_with("75%"), "b\nc\n");
+        assert_eq!(calc_with("76%"), "");
+        assert_eq!(calc_with("2"), "b\nc\n");
+        assert_eq!(calc_with("0"), "a\nb\nc\nd\ne\n");
+        assert_eq!(calc_with("4"), "");
+        assert_eq!(calc_with("5"), "");
+        assert_eq!(calc_with("9"), "");
+        assert_eq!(Threshold::Percent(80.0).files(9), 8);
+        assert!("101%".parse::<Threshold>().is_err());
+        assert!("many".parse::<Threshold>().is_err());
+    }
+
+    #[test]
+    fn file_bounds_narrow_the_operations_that_count_files() {
+        let args: Vec<&[u8]> = vec![b"a\nb\nc\n", b"b\nc\n", b"c\nd\n", b"c\nd\ne\n"];
+        let calc_with = |op, min_files, max_files| {
+            let mut options = Options::new(op);
+            (options.min_files, options.max_files) = (min_files, max_files);
+            let rest = args[1..].iter().map(|o| Ok(*o));
+            let mut answer = Vec::new();
+            calculate(&options, args[0], rest, &mut answer).map(|_| answer)
+        };
+        let text = |answer: Result<Vec<u8>>| String::from_utf8(answer.unwrap()).unwrap();
+        assert_eq!(text(calc_with(Union, Some(2), None)), "b\nc\nd\n");
+        assert_eq!(text(calc_with(Union, Some(2), Some(3))), "b\nd\n");
+        assert_eq!(text(calc_with(Union, None, Some(1))), "a\ne\n");
+        assert_eq!(text(calc_with(MultipleByFile, None, Some(2))), "b\nd\n");
+        assert_eq!(text(calc_with(Intersect, None, Some(3))), "");
+        assert_eq!(text(calc_with(Diff, Some(1), Some(1))), "a\n");
+        assert!(calc_with(Multiple, Some(2), None).is_err());
+    }
+
+    #[test]
+    fn grouping_puts_each_files_single_lines_under_its_name() {
+        let args: Vec<&[u8]> = vec![b"a\nb\nb\nc\n", b"c\nd\n", b"c\nb\ne\n"];
+        let mut options = Options::new(SingleByFile);
+        options.grouped = Some(vec!["x".to_string(), "y".to_string(), "z".to_string()]);
+        let rest = args[1..].iter().map(|o| Ok(*o));
+        let mut answer = Vec::new();
+        calculate(&options, args[0], rest, &mut answer).unwrap();
+        let expected = "== only x ==\na\n== only y ==\nd\n== only z ==\ne\n";
+        assert_eq!(String::from_utf8(answer).unwrap(), expected);
+        options.op = Single;
+        assert!(calculate(&options, args[0], args[1..].iter().map(|o| Ok(*o)), Vec::new()).is_err());
+    }
+
+    #[test]
+    fn inverting_prints_the_lines_an_operation_leaves_out() {
+        let args: Vec<&[u8]> = vec![b"a\nb\nb\nc\n", b"c\nd\n", b"c\nb\ne\n"];
+        let inverted = |op| {
+            let mut options = Options::new(op);
+            options.invert = true;
+            let rest = args[1..].iter().map(|o| Ok(*o));
+            let mut answer = Vec::new();
+            calculate(&options, args[0], rest, &mut answer).map(|_| answer)
+        };
+        let text = |answer: Result<Vec<u8>>| String::from_utf8(answer.unwrap()).unwrap();
+        assert_eq!(text(inverted(Union)), "");
+        assert_eq!(text(inverted(Intersect)), "a\nb\nd\ne\n");
+        assert_eq!(text(inverted(Diff)), "b\nc\nd\ne\n");
+        assert_eq!(text(inverted(Single)), "b\nc\n");
+        assert_eq!(text(inverted(MultipleByFile)), "a\nd\ne\n");
+        assert_eq!(text(inverted(Majority)), "a\nd\ne\n");
+        assert!(inverted(Top).is_err());
+    }
+
+    #[test]
+    fn count_bounds_narrow_the_operations_that_count_lines() {
+        let args: Vec<&[u8]> = vec![b"a\nb\nb\nc\nc\nc\n", b"c\nd\nd\nd\nd\n"];
+        let calc_with = |op, min_count, max_count| {
+            let mut options = Options::new(op);
+            (options.min_count, options.max_count) = (min_count, max_count);
+            let rest = args[1..].iter().map(|o| Ok(*o));
+            let mut answer = Vec::new();
+            calculate(&options, args[0], rest, &mut answer).map(|_| answer)
+        };
+        let text = |answer: Result<Vec<u8>>| String::from_utf8(answer.unwrap()).unwrap();
+        assert_eq!(text(calc_with(Multiple, Some(4), None)), "c\nd\n");
+        assert_eq!(text(calc_with(Union, Some(2), Some(3))), "b\n");
+        assert_eq!(text(calc_with(Union, None, Some(1))), "a\n");
+        assert!(calc_with(Intersect, Some(2), None).is_err());
+    }
+
+    #[test]
+    fn csv_quoting_quotes_only_fields_that_need_it() {
+        let mut quoted = Vec::new();
+        for line in [&b"plain text"[..], b"a,b", b"say \"hi\"", b"cr\r", b""] {
+            LineFormat::CsvQuoted.write(line, &mut quoted).unwrap();
+            quoted.push(b'|');
+        }
+        assert_eq!(
+            String::from_utf8(quoted).unwrap(),
+            "plain text|\"a,b\"|\"say \"\"hi\"\"\"|\"cr\r\"||"
+        );
+    }
+
+    #[test]
+    fn line_count_update_with_uses_saturating_increment() {
+        let mut changer = Lines(u32::MAX - 2);
+        let other = Lines::new();
+        assert_eq!(changer.retention_value(), u32::MAX - 2);
+        changer.update_with(other);
+        assert_eq!(changer.retention_value(), u32::MAX - 1);
+        changer.update_with(other);
+        assert_eq!(changer.retention_value(), u32::MAX);
+        changer.update_with(other);
+        assert_eq!(changer.retention_value(), u32::MAX);
+    }
+
+    #[test]
+    fn split_counts_the_first_operand_apart_from_the_rest() {
+        let zet = ZetSet::<Log<Split>>::new(
+            b"a\nb\na\n",
+            Log(Split::new()),
+            Key::whole_line(),
+            Framing::Lines,
+        )
+        .unwrap();
+        let mut result = Vec::new();
+        Log::<Split>::output_zet_set(&zet, &Options::new(Union), &mut result).unwrap();
+        assert_eq!(String::from_utf8(result).unwrap(), "2 0 a\n1 0 b\n");
+
+        let mut item = Split::new();
+        item.next_file();
+        let mut seen = Split::new();
+        seen.update_with(item);
+        seen.update_with(item);
+        assert_eq!(seen, Split { first: 1, rest: 2 });
+        assert_eq!(seen.retention_value(), 3);
+    }
+
+    #[test]
+    fn log_lines_logs_the_string_overflow_for_u32_max() {
+        let zet = ZetSet::<Log<Lines>>::new(
+            b"a\na\na\nb\n",
+            Log(Lines(u32::MAX - 1)),
+            Key::whole_line(),
+            Framing::Lines,
+        )
+        .unwrap();
+        let mut result = Vec::new();
+        Log::<Lines>::output_zet_set(&zet, &Options::new(Union), &mut result).unwrap();
+        let result = String::from_utf8(result).unwrap();
+        assert_eq!(result, format!(" overflow  a\n{} b\n", u32::MAX - 1));
+    }
+}
@@ -0,0 +1,122 @@
+//! Random samples of a set's lines, for `--sample N`, and random orders of
+//! them, for `--shuffle`. We choose which lines to
+//! keep by reservoir sampling (Vitter's Algorithm R): the first `N` lines fill
+//! the reservoir, and then the `i`th line (counting from 0) replaces a random
+//! one of them with probability `N / (i + 1)`. Every line ends up equally likely
+//! to be kept. We shuffle by the Fisher-Yates algorithm, which makes every
+//! order equally likely.
+//!
+//! The random numbers come from a `SplitMix64` generator started from the
+//! given seed, so the same seed and the same input give the same result.
+
+/// With `--sample N --seed S`, print a random sample of `N` of the lines that
+/// would otherwise be printed, chosen with seed `S`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sample {
+    /// The number of lines to keep
+    pub size: usize,
+    /// The seed for the random choice of lines
+    pub seed: u64,
+}
+
+impl Sample {
+    /// Which of `len` entries to keep: a `Vec` with `true` for `self.size` of
+    /// them (or for all of them, if there are no more than that)
+    #[must_use]
+    pub fn chosen(&self, len: usize) -> Vec<bool> {
+        let mut kept = vec![false; len];
+        if len <= self.size {
+            kept.fill(true);
+            return kept;
+        }
+        let mut rng = SplitMix64(self.seed);
+        let mut reservoir: Vec<usize> = (0..self.size).collect();
+        for index in self.size..len {
+            let slot = rng.below(index + 1);
+            if slot < self.size {
+                reservoir[slot] = index;
+            }
+        }
+        for index in reservoir {
+            kept[index] = true;
+        }
+        kept
+    }
+}
+
+/// A random order of `0..len`, shuffled with seed `seed`
+#[must_use]
+pub fn shuffled(len: usize, seed: u64) -> Vec<usize> {
+    let mut rng = SplitMix64(seed);
+    let mut order: Vec<usize> = (0..len).collect();
+    for index in (1..len).rev() {
+        order.swap(index, rng.below(index + 1));
+    }
+    order
+}
+
+/// Steele, Lea, and Flood's `SplitMix64`: small and fast, and plenty random
+/// enough for choosing lines to look at
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A random number less than `n`, by Lemire's multiply-and-shift (whose
+    /// slight bias doesn't matter here)
+    #[allow(clippy::cast_possible_truncation)]
+    fn below(&mut self, n: usize) -> usize {
+        ((u128::from(self.next()) * n as u128) >> 64) as usize
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keeps_everything_when_there_is_no_more_than_the_sample() {
+        let sample = Sample { size: 5, seed: 1 };
+        assert_eq!(sample.chosen(3), vec![true; 3]);
+        assert_eq!(sample.chosen(5), vec![true; 5]);
+    }
+
+    #[test]
+    fn keeps_the_sample_size_reproducibly() {
+        let sample = Sample { size: 10, seed: 42 };
+        let kept = sample.chosen(1000);
+        assert_eq!(kept.iter().filter(|&&k| k).count(), 10);
+        assert_eq!(kept, sample.chosen(1000));
+        assert_ne!(kept, Sample { size: 10, seed: 43 }.chosen(1000));
+    }
+
+    #[test]
+    fn every_entry_can_be_chosen() {
+        let mut seen = [false; 20];
+        for seed in 0..200 {
+            let kept = Sample { size: 3, seed }.chosen(20);
+            for (index, kept) in kept.into_iter().enumerate() {
+                seen[index] |= kept;
+            }
+        }
+        assert!(seen.iter().all(|&s| s));
+    }
+
+    #[test]
+    fn shuffling_is_a_reproducible_permutation() {
+        let order = shuffled(50, 9);
+        let mut sorted = order.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..50).collect::<Vec<_>>());
+        assert_ne!(order, sorted);
+        assert_eq!(order, shuffled(50, 9));
+        assert!(shuffled(0, 9).is_empty());
+    }
+}
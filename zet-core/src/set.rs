@@ -0,0 +1,1102 @@
+//! Provides the `ZetSet` structure, intended to be initialized from the
+//! contents of the first input file.
+use crate::counted;
+use crate::interrupt::Interrupt;
+use crate::key::Key;
+use crate::operations::{Bookkeeping, OpName, Options};
+use crate::sample;
+use anyhow::Result;
+use fxhash::FxBuildHasher;
+use indexmap::{map::Entry, IndexMap};
+use memchr::{memchr, memchr2_iter, memmem};
+use std::borrow::Cow;
+use std::fmt;
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+
+/// A `ZetSet` is a set of lines, each line represented as a key of an `IndexMap`.
+/// * Keys are `Cow<'data, [u8]>`
+/// * Lines inserted from the first file operand are represented as `Cow::Borrowed` keys
+/// * Lines inserted from the second and following files are represented as `Cow::Owned` keys
+/// * Each set operation (`Union`, `Diff`, etc) associates a small bookkeeping value
+///   with each key. The value type differs from operation to operation, and by whether we're
+///   counting the number of times each line appears, or the number of files in which each
+///   lines appears (or if we're not counting either).
+/// * When a `Key` other than the whole line is in use (as with `--key`), the
+///   `IndexMap` keys are the comparison keys, and the `lines` field holds the
+///   first line seen with each key (or with `Keep::Last`, the last), in the
+///   same order, for output. Otherwise `lines` is empty, and the lines are the
+///   `IndexMap` keys themselves.
+/// * A `ZetSet` also keeps information about whether the first file operand began with
+///   a Unicode Byte Order Mark, and what line terminator was used on the first line of
+///   the first file. On output, the `ZetSet` will print a Byte Order Mark if the first
+///   file operand had one, and will use the same line terminator as that file's first
+///   line.
+/// * In fallible-allocation mode, the `ZetSet` uses `try_reserve` to grow, and
+///   returns an `InputTooLarge` error rather than aborting if memory runs out.
+/// * With `--where`, the `positions` field records where each line occurs.
+/// * With `Order::LastSeen`, the `recency` field records when each line was
+///   last seen.
+/// * With `--show-files` or `--count-per-file`, the `containing` field records
+///   the operands each line occurs in, and how many times.
+/// * The key type `K` defaults to `Cow<'data, [u8]>`, and only then does the
+///   `ZetSet` deal in lines. With any other hashable `K` — records parsed by
+///   the caller, say — the methods of the `impl<K>` block below insert and
+///   update keys directly.
+#[derive(Clone, Debug)]
+pub struct ZetSet<'data, B: Bookkeeping, K = Cow<'data, [u8]>> {
+    set: IndexMap<K, B, FxBuildHasher>,
+    key: Key,
+    lines: Vec<Cow<'data, [u8]>>,
+    keep: Keep,
+    fallible: bool,
+    positions: Option<Positions>,
+    recency: Option<Recency>,
+    containing: Option<Containing>,
+    counted: bool,
+    csv_header: Option<Header>,
+    pub bom: &'static [u8],       // Byte Order Mark or empty
+    pub line_terminator: Vec<u8>, // \n, \r\n, or the record delimiter
+}
+type CowSet<'data, B> = IndexMap<Cow<'data, [u8]>, B, FxBuildHasher>;
+
+/// With `--csv`, each operand's first record is its header, which is neither
+/// compared nor printed. `named` is the key, whose named columns (if any) we
+/// look up in each header; `awaited` says whether the next record read is a
+/// header.
+#[derive(Clone, Debug)]
+struct Header {
+    named: Key,
+    awaited: bool,
+}
+
+/// How input is divided into records. Normally each line is a record (and we
+/// strip its `\n` or `\r\n` terminator). With `--record-bytes N`, the input is
+/// a sequence of `N`-byte binary records with no terminator (though the last
+/// record may be short). Such input is treated as opaque bytes: we don't look
+/// for Byte Order Marks or translate UTF-16, and output has no terminators.
+/// With `--no-decode`, records are lines split only on `\n`, again treated as
+/// opaque bytes: a `\r` before the `\n` is part of the line, and a Byte Order
+/// Mark is part of the first line. With `--csv`, records are CSV records,
+/// which are lines except that a newline inside a double-quoted field doesn't
+/// end the record; they're text, like lines. With `--record-delimiter`, records
+/// end with the given byte or bytes rather than `\n` (with `-z`, a NUL byte, as
+/// with `sort -z` or `find -print0`), and are opaque bytes, as with
+/// `--no-decode`; output records end with the same delimiter.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Framing {
+    #[default]
+    Lines,
+    RawLines,
+    FixedWidth(NonZeroUsize),
+    Csv,
+    Separated(Vec<u8>),
+}
+
+/// When keys aren't whole lines, several different lines may have the same
+/// key. Which of them do we print?
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Keep {
+    /// The first line seen with the key
+    #[default]
+    First,
+    /// The last line seen with the key, replacing earlier ones as we read
+    Last,
+}
+
+/// The order in which a set's lines are printed: that of their first
+/// appearance in the input (the default), or of their last
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Order {
+    #[default]
+    FirstSeen,
+    LastSeen,
+}
+
+impl Framing {
+    /// Are records text, which may be decoded from UTF-16 and start with a
+    /// Byte Order Mark?
+    #[must_use]
+    pub fn is_text(&self) -> bool {
+        matches!(self, Framing::Lines | Framing::Csv)
+    }
+
+    /// The bytes that end a record (other than a fixed-width one): those given
+    /// with `--record-delimiter`, and otherwise `\n`
+    #[must_use]
+    pub fn terminator(&self) -> &[u8] {
+        match self {
+            Framing::Separated(delimiter) => delimiter,
+            _ => b"\n",
+        }
+    }
+}
+
+/// The offset of the `\n` that ends the first CSV record of `slice` — the first
+/// one outside double quotes — if there is one
+#[must_use]
+pub fn csv_record_end(slice: &[u8]) -> Option<usize> {
+    let mut quoted = false;
+    for n in memchr2_iter(b'\n', b'"', slice) {
+        if slice[n] == b'"' {
+            quoted = !quoted;
+        } else if !quoted {
+            return Some(n);
+        }
+    }
+    None
+}
+
+/// Where a line occurs: the number of the operand it's in (0 for the first
+/// operand), and its line number in that operand (starting with 1)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub operand: u32,
+    pub line: u64,
+}
+
+/// The positions at which each line of a `ZetSet` occurs (at most `limit` of
+/// them per line), in the same order as the set's lines. `current` is the
+/// position of the line being read.
+#[derive(Clone, Debug)]
+struct Positions {
+    limit: usize,
+    current: Position,
+    of_entry: Vec<Vec<Position>>,
+}
+
+impl Positions {
+    fn new(limit: usize) -> Self {
+        Positions { limit, current: Position { operand: 0, line: 0 }, of_entry: Vec::new() }
+    }
+
+    fn next_operand(&mut self) {
+        self.current = Position { operand: self.current.operand.wrapping_add(1), line: 0 };
+    }
+
+    /// Count a line read, noting its position if it's the set's `index`th
+    /// line
+    fn note(&mut self, index: Option<usize>) {
+        self.current.line += 1;
+        if let Some(index) = index {
+            if index == self.of_entry.len() {
+                self.of_entry.push(Vec::new());
+            }
+            let positions = &mut self.of_entry[index];
+            if positions.len() < self.limit {
+                positions.push(self.current);
+            }
+        }
+    }
+}
+
+/// For `Order::LastSeen`, the number of the line (counting every line read) at
+/// which each line of a `ZetSet` was last seen, in the same order as the set's
+/// lines
+#[derive(Clone, Debug, Default)]
+struct Recency {
+    lines_read: u64,
+    of_entry: Vec<u64>,
+}
+
+impl Recency {
+    /// Count a line read, noting it as the last sighting of the set's
+    /// `index`th line
+    fn note(&mut self, index: Option<usize>) {
+        self.lines_read += 1;
+        if let Some(index) = index {
+            if index == self.of_entry.len() {
+                self.of_entry.push(self.lines_read);
+            } else {
+                self.of_entry[index] = self.lines_read;
+            }
+        }
+    }
+}
+
+/// For `--show-files` and `--count-per-file`, the operands (counting from 0)
+/// each line of a `ZetSet` occurs in, each with the number of times it occurs
+/// there, in the same order as the set's lines. `current` is the operand being
+/// read.
+#[derive(Clone, Debug, Default)]
+struct Containing {
+    current: u32,
+    of_entry: Vec<Vec<(u32, u32)>>,
+}
+
+impl Containing {
+    fn next_operand(&mut self) {
+        self.current = self.current.saturating_add(1);
+    }
+
+    /// Note that the set's `index`th line occurs (once more) in the current
+    /// operand
+    fn note(&mut self, index: Option<usize>) {
+        if let Some(index) = index {
+            if index == self.of_entry.len() {
+                self.of_entry.push(Vec::new());
+            }
+            match self.of_entry[index].last_mut() {
+                Some((operand, count)) if *operand == self.current => {
+                    *count = count.saturating_add(1);
+                }
+                _ => self.of_entry[index].push((self.current, 1)),
+            }
+        }
+    }
+}
+
+/// The error returned by a `ZetSet` in fallible-allocation mode when there
+/// isn't enough memory to add another line. Callers can recognize it with
+/// `anyhow::Error::downcast_ref::<InputTooLarge>()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputTooLarge {
+    /// The number of distinct lines in the set when we ran out of memory
+    pub distinct_lines: usize,
+}
+
+impl fmt::Display for InputTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Input too large: out of memory after {} distinct lines", self.distinct_lines)
+    }
+}
+
+impl std::error::Error for InputTooLarge {}
+
+/// We don't, in fact, require the second and following "files" to be files! Our
+/// only requirement is that they implement `for_byte_line`. The `LaterOperand`
+/// trait codifies that.
+pub trait LaterOperand {
+    /// The call `o.for_byte_line(|line| ...)` method calls the given closure
+    /// for each &[u8] in `o`.
+    fn for_byte_line(self, for_each_line: impl FnMut(&[u8])) -> Result<()>;
+
+    /// Like `for_byte_line`, but stops as soon as the closure returns `false`.
+    /// The provided implementation just stops calling the closure; operands
+    /// that can stop reading early should override it.
+    fn for_byte_line_while(self, mut for_each_line: impl FnMut(&[u8]) -> bool) -> Result<()>
+    where
+        Self: Sized,
+    {
+        let mut going = true;
+        self.for_byte_line(|line| going = going && for_each_line(line))
+    }
+}
+
+/// When a `ZetSet` processes a line from an operand, it does one of two things:
+/// * If the line is not present in the set, it is inserted, with a bookkeeping
+///   value `item` passed by the caller.
+/// * If the line is already present in the set, `v.update_with(item)` is
+///   called on its bookkeeping value `v`.
+///
+/// The `new` function inserts lines borrowed from its `slice` argument. The
+/// `insert_or_update` inserts `Cow::Owned` lines, so its `operand` argument
+/// need not outlive the `ZetSet` The `update_if_present` method only updates —
+/// it's used by the `Insert` and `Diff` operations, which only decrease the set
+/// returned by `new` and never add to it.
+///
+/// Before inserting or updating, the `ZetSet` calls `item.for_line(line)`, so
+/// that bookkeeping types can take the line's contents into account. With
+/// `--counted-input`, it first strips the count from the start of the line,
+/// and calls `item.weighted(count)`.
+///
+/// The `retain` method filters the set, using a function passed by the caller that
+/// looks at the `.retention_value()` of the bookkeeping item. The `retain_if`
+/// method is more general: its function sees each line and its bookkeeping item.
+///
+/// The `output_to` method prints the lines of the set, calling the bookkeeping
+/// item's `write_count` method (when appropriate) to prefix each line with the
+/// number of times it appears in the input, or the number of files it appears
+/// in.
+impl<'data, B: Bookkeeping> ZetSet<'data, B> {
+    /// Create a new `ZetSet`, with each key a line borrowed from `slice`, and
+    /// value `item` for every line newly seen. If a line is already present,
+    /// with bookkeeping value `v`, update it by calling `v.update_with(item)`
+    ///
+    /// This is very much like the `insert_or_update` method, which uses
+    /// `for_byte_line`. But I had borrow-checker trouble when I tried that, so
+    /// this code uses `for_each_record`, a specialized version of
+    /// `for_byte_line` whose records are borrowed from `slice`.
+    ///
+    /// With `--key-cmd`, keys come from an external command, which we run
+    /// just once for all the lines of `slice`.
+    pub fn new(slice: &'data [u8], item: B, key: Key, framing: Framing) -> Result<Self> {
+        let mut options = Options::new(OpName::Union);
+        options.key = key;
+        options.framing = framing;
+        Self::with_options(slice, item, &options)
+    }
+
+    /// Like `new`, but taking the key, framing, allocation mode, and which
+    /// line to keep from `options` — and with `options.locations` set,
+    /// recording the positions at which each line occurs, with
+    /// `options.counted_input` set, weighting each line by the count it starts
+    /// with, with `Order::LastSeen`, recording when each line was last seen, and
+    /// with `options.show_files` or `options.count_per_file` set, recording the
+    /// operands each line occurs in.
+    pub fn with_options(slice: &'data [u8], item: B, options: &Options) -> Result<Self> {
+        let Options {
+            ref key, ref framing, fallible_allocation, ref locations, keep, order, ..
+        } = *options;
+        let (bom, line_terminator) = output_info(slice, framing);
+        let slice = &slice[bom.len()..];
+        let mut zet = ZetSet {
+            set: IndexMap::default(),
+            key: key.clone(),
+            lines: Vec::new(),
+            keep,
+            fallible: fallible_allocation,
+            positions: locations.as_ref().map(|locations| Positions::new(locations.limit())),
+            recency: (order == Order::LastSeen).then(Recency::default),
+            containing: (options.show_files.is_some() || options.count_per_file.is_some())
+                .then(Containing::default),
+            counted: options.counted_input,
+            csv_header: (*framing == Framing::Csv)
+                .then(|| Header { named: key.clone(), awaited: true }),
+            bom,
+            line_terminator: line_terminator.to_vec(),
+        };
+        if zet.key.is_command() {
+            let mut records = Vec::new();
+            for_each_record(slice, options, |record| records.push(record));
+            let (records, items) = zet.weigh_each(records, item)?;
+            let keys = zet.key.of_each(&records)?;
+            for ((record, item), key) in records.into_iter().zip(items).zip(keys) {
+                zet.add(Cow::Owned(key), || Cow::Borrowed(record), item.for_line(record))?;
+            }
+        } else {
+            let mut added = Ok(());
+            for_each_record(slice, options, |record| {
+                if added.is_ok() {
+                    added = zet.admit(record, item).and_then(|weighed| {
+                        let Some((record, item)) = weighed else { return Ok(()) };
+                        let key = zet.key.of(record);
+                        Ok(zet.add(key, || Cow::Borrowed(record), item.for_line(record))?)
+                    });
+                }
+            });
+            added?;
+        }
+        Ok(zet)
+    }
+
+    /// With `--counted-input`, split `line` into the line counted and `item`
+    /// weighted by its count, or return `None` if the count is zero (so the
+    /// line stands for no occurrences at all). Otherwise return `line` and
+    /// `item` unchanged.
+    fn weigh<'a>(&self, line: &'a [u8], item: B) -> Result<Option<(&'a [u8], B)>> {
+        if !self.counted {
+            return Ok(Some((line, item)));
+        }
+        let (weight, counted) = counted::weight_of(line)?;
+        Ok((weight > 0).then(|| (counted, item.weighted(weight))))
+    }
+
+    /// Like `weigh`, but first, if `line` is an operand's CSV header, look up
+    /// the key's named columns in it, and return `None`, leaving it out
+    fn admit<'a>(&mut self, line: &'a [u8], item: B) -> Result<Option<(&'a [u8], B)>> {
+        if let Some(header) = self.csv_header.as_mut().filter(|header| header.awaited) {
+            header.awaited = false;
+            if header.named.names_columns() {
+                self.key = header.named.for_header(line)?;
+            }
+            self.note(None);
+            return Ok(None);
+        }
+        self.weigh(line, item)
+    }
+
+    /// Call `admit` for each of `lines`, returning the lines and items of
+    /// those not left out, separately
+    fn weigh_each<'a>(
+        &mut self,
+        lines: impl IntoIterator<Item = &'a [u8]>,
+        item: B,
+    ) -> Result<(Vec<&'a [u8]>, Vec<B>)> {
+        let weighed = lines.into_iter().map(|line| self.admit(line, item));
+        Ok(weighed.collect::<Result<Vec<_>>>()?.into_iter().flatten().unzip())
+    }
+
+    /// Insert `key` with bookkeeping value `item` if it isn't already present
+    /// (remembering the `line` it came from if keys aren't whole lines).
+    /// Otherwise call `v.update_with(item)` on its bookkeeping value `v`.
+    fn add(
+        &mut self,
+        key: Cow<'data, [u8]>,
+        line: impl FnOnce() -> Cow<'data, [u8]>,
+        item: B,
+    ) -> Result<(), InputTooLarge> {
+        if self.fallible {
+            self.make_room()?;
+        }
+        let index = match self.set.entry(key) {
+            Entry::Occupied(mut entry) => {
+                entry.get_mut().update_with(item);
+                let index = entry.index();
+                self.keep_line(index, line);
+                index
+            }
+            Entry::Vacant(entry) => {
+                let index = entry.index();
+                entry.insert(item);
+                if !self.key.is_whole_line() {
+                    self.lines.push(line());
+                }
+                index
+            }
+        };
+        self.note(Some(index));
+        Ok(())
+    }
+
+    /// With `Keep::Last`, make `line` the line printed for the set's `index`th
+    /// key (which it has)
+    fn keep_line(&mut self, index: usize, line: impl FnOnce() -> Cow<'data, [u8]>) {
+        if self.keep == Keep::Last && !self.key.is_whole_line() {
+            self.lines[index] = line();
+        }
+    }
+
+    /// With `--where`, count a line read, noting its position if it's the
+    /// set's `index`th line (and with `Order::LastSeen` or `--show-files`,
+    /// when or where it was seen)
+    fn note(&mut self, index: Option<usize>) {
+        if let Some(positions) = &mut self.positions {
+            positions.note(index);
+        }
+        if let Some(recency) = &mut self.recency {
+            recency.note(index);
+        }
+        if let Some(containing) = &mut self.containing {
+            containing.note(index);
+        }
+    }
+
+    /// With `--where` or `--show-files`, start on the lines of the next
+    /// operand (and with a CSV header to read, on its header)
+    fn next_operand(&mut self) {
+        if let Some(header) = &mut self.csv_header {
+            header.awaited = true;
+        }
+        if let Some(positions) = &mut self.positions {
+            positions.next_operand();
+        }
+        if let Some(containing) = &mut self.containing {
+            containing.next_operand();
+        }
+    }
+
+    /// If the set is full, try to grow it, so that adding a line can't abort.
+    fn make_room(&mut self) -> Result<(), InputTooLarge> {
+        let distinct_lines = self.set.len();
+        let too_large = InputTooLarge { distinct_lines };
+        if distinct_lines == self.set.capacity() {
+            self.set.try_reserve(1).map_err(|_| too_large)?;
+        }
+        if !self.key.is_whole_line() && self.lines.len() == self.lines.capacity() {
+            self.lines.try_reserve(1).map_err(|_| too_large)?;
+        }
+        Ok(())
+    }
+
+    /// For each line in `operand`, insert `line` as `Cow::Owned` to the
+    /// underlying `IndexMap` if it is not already present, with bookkeeping
+    /// value `item`. If `line` is already present, with bookkeeping value `v`,
+    /// update it by calling `v.update_with(item)`
+    pub fn insert_or_update(&mut self, operand: impl LaterOperand, item: B) -> Result<()> {
+        self.next_operand();
+        if self.key.is_command() {
+            let lines = all_lines_of(operand)?;
+            let (lines, items) = self.weigh_each(lines.iter().map(Vec::as_slice), item)?;
+            let keys = self.key.of_each(&lines)?;
+            for ((line, item), key) in lines.into_iter().zip(items).zip(keys) {
+                let item = item.for_line(line);
+                self.add(Cow::Owned(key), || Cow::Owned(line.to_vec()), item)?;
+            }
+            return Ok(());
+        }
+        let mut added = Ok(());
+        operand.for_byte_line(|line| {
+            if added.is_ok() {
+                added = self.admit(line, item).and_then(|weighed| {
+                    let Some((line, item)) = weighed else { return Ok(()) };
+                    let key = Cow::Owned(self.key.of(line).into_owned());
+                    Ok(self.add(key, || Cow::Owned(line.to_vec()), item.for_line(line))?)
+                });
+            }
+        })?;
+        added
+    }
+
+    /// For each line in `operand` that is already present in the underlying
+    /// `IndexMap` with bookkeeping value `v`, call `v.update_with(item)`.
+    pub fn update_if_present(&mut self, operand: impl LaterOperand, item: B) -> Result<()> {
+        self.next_operand();
+        if self.key.is_command() {
+            let lines = all_lines_of(operand)?;
+            let (lines, items) = self.weigh_each(lines.iter().map(Vec::as_slice), item)?;
+            let keys = self.key.of_each(&lines)?;
+            for ((line, item), key) in lines.into_iter().zip(items).zip(keys) {
+                let index = update(&mut self.set, &key, line, item);
+                if let Some(index) = index {
+                    self.keep_line(index, || Cow::Owned(line.to_vec()));
+                }
+                self.note(index);
+            }
+            return Ok(());
+        }
+        let mut updated = Ok(());
+        operand.for_byte_line(|line| {
+            if updated.is_ok() {
+                updated = self.admit(line, item).map(|weighed| {
+                    if let Some((line, item)) = weighed {
+                        let index = update(&mut self.set, self.key.of(line).as_ref(), line, item);
+                        if let Some(index) = index {
+                            self.keep_line(index, || Cow::Owned(line.to_vec()));
+                        }
+                        self.note(index);
+                    }
+                });
+            }
+        })?;
+        updated
+    }
+
+    /// For each line in `operand`, call `verdict(previous)`, where `previous`
+    /// is the line's bookkeeping value before it's updated with `item`, or
+    /// `None` if the line isn't present (in which case it's inserted with value
+    /// `item` if `insert` is set). Stop reading `operand` as soon as `verdict`
+    /// returns `false`.
+    pub fn scan_while(
+        &mut self,
+        operand: impl LaterOperand,
+        item: B,
+        insert: bool,
+        mut verdict: impl FnMut(Option<B>) -> bool,
+    ) -> Result<()> {
+        self.next_operand();
+        if self.key.is_command() {
+            let lines = all_lines_of(operand)?;
+            let (lines, items) = self.weigh_each(lines.iter().map(Vec::as_slice), item)?;
+            let keys = self.key.of_each(&lines)?;
+            for ((line, item), key) in lines.into_iter().zip(items).zip(keys) {
+                if !verdict(self.visit(Cow::Owned(key), line, item, insert)?) {
+                    break;
+                }
+            }
+            return Ok(());
+        }
+        let mut visited = Ok(());
+        operand.for_byte_line_while(|line| {
+            let previous = self.admit(line, item).and_then(|weighed| {
+                let Some((line, item)) = weighed else { return Ok(None) };
+                let key = Cow::Owned(self.key.of(line).into_owned());
+                Ok(Some(self.visit(key, line, item, insert)?))
+            });
+            match previous {
+                Ok(None) => true,
+                Ok(Some(previous)) => verdict(previous),
+                Err(err) => {
+                    visited = Err(err);
+                    false
+                }
+            }
+        })?;
+        visited
+    }
+
+    /// Update the bookkeeping value of `key` (from `line`) with `item`, or if
+    /// it's absent and `insert` is set, insert it. Returns the previous value.
+    fn visit(
+        &mut self,
+        key: Cow<'data, [u8]>,
+        line: &[u8],
+        item: B,
+        insert: bool,
+    ) -> Result<Option<B>, InputTooLarge> {
+        let item = item.for_line(line);
+        if let Some((index, _, bookkeeping)) = self.set.get_full_mut(key.as_ref()) {
+            let previous = *bookkeeping;
+            bookkeeping.update_with(item);
+            self.keep_line(index, || Cow::Owned(line.to_vec()));
+            self.note(Some(index));
+            return Ok(Some(previous));
+        }
+        if insert {
+            self.add(key, || Cow::Owned(line.to_vec()), item)?;
+        } else {
+            self.note(None);
+        }
+        Ok(None)
+    }
+
+    /// Does the set contain `line` — or if keys aren't whole lines, a line
+    /// with the same key?
+    pub fn contains(&self, line: &[u8]) -> Result<bool> {
+        let key = if self.key.is_command() {
+            Cow::Owned(self.key.of_each(&[line])?.swap_remove(0))
+        } else {
+            self.key.of(line)
+        };
+        Ok(self.set.contains_key(key.as_ref()))
+    }
+
+    /// The lines of this set whose keys aren't in `other`, in order
+    pub fn lines_not_in<'a, C: Bookkeeping>(
+        &'a self,
+        other: &'a ZetSet<'_, C>,
+    ) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let keys = self.set.keys();
+        let absent =
+            self.lines().zip(keys).filter(|(_, key)| !other.set.contains_key(key.as_ref()));
+        absent.map(|(line, _)| line)
+    }
+
+    /// Retain the lines for which `keep(line, item)` is true, where `item` is
+    /// the line's bookkeeping value
+    pub fn retain_if(&mut self, mut keep: impl FnMut(&[u8], &B) -> bool) {
+        let mut kept = Vec::with_capacity(self.set.len());
+        for (line, item) in self.iter() {
+            kept.push(keep(line, item));
+        }
+        self.retain_by(&kept);
+    }
+
+    /// Reorder the set so that its lines come in bytewise order, rather than
+    /// in order of first appearance
+    pub fn sort_lines(&mut self) {
+        let mut order: Vec<usize> = (0..self.set.len()).collect();
+        let lines: Vec<&[u8]> = self.lines().collect();
+        order.sort_by_key(|&index| lines[index]);
+        self.reorder(&order);
+    }
+
+    /// Reorder the set so that its lines come in order of their last
+    /// appearance. (This needs the set to have been built with
+    /// `Order::LastSeen`; otherwise it leaves the order alone.)
+    pub fn sort_by_last_seen(&mut self) {
+        let Some(recency) = &self.recency else { return };
+        let mut order: Vec<usize> = (0..self.set.len()).collect();
+        order.sort_unstable_by_key(|&index| recency.of_entry[index]);
+        self.reorder(&order);
+    }
+
+    /// Reorder the set at random, shuffled with seed `seed` (so the same
+    /// seed gives the same order)
+    pub fn shuffle(&mut self, seed: u64) {
+        self.reorder(&sample::shuffled(self.set.len(), seed));
+    }
+
+    /// Rearrange the set (and its lines, positions, recency, and containing
+    /// operands, if we keep them) so that the `n`th entry is the one that was
+    /// at `order[n]`
+    fn reorder(&mut self, order: &[usize]) {
+        let mut entries: Vec<_> = std::mem::take(&mut self.set).into_iter().collect();
+        permute(&mut entries, order);
+        self.set = entries.into_iter().collect();
+        permute(&mut self.lines, order);
+        if let Some(positions) = &mut self.positions {
+            permute(&mut positions.of_entry, order);
+        }
+        if let Some(recency) = &mut self.recency {
+            permute(&mut recency.of_entry, order);
+        }
+        if let Some(containing) = &mut self.containing {
+            permute(&mut containing.of_entry, order);
+        }
+    }
+
+    /// With `--where`, the positions at which the set's `index`th line occurs
+    /// (otherwise, none)
+    #[must_use]
+    pub fn positions_of(&self, index: usize) -> &[Position] {
+        let positions = self.positions.as_ref().and_then(|p| p.of_entry.get(index));
+        positions.map_or(&[], Vec::as_slice)
+    }
+
+    /// With `--show-files`, the operands (counting from 0) that the set's
+    /// `index`th line occurs in (otherwise, none)
+    pub fn operands_containing(&self, index: usize) -> impl Iterator<Item = u32> + '_ {
+        self.counts_in_operands(index).iter().map(|&(operand, _)| operand)
+    }
+
+    /// With `--show-files` or `--count-per-file`, the operands (counting from
+    /// 0) that the set's `index`th line occurs in, each with the number of
+    /// times it occurs there (otherwise, none)
+    #[must_use]
+    pub fn counts_in_operands(&self, index: usize) -> &[(u32, u32)] {
+        let operands = self.containing.as_ref().and_then(|c| c.of_entry.get(index));
+        operands.map_or(&[], Vec::as_slice)
+    }
+
+    /// The lines of the set, in order, for output
+    pub fn lines(&self) -> impl Iterator<Item = &[u8]> {
+        self.iter().map(|(line, _)| line)
+    }
+    /// Each line of the set, with its bookkeeping value
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &B)> + Clone {
+        self.set.iter().enumerate().map(|(i, (key, item))| match self.lines.get(i) {
+            Some(line) => (line.as_ref(), item),
+            None => (key.as_ref(), item),
+        })
+    }
+}
+
+/// The methods of this block work for any key type. For keys other than lines,
+/// a `ZetSet` is built with `from_records`, grown or updated with
+/// `insert_or_update_records` and `update_records_if_present`, sifted with
+/// `retain`, and read back with `records` — the same bookkeeping as for lines,
+/// without the reading and splitting of text.
+impl<B: Bookkeeping, K: Hash + Eq> ZetSet<'_, B, K> {
+    /// Create a new `ZetSet` of `records`, with value `item` for every record
+    /// newly seen (and `v.update_with(item)` for a record seen before, with
+    /// value `v`)
+    pub fn from_records(records: impl IntoIterator<Item = K>, item: B) -> Self {
+        let mut zet = ZetSet {
+            set: IndexMap::default(),
+            key: Key::default(),
+            lines: Vec::new(),
+            keep: Keep::First,
+            fallible: false,
+            positions: None,
+            recency: None,
+            containing: None,
+            counted: false,
+            csv_header: None,
+            bom: b"",
+            line_terminator: b"\n".to_vec(),
+        };
+        zet.insert_or_update_records(records, item);
+        zet
+    }
+
+    /// Insert each of `records` not already present, with value `item`, and
+    /// call `v.update_with(item)` on the value `v` of each that is
+    pub fn insert_or_update_records(&mut self, records: impl IntoIterator<Item = K>, item: B) {
+        for record in records {
+            match self.set.entry(record) {
+                Entry::Occupied(mut entry) => entry.get_mut().update_with(item),
+                Entry::Vacant(entry) => {
+                    entry.insert(item);
+                }
+            }
+        }
+    }
+
+    /// Call `v.update_with(item)` on the value `v` of each of `records` that's
+    /// present, ignoring the rest
+    pub fn update_records_if_present(&mut self, records: impl IntoIterator<Item = K>, item: B) {
+        for record in records {
+            if let Some(bookkeeping) = self.set.get_mut(&record) {
+                bookkeeping.update_with(item);
+            }
+        }
+    }
+
+    /// Is `record` in the set?
+    pub fn contains_record(&self, record: &K) -> bool {
+        self.set.contains_key(record)
+    }
+
+    /// Each key of the set, in order of first appearance, with its bookkeeping
+    /// value
+    pub fn records(&self) -> impl Iterator<Item = (&K, &B)> {
+        self.set.iter()
+    }
+
+    /// The keys of the set, in order of first appearance
+    pub fn into_records(self) -> impl Iterator<Item = K> {
+        self.set.into_keys()
+    }
+
+    /// The number of distinct lines (or keys) in the set
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    /// Is the set empty?
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    /// Like `IndexMap`'s `.retain` method, but exposes just the bookkeeping
+    /// item's `.retention_value()`
+    pub fn retain(&mut self, keep: impl Fn(u32) -> bool) {
+        let parallel = self.positions.is_some() || self.recency.is_some();
+        if self.lines.is_empty() && !parallel && self.containing.is_none() {
+            self.set.retain(|_k, v| keep(v.retention_value()));
+            return;
+        }
+        let kept: Vec<_> = self.set.values().map(|item| keep(item.retention_value())).collect();
+        self.retain_by(&kept);
+    }
+
+    /// Like `retain`, but `keep` also sees the index of each entry (counting
+    /// from 0, in order of first appearance)
+    pub fn retain_indexed(&mut self, keep: impl Fn(usize, u32) -> bool) {
+        let values = self.set.values().enumerate();
+        let kept: Vec<_> =
+            values.map(|(index, item)| keep(index, item.retention_value())).collect();
+        self.retain_by(&kept);
+    }
+
+    /// Retain the `n`th entry of the set (and its line and positions, if we
+    /// keep them) if `kept[n]` is true
+    fn retain_by(&mut self, kept: &[bool]) {
+        let mut verdicts = kept.iter();
+        self.set.retain(|_k, _v| verdicts.next() == Some(&true));
+        if !self.lines.is_empty() {
+            let mut verdicts = kept.iter();
+            self.lines.retain(|_| verdicts.next() == Some(&true));
+        }
+        if let Some(positions) = &mut self.positions {
+            let mut verdicts = kept.iter();
+            positions.of_entry.retain(|_| verdicts.next() == Some(&true));
+        }
+        if let Some(recency) = &mut self.recency {
+            let mut verdicts = kept.iter();
+            recency.of_entry.retain(|_| verdicts.next() == Some(&true));
+        }
+        if let Some(containing) = &mut self.containing {
+            let mut verdicts = kept.iter();
+            containing.of_entry.retain(|_| verdicts.next() == Some(&true));
+        }
+    }
+}
+
+/// Rearrange `items` so that the `n`th is the one that was at `order[n]`
+/// (leaving `items` alone if it's empty, as `lines` is for whole-line keys)
+fn permute<T>(items: &mut Vec<T>, order: &[usize]) {
+    if items.is_empty() {
+        return;
+    }
+    let mut taken: Vec<Option<T>> = std::mem::take(items).into_iter().map(Some).collect();
+    items.extend(order.iter().filter_map(|&index| taken[index].take()));
+}
+
+/// If `key` (from `line`) is present in `set`, with bookkeeping value `v`, call
+/// `v.update_with(item)` and return its index
+fn update<B: Bookkeeping>(set: &mut CowSet<B>, key: &[u8], line: &[u8], item: B) -> Option<usize> {
+    let (index, _, bookkeeping) = set.get_full_mut(key)?;
+    bookkeeping.update_with(item.for_line(line));
+    Some(index)
+}
+
+/// Call `for_each_record` on each record of `slice`, borrowed from `slice`.
+/// For `Framing::Lines` and `Framing::Csv`, we strip the line terminator (`\n`
+/// or `\r\n`) from each record; for `Framing::RawLines`, just the `\n`; and
+/// for `Framing::Separated`, its delimiter. See
+/// Andrew Gallant's `bstr` crate, in particular
+/// `bstr::io::for_byte_record_with_terminator`. The framing is
+/// `options.framing`; we stop early if `options.interrupt` is requested, and
+/// add the records read to `options.tally`.
+pub(crate) fn for_each_record<'data>(
+    slice: &'data [u8],
+    options: &Options,
+    mut for_each_record: impl FnMut(&'data [u8]),
+) {
+    let mut records = 0;
+    split_records(slice, &options.framing, &options.interrupt, |record| {
+        records += 1;
+        for_each_record(record);
+    });
+    options.tally.read(records);
+}
+
+fn split_records<'data>(
+    mut slice: &'data [u8],
+    framing: &Framing,
+    interrupt: &Interrupt,
+    mut for_each_record: impl FnMut(&'data [u8]),
+) {
+    if let Framing::FixedWidth(width) = *framing {
+        for record in slice.chunks(width.get()) {
+            if interrupt.requested() {
+                break;
+            }
+            for_each_record(record);
+        }
+        return;
+    }
+    let terminator = framing.terminator();
+    let finder = memmem::Finder::new(terminator);
+    let end_of = |s: &[u8]| match (framing, terminator) {
+        (Framing::Csv, _) => csv_record_end(s),
+        (_, &[byte]) => memchr(byte, s),
+        _ => finder.find(s),
+    };
+    while let Some(end) = end_of(slice) {
+        if interrupt.requested() {
+            return;
+        }
+        let (mut line, rest) = slice.split_at(end);
+        slice = &rest[terminator.len()..];
+        if framing.is_text() && line.last() == Some(&b'\r') {
+            line = &line[..line.len() - 1];
+        }
+        for_each_record(line);
+    }
+    if !slice.is_empty() {
+        for_each_record(slice);
+    }
+}
+
+/// When an external command computes keys, it needs to see all the lines of
+/// an operand at once — so we read them into memory.
+fn all_lines_of(operand: impl LaterOperand) -> Result<Vec<Vec<u8>>> {
+    let mut lines = Vec::new();
+    operand.for_byte_line(|line| lines.push(line.to_vec()))?;
+    Ok(lines)
+}
+
+/// Returns `(bom, line_terminator)`, where `bom` is the (UTF-8) Byte Order
+/// Mark, or the empty string if `slice` has none, and `line_terminator` is
+/// `\r\n` if the first line of `slice` ends with `\r\n`, and `\n` if the first
+/// line ends just with `\n` (or is the only line in the file and has no line
+/// terminator). Fixed-width binary records have neither, raw lines always end
+/// with `\n`, with any `\r` or BOM left in the lines themselves, and records
+/// separated by a `--record-delimiter` always end with that delimiter.
+pub(crate) fn output_info<'f>(slice: &[u8], framing: &'f Framing) -> (&'static [u8], &'f [u8]) {
+    match framing {
+        Framing::Lines | Framing::Csv => {}
+        Framing::RawLines => return (b"", b"\n"),
+        Framing::Separated(delimiter) => return (b"", delimiter),
+        Framing::FixedWidth(_) => return (b"", b""),
+    }
+    let mut bom: &'static [u8] = b"";
+    let mut line_terminator: &'static [u8] = b"\n";
+    if has_bom(slice) {
+        bom = BOM_BYTES;
+    }
+    if let Some(n) = memchr(b'\n', slice) {
+        if n > 0 && slice[n - 1] == b'\r' {
+            line_terminator = b"\r\n";
+        }
+    }
+    (bom, line_terminator)
+}
+
+const BOM_0: u8 = b'\xEF';
+const BOM_1: u8 = b'\xBB';
+const BOM_2: u8 = b'\xBF';
+const BOM_BYTES: &[u8] = b"\xEF\xBB\xBF";
+/// Does `first_operand` begin with a (UTF-8) Byte Order Mark?
+fn has_bom(first_operand: &[u8]) -> bool {
+    first_operand.len() >= 3
+        && first_operand[0] == BOM_0
+        && first_operand[1] == BOM_1
+        && first_operand[2] == BOM_2
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const UTF8_BOM: &str = "\u{FEFF}";
+
+    #[test]
+    fn utf8_bom_is_correct() {
+        assert_eq!([BOM_0, BOM_1, BOM_2], UTF8_BOM.as_bytes());
+    }
+
+    #[test]
+    fn csv_records_end_at_newlines_outside_quotes() {
+        assert_eq!(csv_record_end(b"a,b\nc"), Some(3));
+        assert_eq!(csv_record_end(b"a,\"b\nc\"\nd"), Some(7));
+        assert_eq!(csv_record_end(b"a,\"\"\"b\nc\""), None);
+        assert_eq!(csv_record_end(b"a,b"), None);
+    }
+
+    /// A bookkeeping type a library user might write: the sum of each line's
+    /// second field, keyed by the first.
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    struct Sum(u32);
+    impl Bookkeeping for Sum {
+        fn new() -> Self {
+            Sum(0)
+        }
+        fn next_file(&mut self) {}
+        fn update_with(&mut self, other: Self) {
+            self.0 += other.0;
+        }
+        fn retention_value(self) -> u32 {
+            self.0
+        }
+        fn for_line(self, line: &[u8]) -> Self {
+            let field = line.split(|&b| b == b' ').nth(1).unwrap_or_default();
+            Sum(std::str::from_utf8(field).unwrap().parse().unwrap())
+        }
+    }
+
+    #[test]
+    fn custom_bookkeeping_sees_each_line() {
+        // (`LaterOperand` is implemented for `&[u8]` in the `operations` tests)
+        let mut zet =
+            ZetSet::new(b"a 1\nb 5\na 2\n", Sum::new(), Key::fields(&[1], None), Framing::Lines)
+                .unwrap();
+        zet.insert_or_update(&b"b 7\nc 1\na 4\n"[..], Sum::new()).unwrap();
+        let sums: Vec<_> = zet.iter().map(|(line, sum)| (line, sum.0)).collect();
+        assert_eq!(sums, [(&b"a 1"[..], 7), (b"b 5", 12), (b"c 1", 1)]);
+
+        zet.retain_if(|line, sum| sum.0 > 5 && line != b"b 5");
+        assert_eq!(zet.lines().collect::<Vec<_>>(), [b"a 1"]);
+    }
+
+    #[test]
+    fn keep_last_replaces_the_line_printed() {
+        let mut options = Options::new(OpName::Union);
+        options.key = Key::fields(&[1], None);
+        options.keep = Keep::Last;
+        let mut zet = ZetSet::with_options(b"a 1\nb 5\na 2\n", Sum::new(), &options).unwrap();
+        zet.update_if_present(&b"b 7\nc 1\n"[..], Sum::new()).unwrap();
+        assert_eq!(zet.lines().collect::<Vec<_>>(), [&b"a 2"[..], b"b 7"]);
+    }
+    #[test]
+    fn sort_lines_keeps_each_line_with_its_bookkeeping() {
+        let mut options = Options::new(OpName::Union);
+        options.key = Key::fields(&[1], None);
+        let mut zet = ZetSet::with_options(b"b 1\nc 2\na 3\nc 4\n", Sum::new(), &options).unwrap();
+        zet.sort_lines();
+        let sorted: Vec<_> = zet.iter().map(|(line, sum)| (line, sum.retention_value())).collect();
+        assert_eq!(sorted, [(&b"a 3"[..], 3), (b"b 1", 1), (b"c 2", 6)]);
+        assert!(zet.contains(b"c 9").unwrap());
+    }
+
+    #[test]
+    fn sort_by_last_seen_follows_the_last_appearance_of_each_line() {
+        let mut options = Options::new(OpName::Union);
+        options.key = Key::fields(&[1], None);
+        options.order = Order::LastSeen;
+        let mut zet = ZetSet::with_options(b"a 1\nb 1\nc 1\na 1\n", Sum::new(), &options).unwrap();
+        zet.insert_or_update(&b"b 1\nd 1\nc 0\n"[..], Sum::new()).unwrap();
+        zet.retain(|sum| sum > 0);
+        zet.sort_by_last_seen();
+        assert_eq!(zet.lines().collect::<Vec<_>>(), [&b"a 1"[..], b"b 1", b"d 1", b"c 1"]);
+    }
+
+    #[test]
+    fn containing_operands_are_noted_once_each() {
+        let mut options = Options::new(OpName::Union);
+        options.show_files = Some(vec![]);
+        let mut zet = ZetSet::with_options(b"a 1\nb 1\na 1\n", Sum::new(), &options).unwrap();
+        zet.insert_or_update(&b"c 1\nb 1\nb 1\n"[..], Sum::new()).unwrap();
+        zet.update_if_present(&b"c 1\nd 1\n"[..], Sum::new()).unwrap();
+        zet.retain(|sum| sum > 0);
+        let containing: Vec<Vec<_>> =
+            (0..3).map(|index| zet.operands_containing(index).collect()).collect();
+        assert_eq!(containing, [vec![0], vec![0, 1], vec![1, 2]]);
+    }
+
+    #[test]
+    fn occurrences_in_each_operand_are_counted() {
+        let mut options = Options::new(OpName::Union);
+        options.count_per_file = Some(vec![]);
+        let mut zet = ZetSet::with_options(b"a 1\nb 1\na 1\n", Sum::new(), &options).unwrap();
+        zet.insert_or_update(&b"c 1\nb 1\nb 1\n"[..], Sum::new()).unwrap();
+        let counts: Vec<_> = (0..3).map(|index| zet.counts_in_operands(index)).collect();
+        assert_eq!(counts, [&[(0, 2)][..], &[(0, 1), (1, 2)], &[(1, 1)]]);
+    }
+}
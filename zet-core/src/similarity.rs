@@ -0,0 +1,111 @@
+//! `zet similarity` prints the Jaccard similarity of every pair of operands:
+//! the number of distinct lines in both, divided by the number in either. It's
+//! 1 for operands with the same set of lines, and 0 for disjoint ones. (We
+//! take two empty operands to be identical, so their similarity is 1 too.)
+//!
+//! The lines are first sorted into the regions of a Venn diagram (see the
+//! `venn` module), so each operand is read just once, and the work after that
+//! depends on the number of regions, not of lines.
+use crate::operations::{Options, OutputFormat};
+use crate::venn::{label, Venn};
+use anyhow::Result;
+
+/// The Jaccard similarity of each pair of `venn`'s operands: `matrix[i][j]` is
+/// the similarity of the `i`th and `j`th operands
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn jaccard(venn: &Venn) -> Vec<Vec<f64>> {
+    let operands = venn.operands();
+    let mut both = vec![vec![0_usize; operands]; operands];
+    for region in venn.regions() {
+        let inside: Vec<_> = (0..operands).filter(|&n| region.contains(n)).collect();
+        for &i in &inside {
+            for &j in &inside {
+                both[i][j] += region.lines.len();
+            }
+        }
+    }
+    let similarity = |i: usize, j: usize| {
+        let either = both[i][i] + both[j][j] - both[i][j];
+        if either == 0 {
+            1.0
+        } else {
+            both[i][j] as f64 / either as f64
+        }
+    };
+    (0..operands).map(|i| (0..operands).map(|j| similarity(i, j)).collect()).collect()
+}
+
+/// Write `matrix` (from `jaccard`), whose operands are called `names`. Plain
+/// output has a row of column labels (`A`, `B`, and so on, as for `venn`),
+/// then a row for each operand: its label, its similarities, and its name. For
+/// `--format csv` or `tsv`, each row starts with the operand's name, and the
+/// header row (if requested) names the operands.
+pub fn write(
+    matrix: &[Vec<f64>],
+    names: &[String],
+    options: &Options,
+    terminator: &[u8],
+    mut out: impl std::io::Write,
+) -> Result<()> {
+    let name = |n: usize| names.get(n).map_or("-", String::as_str);
+    if options.output_format == OutputFormat::Plain {
+        let width = label(matrix.len().saturating_sub(1)).len();
+        let labels: Vec<_> = (0..matrix.len()).map(|n| format!("{:<6}", label(n))).collect();
+        let labels = format!("{:width$}  {}", "", labels.join("  "));
+        out.write_all(labels.trim_end().as_bytes())?;
+        out.write_all(terminator)?;
+        for (n, row) in matrix.iter().enumerate() {
+            write!(out, "{:<width$}", label(n))?;
+            for similarity in row {
+                write!(out, "  {similarity:.4}")?;
+            }
+            write!(out, "  {}", name(n))?;
+            out.write_all(terminator)?;
+        }
+    } else {
+        let delimiter = [options.output_format.delimiter()];
+        if options.header_row {
+            out.write_all(b"file")?;
+            for n in 0..matrix.len() {
+                out.write_all(&delimiter)?;
+                options.line_format.write(name(n).as_bytes(), &mut out)?;
+            }
+            out.write_all(terminator)?;
+        }
+        for (n, row) in matrix.iter().enumerate() {
+            options.line_format.write(name(n).as_bytes(), &mut out)?;
+            for similarity in row {
+                out.write_all(&delimiter)?;
+                write!(out, "{similarity:.4}")?;
+            }
+            out.write_all(terminator)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::operations::OpName;
+
+    #[test]
+    fn similarity_is_shared_lines_over_all_lines() {
+        let rest = ["b\nc\nd\n", "x\n", ""].map(|operand| Ok(operand.as_bytes()));
+        let venn = Venn::new(&Options::new(OpName::Union), b"a\nb\nc\nb\n", rest.into_iter());
+        let matrix = jaccard(&venn.unwrap());
+        assert_eq!(matrix[0], [1.0, 0.5, 0.0, 0.0]);
+        assert_eq!(matrix[1][0], 0.5);
+        assert_eq!(matrix[3][3], 1.0);
+
+        let names = ["a.txt", "b.txt"].map(String::from);
+        let mut out = Vec::new();
+        let matrix = [vec![1.0, 0.5], vec![0.5, 1.0]];
+        write(&matrix, &names, &Options::new(OpName::Union), b"\n", &mut out).unwrap();
+        let expected = "   A       B\nA  1.0000  0.5000  a.txt\nB  0.5000  1.0000  b.txt\n";
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+}
@@ -0,0 +1,136 @@
+//! Summary statistics for `zet stats`, describing how the input's lines are
+//! distributed: how many there are, how many are distinct, and how skewed their
+//! frequencies are.
+//!
+//! * The Shannon entropy (in bits) of the line distribution is `log2(d)` if
+//!   each of `d` distinct lines occurs equally often, and smaller the more a few
+//!   lines dominate.
+//! * The Gini coefficient of the line counts is 0 if every distinct line occurs
+//!   equally often, and approaches 1 as a single line accounts for nearly all
+//!   of the input.
+//! * The percentiles are of the distinct lines' counts, using the nearest-rank
+//!   method: `p90` is the smallest count at least 90% of distinct lines have
+//!   no more than.
+use crate::operations::{Options, OutputFormat};
+use anyhow::Result;
+
+/// The statistics of a list of line counts
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Frequencies {
+    lines: u64,
+    distinct: u64,
+    entropy: f64,
+    gini: f64,
+    percentiles: [(&'static str, u64); 4],
+}
+
+impl Frequencies {
+    /// Calculate the statistics of `counts`, the number of times each distinct
+    /// line occurs
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn of(mut counts: Vec<u64>) -> Self {
+        counts.sort_unstable();
+        let lines: u64 = counts.iter().sum();
+        let distinct = counts.len() as u64;
+        let (total, n) = (lines as f64, counts.len() as f64);
+
+        let entropy = counts
+            .iter()
+            .map(|&count| count as f64 / total)
+            .map(|p| -p * p.log2())
+            .sum::<f64>()
+            .max(0.0);
+        // With the counts in ascending order, G = 2Σ(i·xᵢ)/(nΣx) − (n+1)/n,
+        // for i from 1 to n
+        let weighted: f64 =
+            counts.iter().zip(1_u32..).map(|(&count, i)| f64::from(i) * count as f64).sum();
+        let gini = if lines == 0 { 0.0 } else { 2.0 * weighted / (n * total) - (n + 1.0) / n };
+
+        let percentile = |p: u64| match counts.len() {
+            0 => 0,
+            len => {
+                let rank = (p * len as u64).div_ceil(100).max(1);
+                counts[usize::try_from(rank).unwrap_or(len) - 1]
+            }
+        };
+        let percentiles = [
+            ("p50", percentile(50)),
+            ("p90", percentile(90)),
+            ("p99", percentile(99)),
+            ("max", percentile(100)),
+        ];
+        Frequencies { lines, distinct, entropy, gini: gini.max(0.0), percentiles }
+    }
+
+    /// Write the statistics one per line, as `name: value`, or for `--format
+    /// csv` or `tsv` (or `--column-delimiter`), as two columns (preceded by a header row if requested).
+    pub(crate) fn write(
+        &self,
+        options: &Options,
+        terminator: &[u8],
+        mut out: impl std::io::Write,
+    ) -> Result<()> {
+        let separator: &[u8] = match options.output_format {
+            OutputFormat::Plain => b": ",
+            OutputFormat::Csv | OutputFormat::Tsv | OutputFormat::Delimited(_) => {
+                &[options.output_format.delimiter()]
+            }
+        };
+        let mut row = |name: &str, value: String| -> Result<()> {
+            out.write_all(name.as_bytes())?;
+            out.write_all(separator)?;
+            out.write_all(value.as_bytes())?;
+            out.write_all(terminator)?;
+            Ok(())
+        };
+        if options.header_row {
+            row("metric", "value".to_string())?;
+        }
+        row("lines", self.lines.to_string())?;
+        row("distinct", self.distinct.to_string())?;
+        row("entropy", format!("{:.4}", self.entropy))?;
+        row("gini", format!("{:.4}", self.gini))?;
+        for (name, count) in self.percentiles {
+            row(name, count.to_string())?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn equal_counts_have_maximal_entropy_and_no_inequality() {
+        let stats = Frequencies::of(vec![5, 5, 5, 5]);
+        assert_eq!((stats.lines, stats.distinct), (20, 4));
+        assert!(close(stats.entropy, 2.0), "{}", stats.entropy);
+        assert!(close(stats.gini, 0.0), "{}", stats.gini);
+        assert_eq!(stats.percentiles, [("p50", 5), ("p90", 5), ("p99", 5), ("max", 5)]);
+    }
+
+    #[test]
+    fn skewed_counts_have_lower_entropy_and_higher_gini() {
+        let stats = Frequencies::of(vec![1, 1, 1, 97]);
+        assert_eq!((stats.lines, stats.distinct), (100, 4));
+        assert!(stats.entropy < 0.25, "{}", stats.entropy);
+        // 2·(1 + 2 + 3 + 4·97)/(4·100) − 5/4
+        assert!(close(stats.gini, 0.72), "{}", stats.gini);
+        assert_eq!(stats.percentiles, [("p50", 1), ("p90", 97), ("p99", 97), ("max", 97)]);
+    }
+
+    #[test]
+    fn empty_input_has_all_zero_statistics() {
+        let stats = Frequencies::of(vec![]);
+        assert_eq!((stats.lines, stats.distinct), (0, 0));
+        assert!(close(stats.entropy, 0.0) && close(stats.gini, 0.0));
+        assert_eq!(stats.percentiles, [("p50", 0), ("p90", 0), ("p99", 0), ("max", 0)]);
+    }
+}
@@ -0,0 +1,129 @@
+//! `zet multiple --stream` prints each line as soon as it's known to occur
+//! more than once — at its second occurrence, or with `--files`, when it first
+//! turns up in a second operand — rather than after reading all the input.
+//! Lines come out in the order they're detected, and output is flushed after
+//! each one, so duplicates in a long-running pipe show up right away.
+//!
+//! Every operand, the first included, is read as it arrives, so unlike the
+//! other operations we don't see the start of the input before writing output:
+//! there's no Byte Order Mark, and lines end with `\n`.
+use crate::counted;
+use crate::operations::{write_header, OpName, Options};
+use crate::set::{output_info, LaterOperand};
+use anyhow::{bail, Result};
+use fxhash::FxBuildHasher;
+use std::collections::HashMap;
+
+/// Print the lines of `operands` that occur more than once (or with
+/// `OpName::MultipleByFile`, in more than one operand) as soon as they do.
+/// `options.op` must be `OpName::Multiple` or `OpName::MultipleByFile`.
+pub fn multiple<O: LaterOperand>(
+    options: &Options,
+    operands: impl Iterator<Item = Result<O>>,
+    mut out: impl std::io::Write,
+) -> Result<()> {
+    let by_file = match options.op {
+        OpName::Multiple => false,
+        OpName::MultipleByFile => true,
+        op => bail!("Only the multiple operation can stream its output, not {}", op.name()),
+    };
+    if options.key.is_command() {
+        bail!("Can't stream output when a command computes the keys of all of an operand at once");
+    }
+    let (_, line_terminator) = output_info(b"", &options.framing);
+    write_header(&[], options, line_terminator, &mut out)?;
+    let mut seen = Seen { sightings: HashMap::default(), by_file, line_terminator, written: 0 };
+    for (operand, next) in options.interrupt.cut_short(options.tally.counting(operands)).enumerate()
+    {
+        let mut result = Ok(());
+        next?.for_byte_line_while(|line| {
+            result = seen.add(options, operand, line, &mut out);
+            result.is_ok()
+        })?;
+        result?;
+    }
+    options.tally.wrote(seen.written);
+    out.flush()?;
+    Ok(())
+}
+
+/// What we've seen of a key so far
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Sighting {
+    /// Seen once (or with `--files`, in just one operand), in `operand`
+    Once { operand: usize },
+    /// Seen again, and its line written
+    Written,
+}
+
+/// The keys seen so far
+struct Seen<'t> {
+    sightings: HashMap<Vec<u8>, Sighting, FxBuildHasher>,
+    by_file: bool,
+    line_terminator: &'t [u8],
+    written: u64,
+}
+
+impl Seen<'_> {
+    /// Note that `line` occurs in `operand`, writing it if that makes it
+    /// a multiple
+    fn add(
+        &mut self,
+        options: &Options,
+        operand: usize,
+        line: &[u8],
+        out: &mut impl std::io::Write,
+    ) -> Result<()> {
+        let (weight, line) =
+            if options.counted_input { counted::weight_of(line)? } else { (1, line) };
+        if weight == 0 {
+            return Ok(());
+        }
+        let key = options.key.of(line);
+        match self.sightings.get_mut(key.as_ref()) {
+            Some(Sighting::Written) => return Ok(()),
+            Some(sighting @ Sighting::Once { .. }) => {
+                if self.by_file && *sighting == (Sighting::Once { operand }) {
+                    return Ok(());
+                }
+                *sighting = Sighting::Written;
+            }
+            None if !self.by_file && weight > 1 => {
+                self.sightings.insert(key.into_owned(), Sighting::Written);
+            }
+            None => {
+                self.sightings.insert(key.into_owned(), Sighting::Once { operand });
+                return Ok(());
+            }
+        }
+        options.write_line(line, out)?;
+        out.write_all(self.line_terminator)?;
+        out.flush()?;
+        self.written += 1;
+        Ok(())
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn streamed(op: OpName, operands: &[&str]) -> String {
+        let operands = operands.iter().map(|operand| Ok(operand.as_bytes()));
+        let mut out = Vec::new();
+        multiple(&Options::new(op), operands, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn lines_are_written_in_the_order_they_turn_out_to_be_multiples() {
+        assert_eq!(streamed(OpName::Multiple, &["a\nb\nc\nb\n", "c\na\nb\nd\n"]), "b\nc\na\n");
+        assert_eq!(
+            streamed(OpName::MultipleByFile, &["a\nb\nc\nb\n", "c\na\nb\nd\n"]),
+            "c\na\nb\n"
+        );
+        assert!(multiple(&Options::new(OpName::Union), [Ok(&b"a"[..])].into_iter(), Vec::new())
+            .is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! Support for `--summary`, which prints a line like `zet: 12,345 lines out of
+//! 1,203,400 read (7 files)` on stderr after the operation.
+//!
+//! Each run keeps its own tally, in the `Tally` of its `Options`, so runs don't
+//! see each other's counts: `operations::calculate` starts a fresh tally for
+//! each call, and returns the `Stats` it tallied. The tally is updated once per
+//! operand (or once per output), not once per line.
+//!
+//! With `--stats json`, the `zet` command writes these statistics in more
+//! detail — the lines read from each operand, the number of distinct lines,
+//! and the time taken — as a JSON object, for programs to read.
+use crate::set::LaterOperand;
+use anyhow::Result;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// What a run read and wrote
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of lines read from each operand, in the order read (an
+    /// operand read twice appears twice)
+    pub read_by_operand: Vec<u64>,
+    /// The number of lines written
+    pub lines_written: u64,
+    /// The number of distinct lines in the input (or, for operations that only
+    /// look for the lines of the first operand, in it), if the operation
+    /// counted them
+    pub distinct_lines: Option<u64>,
+}
+
+impl Stats {
+    /// The number of lines read, from all the operands
+    #[must_use]
+    pub fn lines_read(&self) -> u64 {
+        self.read_by_operand.iter().sum()
+    }
+}
+
+/// Where a run tallies its `Stats` as it goes. Clones share the tally; the
+/// default is a tally with nothing in it.
+#[derive(Clone, Debug, Default)]
+pub struct Tally(Arc<Mutex<Stats>>);
+
+impl Tally {
+    /// The statistics tallied so far
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        locked(&self.0).clone()
+    }
+
+    /// Note that we've read `lines` more lines of input: those of another
+    /// operand
+    pub(crate) fn read(&self, lines: u64) {
+        locked(&self.0).read_by_operand.push(lines);
+    }
+
+    /// Note that we've written `lines` more lines of output
+    pub(crate) fn wrote(&self, lines: u64) {
+        locked(&self.0).lines_written += lines;
+    }
+
+    /// Note that the input held `lines` distinct lines
+    pub(crate) fn distinct(&self, lines: u64) {
+        locked(&self.0).distinct_lines = Some(lines);
+    }
+
+    /// Wrap each of `operands` so that reading it adds its lines to the tally
+    pub(crate) fn counting<O: LaterOperand, I: Iterator<Item = Result<O>>>(
+        &self,
+        operands: I,
+    ) -> std::iter::Map<I, impl FnMut(Result<O>) -> Result<Counted<O>>> {
+        let tally = self.clone();
+        operands
+            .map(move |operand| operand.map(|operand| Counted { operand, tally: tally.clone() }))
+    }
+}
+
+/// An operand that adds the lines read from it to `tally`
+pub(crate) struct Counted<O> {
+    operand: O,
+    tally: Tally,
+}
+
+impl<O: LaterOperand> LaterOperand for Counted<O> {
+    fn for_byte_line(self, mut for_each_line: impl FnMut(&[u8])) -> Result<()> {
+        let mut lines = 0;
+        let read = self.operand.for_byte_line(|line| {
+            lines += 1;
+            for_each_line(line);
+        });
+        self.tally.read(lines);
+        read
+    }
+
+    fn for_byte_line_while(self, mut for_each_line: impl FnMut(&[u8]) -> bool) -> Result<()> {
+        let mut lines = 0;
+        let read = self.operand.for_byte_line_while(|line| {
+            lines += 1;
+            for_each_line(line)
+        });
+        self.tally.read(lines);
+        read
+    }
+}
+
+/// The summary of a run that read `files` operands, with statistics `stats`:
+/// the number of lines written (if `wrote_lines` is set) and read
+#[must_use]
+pub fn message(stats: &Stats, files: usize, wrote_lines: bool) -> String {
+    let read = stats.lines_read();
+    let files = format!("{} {}", with_commas(files as u64), plural(files as u64, "file"));
+    if wrote_lines {
+        let written = stats.lines_written;
+        let lines = plural(written, "line");
+        format!("zet: {} {lines} out of {} read ({files})", with_commas(written), with_commas(read))
+    } else {
+        format!("zet: {} {} read ({files})", with_commas(read), plural(read, "line"))
+    }
+}
+
+/// The contents of `mutex` — which can't be left half-updated, so if a
+/// thread panicked while holding it, it's still fine
+fn locked<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn plural(n: u64, noun: &str) -> String {
+    if n == 1 {
+        noun.to_string()
+    } else {
+        format!("{noun}s")
+    }
+}
+
+/// `n` with commas separating each group of three digits
+fn with_commas(n: u64) -> String {
+    let digits = n.to_string();
+    let mut result = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            result.push(',');
+        }
+        result.push(digit);
+    }
+    result
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn commas_separate_groups_of_three_digits() {
+        let cases = [(0, "0"), (999, "999"), (1000, "1,000"), (1203400, "1,203,400")];
+        for (n, expected) in cases {
+            assert_eq!(with_commas(n), expected);
+        }
+    }
+}
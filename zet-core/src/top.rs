@@ -0,0 +1,233 @@
+//! Approximate heavy hitters for `zet top --approx K`. Counting every distinct
+//! line exactly can take more memory than we have, but the most frequent lines
+//! can be found with just `K` counters, using the Space-Saving algorithm of
+//! Metwally, Agrawal, and El Abbadi (2005):
+//!
+//! * A line that already has a counter increments it.
+//! * Otherwise, if there's a free counter, the line gets it, with count 1.
+//! * Otherwise the line takes over the counter with the smallest count `c`,
+//!   which becomes `c + 1`, with `c` recorded as its possible overcount.
+//!
+//! Every line occurring more than `n / K` times in `n` input lines is sure to
+//! have a counter at the end, and no count is more than its overcount too high.
+//! We keep the counters in a min-heap, so finding the smallest is cheap.
+//!
+//! The counts are overestimates: with more distinct lines than counters, even a
+//! line occurring once can end up with a large count. So we print two counts
+//! for each line: the estimate, which is at least the true count, and the
+//! estimate less its overcount, which is at most the true count. When the two
+//! are equal, the count is exact.
+//!
+//! With `--counted-input`, a line with count `w` adds `w` rather than 1, and a
+//! line taking over the smallest counter makes it `c + w`.
+use crate::counted;
+use crate::operations::{self, CountColumns, Options};
+use crate::set::{self, LaterOperand};
+use anyhow::Result;
+use fxhash::FxBuildHasher;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+
+/// Print (at most) the `options.top` most frequent lines of the input, as
+/// estimated with `counters` counters, with their estimated counts and the
+/// least their true counts can be.
+pub(crate) fn approximate<O: LaterOperand>(
+    options: &Options,
+    counters: NonZeroUsize,
+    first_operand: &[u8],
+    rest: impl Iterator<Item = Result<O>>,
+    mut out: impl std::io::Write,
+) -> Result<()> {
+    let (bom, line_terminator) = set::output_info(first_operand, &options.framing);
+    let mut summary = SpaceSaving::new(counters);
+    let mut added = Ok(());
+    let mut add = |line: &[u8]| {
+        if added.is_ok() {
+            added = summary.add_line(options, line);
+        }
+    };
+    set::for_each_record(&first_operand[bom.len()..], options, &mut add);
+    for operand in options.interrupt.cut_short(rest) {
+        operand?.for_byte_line(&mut add)?;
+    }
+    added?;
+
+    let mut top = summary.by_count();
+    top.truncate(options.top.unwrap_or(usize::MAX));
+    if top.is_empty() && !options.header_row {
+        return Ok(());
+    }
+    let columns = CountColumns::new(options, top.iter().map(|counter| counter.count));
+    out.write_all(options.line_format.bom(bom))?;
+    operations::write_header(&["count", "at_least"], options, line_terminator, &mut out)?;
+    options.tally.wrote(top.len() as u64);
+    for counter in top {
+        let write_counts = |out: &mut _| {
+            columns.write(counter.count, out)?;
+            columns.write(counter.count - counter.overcount, out)
+        };
+        columns.write_row(&counter.line, options, line_terminator, &mut out, write_counts)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// A line being counted: `count` may exceed the true count by up to `overcount`.
+#[derive(Clone, Debug)]
+struct Counter {
+    key: Vec<u8>,
+    line: Vec<u8>,
+    count: u64,
+    overcount: u64,
+}
+
+/// The Space-Saving summary: `counters` holds at most `capacity` counters;
+/// `heap` is a min-heap (by count) of indexes into `counters`, with
+/// `position[i]` the position of counter `i` in `heap`; and `index` maps each
+/// counted key to its counter.
+struct SpaceSaving {
+    capacity: usize,
+    counters: Vec<Counter>,
+    heap: Vec<usize>,
+    position: Vec<usize>,
+    index: HashMap<Vec<u8>, usize, FxBuildHasher>,
+}
+
+impl SpaceSaving {
+    fn new(capacity: NonZeroUsize) -> Self {
+        SpaceSaving {
+            capacity: capacity.get(),
+            counters: Vec::new(),
+            heap: Vec::new(),
+            position: Vec::new(),
+            index: HashMap::default(),
+        }
+    }
+
+    /// Count `line`, which with `options.counted_input` stands for as many
+    /// occurrences as the count it starts with (so none, if that's zero)
+    fn add_line(&mut self, options: &Options, line: &[u8]) -> Result<()> {
+        let (weight, line) =
+            if options.counted_input { counted::weight_of(line)? } else { (1, line) };
+        if weight > 0 {
+            self.add(&options.key.of(line), line, weight.into());
+        }
+        Ok(())
+    }
+
+    /// Count `weight` occurrences of `key` (taken from `line`)
+    fn add(&mut self, key: &[u8], line: &[u8], weight: u64) {
+        if let Some(&i) = self.index.get(key) {
+            self.counters[i].count += weight;
+            self.sift_down(self.position[i]);
+        } else if self.counters.len() < self.capacity {
+            let i = self.counters.len();
+            let counter =
+                Counter { key: key.to_vec(), line: line.to_vec(), count: weight, overcount: 0 };
+            self.counters.push(counter);
+            self.index.insert(key.to_vec(), i);
+            self.heap.push(i);
+            self.position.push(i);
+            self.sift_up(i);
+        } else {
+            let i = self.heap[0];
+            let smallest = &mut self.counters[i];
+            let evicted = std::mem::replace(&mut smallest.key, key.to_vec());
+            smallest.line = line.to_vec();
+            smallest.overcount = smallest.count;
+            smallest.count += weight;
+            self.index.remove(&evicted);
+            self.index.insert(key.to_vec(), i);
+            self.sift_down(0);
+        }
+    }
+
+    /// The counters, most frequent first (and in order of creation for equal
+    /// counts)
+    fn by_count(self) -> Vec<Counter> {
+        let mut counters = self.counters;
+        counters.sort_by_key(|counter| std::cmp::Reverse(counter.count));
+        counters
+    }
+
+    fn count_at(&self, position: usize) -> u64 {
+        self.counters[self.heap[position]].count
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        self.position[self.heap[a]] = a;
+        self.position[self.heap[b]] = b;
+    }
+
+    fn sift_up(&mut self, mut position: usize) {
+        while position > 0 {
+            let parent = (position - 1) / 2;
+            if self.count_at(parent) <= self.count_at(position) {
+                break;
+            }
+            self.swap(parent, position);
+            position = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut position: usize) {
+        loop {
+            let mut smallest = position;
+            for child in [2 * position + 1, 2 * position + 2] {
+                if child < self.heap.len() && self.count_at(child) < self.count_at(smallest) {
+                    smallest = child;
+                }
+            }
+            if smallest == position {
+                break;
+            }
+            self.swap(smallest, position);
+            position = smallest;
+        }
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn summarize(capacity: usize, lines: &[&str]) -> Vec<(String, u64, u64)> {
+        let mut summary = SpaceSaving::new(NonZeroUsize::new(capacity).unwrap());
+        for line in lines {
+            summary.add(line.as_bytes(), line.as_bytes(), 1);
+        }
+        let counted = summary.by_count().into_iter();
+        counted.map(|c| (String::from_utf8(c.line).unwrap(), c.count, c.overcount)).collect()
+    }
+
+    #[test]
+    fn counts_are_exact_when_every_line_has_a_counter() {
+        let lines = ["a", "b", "a", "c", "b", "a"];
+        let expected = [("a", 3, 0), ("b", 2, 0), ("c", 1, 0)];
+        let expected: Vec<_> = expected.iter().map(|&(l, c, o)| (l.to_string(), c, o)).collect();
+        assert_eq!(summarize(3, &lines), expected);
+    }
+
+    #[test]
+    fn heavy_hitters_survive_a_stream_of_rare_lines() {
+        let rare: Vec<String> = (0..1000).map(|n| format!("rare {n}")).collect();
+        let mut lines = Vec::new();
+        for (n, line) in rare.iter().enumerate() {
+            lines.push(line.as_str());
+            if n % 3 == 0 {
+                lines.push("often");
+            }
+            if n % 5 == 0 {
+                lines.push("sometimes");
+            }
+        }
+        let top = summarize(10, &lines);
+        let truth = [("often", 334), ("sometimes", 200)];
+        for ((line, count, overcount), (expected, true_count)) in top.iter().zip(truth) {
+            assert_eq!(line, expected);
+            assert!(count - overcount <= true_count && true_count <= *count);
+        }
+    }
+}
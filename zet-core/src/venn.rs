@@ -0,0 +1,310 @@
+//! Sorts the lines of the operands into the regions of a Venn diagram, for
+//! `zet venn` and `zet tui` (and `zet union --format matrix`, which shows the
+//! operands each line occurs in, and `--format comm`, which is like `comm`). Each distinct line belongs to exactly one region:
+//! the one for the set of operands it occurs in. With operands `A`, `B`, and
+//! `C`, a line in `A` and `C` but not `B` is in the `A C` region — which is
+//! what `zet diff` would print for `A C B`, less the lines also in `B`.
+//!
+//! Operands are labelled `A`, `B`, `C`, and so on (then `27`, `28`, … past
+//! `Z`), and each region by the labels of the operands it lies inside.
+//!
+//! A line's bookkeeping value is a bit mask of the operands it occurs in, so we
+//! can handle at most 64 operands.
+use crate::operations::{Bookkeeping, Options};
+use crate::set::{LaterOperand, ZetSet};
+use crate::summary::Tally;
+use anyhow::{bail, Result};
+use fxhash::FxBuildHasher;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// The most operands a `Venn` can sort lines for
+pub const MAX_OPERANDS: usize = u64::BITS as usize;
+
+/// The distinct lines of the operands, sorted into regions
+pub struct Venn<'data> {
+    set: ZetSet<'data, Membership>,
+    operands: usize,
+    tally: Tally,
+}
+
+/// The lines in one region of a Venn diagram: those that occur in exactly the
+/// operands whose bits are set in `members` (bit 0 for the first operand)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region<'a> {
+    pub members: u64,
+    pub lines: Vec<&'a [u8]>,
+}
+
+impl Region<'_> {
+    /// Does the region lie inside the `n`th operand (counting from 0)?
+    #[must_use]
+    pub fn contains(&self, n: usize) -> bool {
+        n < MAX_OPERANDS && self.members & (1 << n) != 0
+    }
+
+    /// The labels of the operands the region lies inside, like `A C`
+    #[must_use]
+    pub fn label(&self) -> String {
+        let inside = (0..MAX_OPERANDS).filter(|&n| self.contains(n));
+        inside.map(label).collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// The label of the `n`th operand (counting from 0)
+#[must_use]
+pub fn label(n: usize) -> String {
+    match u8::try_from(n) {
+        Ok(n) if n < 26 => char::from(b'A' + n).to_string(),
+        _ => (n + 1).to_string(),
+    }
+}
+
+impl<'data> Venn<'data> {
+    /// Sort the lines of `first_operand` and `rest`, compared as `options`
+    /// says. (The operation given by `options.op` is ignored.)
+    pub fn new<O: LaterOperand>(
+        options: &Options,
+        first_operand: &'data [u8],
+        rest: impl Iterator<Item = Result<O>>,
+    ) -> Result<Self> {
+        let mut item = Membership::new();
+        let mut set = ZetSet::with_options(first_operand, item, options)?;
+        let mut operands = 1;
+        for operand in options.interrupt.cut_short(options.tally.counting(rest)) {
+            if operands == MAX_OPERANDS {
+                bail!("Can't sort the lines of more than {MAX_OPERANDS} files into regions");
+            }
+            item.next_file();
+            set.insert_or_update(operand?, item)?;
+            operands += 1;
+        }
+        Ok(Venn { set, operands, tally: options.tally.clone() })
+    }
+
+    /// The number of operands
+    #[must_use]
+    pub fn operands(&self) -> usize {
+        self.operands
+    }
+
+    /// The number of distinct lines in each operand
+    #[must_use]
+    pub fn cardinalities(&self) -> Vec<usize> {
+        let mut cardinalities = vec![0; self.operands];
+        for (_, Membership(members)) in self.set.iter() {
+            for (n, cardinality) in cardinalities.iter_mut().enumerate() {
+                *cardinality += usize::from(members & (1 << n) != 0);
+            }
+        }
+        cardinalities
+    }
+
+    /// The regions that have lines in them, those inside the fewest operands
+    /// first, with each region's lines in order of first appearance
+    #[must_use]
+    pub fn regions(&self) -> Vec<Region<'_>> {
+        let mut regions: Vec<Region> = Vec::new();
+        let mut index = HashMap::<u64, usize, FxBuildHasher>::default();
+        for (line, &Membership(members)) in self.set.iter() {
+            let i = *index.entry(members).or_insert_with(|| {
+                regions.push(Region { members, lines: Vec::new() });
+                regions.len() - 1
+            });
+            regions[i].lines.push(line);
+        }
+        // `reverse_bits` puts the first operand's bit first, so that (say) the
+        // `A B` region comes before the `A C` region
+        regions.sort_by_key(|region| {
+            (region.members.count_ones(), Reverse(region.members.reverse_bits()))
+        });
+        regions
+    }
+
+    /// The Byte Order Mark (if any) and line terminator to use when writing
+    /// lines, as for the output of `calculate`
+    #[must_use]
+    pub fn output_info(&self) -> (&'static [u8], &[u8]) {
+        (self.set.bom, &self.set.line_terminator)
+    }
+
+    /// Write the lines of `region` to `out`, after the Byte Order Mark (if
+    /// any), as a file of their own
+    pub fn write_region(&self, region: &Region, mut out: impl std::io::Write) -> Result<()> {
+        out.write_all(self.set.bom)?;
+        self.write_lines(region, &mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Write each region to `out`, under a heading naming the operands it lies
+    /// inside, like `== only x.txt ==` or `== x.txt ∩ z.txt ==`, where `names`
+    /// are the names of the operands
+    pub fn write_sections(&self, names: &[String], mut out: impl std::io::Write) -> Result<()> {
+        out.write_all(self.set.bom)?;
+        for region in self.regions() {
+            let inside = names.iter().enumerate().filter(|&(n, _)| region.contains(n));
+            let inside: Vec<_> = inside.map(|(_, name)| name.as_str()).collect();
+            let heading = match inside.as_slice() {
+                [name] => format!("== only {name} =="),
+                inside => format!("== {} ==", inside.join(" ∩ ")),
+            };
+            out.write_all(heading.as_bytes())?;
+            out.write_all(&self.set.line_terminator)?;
+            self.write_lines(&region, &mut out)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Write a row for each distinct line, in order of first appearance: a
+    /// column for each operand, headed by its name (from `names`), marked `x`
+    /// if the line occurs in the operand and `.` if not, then the line itself
+    pub fn write_matrix(&self, names: &[String], mut out: impl std::io::Write) -> Result<()> {
+        let names = &names[..self.operands.min(names.len())];
+        let widths: Vec<_> = names.iter().map(|name| name.chars().count()).collect();
+        out.write_all(self.set.bom)?;
+        for name in names {
+            write!(out, "{name} ")?;
+        }
+        out.write_all(b"line")?;
+        out.write_all(&self.set.line_terminator)?;
+        for (line, &Membership(members)) in self.set.iter() {
+            for (n, &width) in widths.iter().enumerate() {
+                let mark = if members & (1 << n) == 0 { '.' } else { 'x' };
+                write!(out, "{mark:width$} ")?;
+            }
+            out.write_all(line)?;
+            out.write_all(&self.set.line_terminator)?;
+        }
+        self.tally.wrote(self.set.len() as u64);
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Write each distinct line, in order of first appearance, like `comm`
+    /// does for two sorted files: lines only in the first operand as they
+    /// are, lines only in the second after a tab, and lines in both after two
+    /// tabs. It's an error if there aren't exactly two operands.
+    pub fn write_comm(&self, mut out: impl std::io::Write) -> Result<()> {
+        if self.operands != 2 {
+            bail!("comm-style output needs exactly two files, not {}", self.operands);
+        }
+        out.write_all(self.set.bom)?;
+        for (line, &Membership(members)) in self.set.iter() {
+            let indent: &[u8] = match members {
+                0b01 => b"",
+                0b10 => b"\t",
+                _ => b"\t\t",
+            };
+            out.write_all(indent)?;
+            out.write_all(line)?;
+            out.write_all(&self.set.line_terminator)?;
+        }
+        self.tally.wrote(self.set.len() as u64);
+        out.flush()?;
+        Ok(())
+    }
+
+    fn write_lines(&self, region: &Region, out: &mut impl std::io::Write) -> Result<()> {
+        for line in &region.lines {
+            out.write_all(line)?;
+            out.write_all(&self.set.line_terminator)?;
+        }
+        self.tally.wrote(region.lines.len() as u64);
+        Ok(())
+    }
+}
+
+/// The bookkeeping value for each line is a bit mask of the operands it's
+/// been seen in. For the item passed to the `ZetSet`, just the current
+/// operand's bit is set. (The `expr` module uses it too.)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct Membership(pub(crate) u64);
+impl Bookkeeping for Membership {
+    fn new() -> Self {
+        Membership(1)
+    }
+    fn next_file(&mut self) {
+        self.0 <<= 1;
+    }
+    fn update_with(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+    fn retention_value(self) -> u32 {
+        self.0.count_ones()
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::operations::OpName;
+
+    fn venn<'a>(operands: &[&'a str]) -> Venn<'a> {
+        let rest = operands[1..].iter().map(|operand| Ok(operand.as_bytes()));
+        Venn::new(&Options::new(OpName::Union), operands[0].as_bytes(), rest).unwrap()
+    }
+
+    #[test]
+    fn each_line_is_in_the_region_for_the_operands_it_occurs_in() {
+        let venn = venn(&["a\nab\nabc\na\n", "ab\nb\nabc\n", "abc\nc\nc\n"]);
+        assert_eq!(venn.operands(), 3);
+        assert_eq!(venn.cardinalities(), [3, 3, 2]);
+        let regions: Vec<_> = venn
+            .regions()
+            .into_iter()
+            .map(|region| (region.members, region.lines.iter().map(|l| l.to_vec()).collect()))
+            .collect();
+        let expected: Vec<(u64, Vec<Vec<u8>>)> = vec![
+            (0b001, vec![b"a".to_vec()]),
+            (0b010, vec![b"b".to_vec()]),
+            (0b100, vec![b"c".to_vec()]),
+            (0b011, vec![b"ab".to_vec()]),
+            (0b111, vec![b"abc".to_vec()]),
+        ];
+        assert_eq!(regions, expected);
+    }
+
+    #[test]
+    fn regions_know_which_operands_they_lie_inside() {
+        let region = Region { members: 0b101, lines: vec![] };
+        let inside: Vec<_> = (0..4).map(|n| region.contains(n)).collect();
+        assert_eq!(inside, [true, false, true, false]);
+        assert_eq!(region.label(), "A C");
+    }
+
+    #[test]
+    fn operands_past_z_are_labelled_by_number() {
+        assert_eq!([label(0), label(25), label(26)], ["A", "Z", "27"]);
+    }
+
+    #[test]
+    fn sections_are_headed_by_the_operands_they_lie_inside() {
+        let venn = venn(&["a\nab\n", "ab\nb\n"]);
+        let names = ["x.txt".to_string(), "y.txt".to_string()];
+        let mut out = Vec::new();
+        venn.write_sections(&names, &mut out).unwrap();
+        let expected = "== only x.txt ==\na\n== only y.txt ==\nb\n== x.txt ∩ y.txt ==\nab\n";
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn matrix_marks_the_operands_each_line_occurs_in() {
+        let venn = venn(&["a\nab\n", "ab\nb\n"]);
+        let names = ["x.txt".to_string(), "-".to_string()];
+        let mut out = Vec::new();
+        venn.write_matrix(&names, &mut out).unwrap();
+        let expected = "x.txt - line\nx     . a\nx     x ab\n.     x b\n";
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn comm_output_indents_lines_by_the_operands_they_occur_in() {
+        let mut out = Vec::new();
+        venn(&["a\nab\n", "b\nab\n"]).write_comm(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "a\n\t\tab\n\tb\n");
+        assert!(venn(&["a\n", "b\n", "c\n"]).write_comm(Vec::new()).is_err());
+    }
+}